@@ -0,0 +1,73 @@
+#include "inc/mesh.hlsl"
+#include "inc/bindless.hlsl"
+
+[[vk::binding(0)]] RWByteAddressBuffer output_vertices;
+[[vk::binding(1)]] StructuredBuffer<row_major float3x4> skin_matrices;
+[[vk::binding(2)]] StructuredBuffer<float> morph_weights;
+[[vk::binding(3)]] cbuffer _ {
+    uint vertex_count;
+    uint source_core_offset;
+    uint source_tangent_offset;
+    uint joints_offset;
+    uint weights_offset;
+    uint morph_target_count;
+    uint morph_position_deltas_offset;
+    uint morph_normal_deltas_offset;
+    uint output_core_offset;
+    uint output_prev_pos_offset;
+    uint output_tangent_offset;
+};
+
+[numthreads(64, 1, 1)]
+void main(uint vid: SV_DispatchThreadID) {
+    if (vid >= vertex_count) {
+        return;
+    }
+
+    Vertex v = unpack_vertex(VertexPacked(asfloat(vertices.Load4(vid * sizeof(float4) + source_core_offset))));
+    const float4 tangent = asfloat(vertices.Load4(vid * sizeof(float4) + source_tangent_offset));
+
+    // Blend shapes first, in the space of the rest pose.
+    for (uint target = 0; target < morph_target_count; ++target) {
+        const float weight = morph_weights[target];
+        if (weight != 0.0) {
+            const uint delta_idx = vid * morph_target_count + target;
+            v.position += weight * asfloat(vertices.Load3(delta_idx * sizeof(float3) + morph_position_deltas_offset));
+            v.normal += weight * asfloat(vertices.Load3(delta_idx * sizeof(float3) + morph_normal_deltas_offset));
+        }
+    }
+
+    float3x4 xform = float3x4(
+        1, 0, 0, 0,
+        0, 1, 0, 0,
+        0, 0, 1, 0
+    );
+
+    if (joints_offset != 0) {
+        // Four 16-bit joint indices
+        const uint2 joints_packed = vertices.Load2(vid * sizeof(uint2) + joints_offset);
+        const uint4 joints = uint4(
+            joints_packed.x & 0xffff, joints_packed.x >> 16,
+            joints_packed.y & 0xffff, joints_packed.y >> 16
+        );
+        const float4 weights = asfloat(vertices.Load4(vid * sizeof(float4) + weights_offset));
+
+        xform =
+            skin_matrices[joints.x] * weights.x
+            + skin_matrices[joints.y] * weights.y
+            + skin_matrices[joints.z] * weights.z
+            + skin_matrices[joints.w] * weights.w;
+    }
+
+    // The current position becomes the previous one, for motion vectors.
+    const float3 prev_pos = unpack_vertex(VertexPacked(asfloat(output_vertices.Load4(vid * sizeof(float4) + output_core_offset)))).position;
+    output_vertices.Store3(vid * sizeof(float4) + output_prev_pos_offset, asuint(prev_pos));
+
+    Vertex res;
+    res.position = mul(xform, float4(v.position, 1.0));
+    res.normal = normalize(mul((float3x3)xform, v.normal));
+    output_vertices.Store4(vid * sizeof(float4) + output_core_offset, asuint(pack_vertex(res).data0));
+
+    const float3 deformed_tangent = normalize(mul((float3x3)xform, tangent.xyz));
+    output_vertices.Store4(vid * sizeof(float4) + output_tangent_offset, asuint(float4(deformed_tangent, tangent.w)));
+}
@@ -309,6 +309,164 @@ struct SpecularBrdf {
 	}
 };
 
+// GGX with separate roughness along the tangent (x) and bitangent (y) axes.
+// Height-correlated masking-shadowing, and VNDF sampling only.
+struct AnisotropicSpecularBrdf {
+    float2 roughness;
+    float3 albedo;
+
+    static float ggx_ndf(float2 alpha, float3 m) {
+        const float3 s = float3(m.x / alpha.x, m.y / alpha.y, m.z);
+        const float d = dot(s, s);
+        return 1.0 / (M_PI * alpha.x * alpha.y * d * d);
+    }
+
+    static float smith_lambda(float2 alpha, float3 w) {
+        const float a2_tan2 = (square(alpha.x * w.x) + square(alpha.y * w.y)) / square(w.z);
+        return 0.5 * (sqrt(1.0 + a2_tan2) - 1.0);
+    }
+
+    float pdf_vn(float3 wo, float3 h) {
+        const float g1 = 1.0 / (1.0 + smith_lambda(roughness, wo));
+        return g1 * ggx_ndf(roughness, h) * max(0.f, dot(wo, h)) / wo.z;
+    }
+
+    // Same as `SpecularBrdf::sample_vndf`, but with distinct alphas.
+    NdfSample sample_vndf(float3 wo, float2 urand) {
+        const float alpha_x = roughness.x, alpha_y = roughness.y;
+
+        float3 Vh = normalize(float3(alpha_x * wo.x, alpha_y * wo.y, wo.z));
+
+        float3 T1 = select((Vh.z < 0.9999f), normalize(cross(float3(0, 0, 1), Vh)), float3(1, 0, 0));
+        float3 T2 = cross(Vh, T1);
+
+        float r = sqrt(urand.x);
+        float phi = (2.f * M_PI) * urand.y;
+        float t1 = r * cos(phi);
+        float t2 = r * sin(phi);
+        float s = 0.5f * (1.f + Vh.z);
+        t2 = (1.f - s) * sqrt(1.f - t1 * t1) + s * t2;
+
+        float3 Nh = t1 * T1 + t2 * T2 + sqrt(max(0.f, 1.f - t1 * t1 - t2 * t2)) * Vh;
+
+        NdfSample res;
+        res.m = normalize(float3(alpha_x * Nh.x, alpha_y * Nh.y, max(0.f, Nh.z)));
+        res.pdf = pdf_vn(wo, res.m);
+        return res;
+    }
+
+    BrdfValue evaluate(float3 wo, float3 wi) {
+        if (wi.z <= 0.0 || wo.z <= 0.0) {
+            return BrdfValue::invalid();
+        }
+
+        const float3 m = normalize(wo + wi);
+        const float jacobian = 1.0 / (4.0 * dot(wi, m));
+        const float3 fresnel = eval_fresnel_schlick(albedo, 1.0, dot(m, wi));
+
+        const float lambda_wo = smith_lambda(roughness, wo);
+        const float lambda_wi = smith_lambda(roughness, wi);
+        const float g = 1.0 / (1.0 + lambda_wo + lambda_wi);
+        const float g_over_g1_wo = (1.0 + lambda_wo) * g;
+
+        BrdfValue res;
+        res.pdf = pdf_vn(wo, m) * jacobian / wi.z;
+        res.transmission_fraction = 1.0.xxx - fresnel;
+        res.value_over_pdf = fresnel * g_over_g1_wo;
+        res.value = fresnel * g * ggx_ndf(roughness, m) / (4 * wo.z * wi.z);
+        return res;
+    }
+
+    BrdfSample sample(float3 wo, float2 urand) {
+        NdfSample ndf_sample = sample_vndf(wo, urand);
+        const float3 wi = reflect(-wo, ndf_sample.m);
+
+        if (ndf_sample.m.z <= BRDF_SAMPLING_MIN_COS || wi.z <= BRDF_SAMPLING_MIN_COS || wo.z <= BRDF_SAMPLING_MIN_COS) {
+            return BrdfSample::invalid();
+        }
+
+        const BrdfValue value = evaluate(wo, wi);
+
+        BrdfSample res;
+        res.pdf = value.pdf;
+        res.value = value.value;
+        res.value_over_pdf = value.value_over_pdf;
+        res.transmission_fraction = value.transmission_fraction;
+        res.wi = wi;
+        res.approx_roughness = sqrt(roughness.x * roughness.y);
+        return res;
+    }
+};
+
+// "Charlie" sheen from Estevez and Kulla, "Production Friendly Microfacet Sheen BRDF",
+// with the visibility term of Neubelt and Pettineo. Sampled with a cosine lobe.
+struct SheenBrdf {
+    float3 color;
+
+    // Alpha; clamped to where the fits below are valid.
+    float roughness;
+
+    static float charlie_ndf(float alpha, float cos_theta) {
+        const float inv_alpha = 1.0 / alpha;
+        const float sin2_theta = max(0.0, 1.0 - cos_theta * cos_theta);
+        return (2.0 + inv_alpha) * pow(sin2_theta, 0.5 * inv_alpha) / M_TAU;
+    }
+
+    static float neubelt_visibility(float ndotl, float ndotv) {
+        return 1.0 / (4.0 * (ndotl + ndotv - ndotl * ndotv));
+    }
+
+    float alpha() {
+        return clamp(roughness, 0.07, 1.0);
+    }
+
+    // Directional albedo for a white sheen. Fit to a numerical integration of the lobe
+    // (RMSE: 0.008), parameterized by the perceptual roughness.
+    float directional_albedo(float ndotv) {
+        const float r = sqrt(alpha());
+        const float a = 1.9631 - 3.0528 * r + 1.7774 * r * r;
+        const float b = 6.8042 - 11.1251 * r + 5.2736 * r * r;
+        const float c = -0.0823 + 0.277 * r - 0.3287 * r * r;
+        return saturate(a * exp(-b * ndotv) + c);
+    }
+
+    BrdfValue evaluate(float3 wo, float3 wi) {
+        if (wi.z <= 0.0 || wo.z <= 0.0) {
+            return BrdfValue::invalid();
+        }
+
+        const float3 m = normalize(wo + wi);
+
+        BrdfValue res;
+        res.pdf = M_FRAC_1_PI;
+        res.value = color * charlie_ndf(alpha(), m.z) * neubelt_visibility(wi.z, wo.z);
+        res.value_over_pdf = res.value / res.pdf;
+        res.transmission_fraction = 1.0;
+        return res;
+    }
+
+    BrdfSample sample(float3 wo, float2 urand) {
+        DiffuseBrdf cosine_lobe;
+        cosine_lobe.albedo = 1.0;
+        const float3 wi = cosine_lobe.sample(wo, urand).wi;
+
+        if (wi.z <= BRDF_SAMPLING_MIN_COS || wo.z <= BRDF_SAMPLING_MIN_COS) {
+            return BrdfSample::invalid();
+        }
+
+        const BrdfValue value = evaluate(wo, wi);
+
+        BrdfSample res;
+        res.pdf = value.pdf;
+        res.value = value.value;
+        res.value_over_pdf = value.value_over_pdf;
+        res.transmission_fraction = value.transmission_fraction;
+        res.wi = wi;
+        res.approx_roughness = 1.0;
+        return res;
+    }
+};
+
 // https://seblagarde.files.wordpress.com/2015/07/course_notes_moving_frostbite_to_pbr_v32.pdf
 float3 specular_dominant_direction(float3 n, float3 v, float roughness) {
     float3 r = reflect(-v, n);
@@ -2,6 +2,7 @@
 #define GBUFFER_HLSL
 
 #include "pack_unpack.hlsl"
+#include "math.hlsl"
 
 struct GbufferData;
 
@@ -20,6 +21,20 @@ struct GbufferDataPacked {
     float3 unpack_emissive();
 };
 
+// Rarely used material properties, in a separate render target so that passes which
+// only need the basics don't pay for them.
+struct GbufferExtDataPacked {
+    uint2 data0;
+
+    static GbufferExtDataPacked from_uint2(uint2 data0) {
+        GbufferExtDataPacked res;
+        res.data0 = data0;
+        return res;
+    }
+
+    void unpack_into(inout GbufferData gbuffer);
+};
+
 struct GbufferData {
     float3 albedo;
     float3 emissive;
@@ -27,6 +42,22 @@ struct GbufferData {
     float roughness;
     float metalness;
 
+    // Material ambient occlusion; only affects diffuse GI.
+    float ao;
+
+    // Strength and roughness of a dielectric coat on top of the base material.
+    float clearcoat;
+    float clearcoat_roughness;
+
+    // Sheen on top of the base material; disabled when `sheen_color` is zero.
+    float3 sheen_color;
+    float sheen_roughness;
+
+    // Strength of specular anisotropy, and the world-space tangent along which
+    // the specular lobe gets stretched.
+    float anisotropy;
+    float3 anisotropy_direction;
+
     static GbufferData create_zero() {
         GbufferData res;
         res.albedo = 0;
@@ -34,10 +65,18 @@ struct GbufferData {
         res.normal = 0;
         res.roughness = 0;
         res.metalness = 0;
+        res.ao = 1;
+        res.clearcoat = 0;
+        res.clearcoat_roughness = 0;
+        res.sheen_color = 0;
+        res.sheen_roughness = 0;
+        res.anisotropy = 0;
+        res.anisotropy_direction = 0;
         return res;
     }
 
     GbufferDataPacked pack();
+    GbufferExtDataPacked pack_ext();
 };
 
 float roughness_to_perceptual_roughness(float r) {
@@ -50,11 +89,16 @@ float perceptual_roughness_to_roughness(float r) {
 
 GbufferDataPacked GbufferData::pack() {
     float4 res = 0.0.xxxx;
-    res.x = asfloat(pack_color_888(albedo));
+    // Occlusion goes in the unused top byte of albedo
+    res.x = asfloat(pack_color_888(albedo) | (pack_unorm(ao, 8) << 24));
     res.y = pack_normal_11_10_11(normal);
 
-    float2 roughness_metalness = float2(roughness_to_perceptual_roughness(roughness), metalness);
-    res.z = asfloat(pack_2x16f_uint(roughness_metalness));
+    // Perceptual roughness: 12 bits, metalness: 6, clearcoat: 6, clearcoat perceptual roughness: 8
+    res.z = asfloat(
+        pack_unorm(roughness_to_perceptual_roughness(roughness), 12)
+        | (pack_unorm(metalness, 6) << 12)
+        | (pack_unorm(clearcoat, 6) << 18)
+        | (pack_unorm(roughness_to_perceptual_roughness(clearcoat_roughness), 8) << 24));
     res.w = asfloat(float3_to_rgb9e5(emissive));
 
    GbufferDataPacked packed;
@@ -62,19 +106,56 @@ GbufferDataPacked GbufferData::pack() {
    return packed;
 }
 
+GbufferExtDataPacked GbufferData::pack_ext() {
+    // The anisotropy direction is stored as an angle in the frame of `build_orthonormal_basis`.
+    // The lobe is symmetric, so half a turn is enough.
+    float angle = 0.0;
+    if (anisotropy > 0.0) {
+        const float3 direction_ts = mul(anisotropy_direction, build_orthonormal_basis(normal));
+        angle = atan2(direction_ts.y, direction_ts.x);
+        if (angle < 0.0) {
+            angle += M_PI;
+        }
+    }
+
+    GbufferExtDataPacked packed;
+    packed.data0.x = pack_color_888(sheen_color)
+        | (pack_unorm(roughness_to_perceptual_roughness(sheen_roughness), 8) << 24);
+    packed.data0.y = pack_unorm(anisotropy, 8) | (pack_unorm(angle / M_PI, 16) << 8);
+    return packed;
+}
+
 GbufferData GbufferDataPacked::unpack() {
     GbufferData res;
     res.albedo = unpack_albedo();
     res.normal = unpack_normal();
 
-    float2 roughness_metalness = unpack_2x16f_uint(data0.z);
-    res.roughness = perceptual_roughness_to_roughness(roughness_metalness.x);
-    res.metalness = roughness_metalness.y;
+    res.roughness = perceptual_roughness_to_roughness(unpack_unorm(data0.z, 12));
+    res.metalness = unpack_unorm(data0.z >> 12, 6);
+    res.clearcoat = unpack_unorm(data0.z >> 18, 6);
+    res.clearcoat_roughness = perceptual_roughness_to_roughness(unpack_unorm(data0.z >> 24, 8));
     res.emissive = unpack_emissive();
+    res.ao = unpack_unorm(data0.x >> 24, 8);
+
+    // Only in the extension target; see `GbufferExtDataPacked::unpack_into`.
+    res.sheen_color = 0;
+    res.sheen_roughness = 0;
+    res.anisotropy = 0;
+    res.anisotropy_direction = 0;
 
     return res;
 }
 
+void GbufferExtDataPacked::unpack_into(inout GbufferData gbuffer) {
+    gbuffer.sheen_color = unpack_color_888(data0.x);
+    gbuffer.sheen_roughness = perceptual_roughness_to_roughness(unpack_unorm(data0.x >> 24, 8));
+    gbuffer.anisotropy = unpack_unorm(data0.y, 8);
+
+    const float angle = unpack_unorm(data0.y >> 8, 16) * M_PI;
+    gbuffer.anisotropy_direction =
+        mul(build_orthonormal_basis(gbuffer.normal), float3(cos(angle), sin(angle), 0.0));
+}
+
 float3 GbufferDataPacked::unpack_normal() {
     return unpack_normal_11_10_11(asfloat(data0.y));
 }
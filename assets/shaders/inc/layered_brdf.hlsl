@@ -42,6 +42,21 @@ struct LayeredBrdf {
     DiffuseBrdf diffuse_brdf;
     SpecularBrdfEnergyPreservation energy_preservation;
 
+    // Dielectric coat on top of the other two layers, weighted by `clearcoat`.
+    SpecularBrdf clearcoat_brdf;
+    SpecularBrdfEnergyPreservation clearcoat_energy_preservation;
+    float clearcoat;
+
+    // Sheen between the clearcoat and the specular and diffuse layers.
+    SheenBrdf sheen_brdf;
+    float3 sheen_preintegrated_reflection;
+    float sheen_albedo_scaling;
+
+    // When above zero, `specular_brdf` gets stretched along `anisotropy_direction`,
+    // a unit vector in the tangent plane.
+    float anisotropy;
+    float2 anisotropy_direction;
+
     static LayeredBrdf from_gbuffer_ndotv(
         GbufferData gbuffer,
         float ndotv
@@ -61,9 +76,140 @@ struct LayeredBrdf {
 
         res.specular_brdf = specular_brdf;
         res.diffuse_brdf = diffuse_brdf;
+
+        res.clearcoat_brdf.albedo = 0.04;
+        res.clearcoat_brdf.roughness = max(1e-4, gbuffer.clearcoat_roughness);
+        res.clearcoat_energy_preservation =
+            SpecularBrdfEnergyPreservation::from_brdf_ndotv(res.clearcoat_brdf, ndotv);
+        res.clearcoat = gbuffer.clearcoat;
+
+        res.sheen_brdf.color = gbuffer.sheen_color;
+        res.sheen_brdf.roughness = gbuffer.sheen_roughness;
+        const float sheen_albedo = res.sheen_brdf.directional_albedo(ndotv);
+        res.sheen_preintegrated_reflection = gbuffer.sheen_color * sheen_albedo;
+        res.sheen_albedo_scaling =
+            1.0 - max3(gbuffer.sheen_color.r, gbuffer.sheen_color.g, gbuffer.sheen_color.b) * sheen_albedo;
+
+        res.anisotropy = gbuffer.anisotropy;
+        res.anisotropy_direction = float2(1.0, 0.0);
+
+        [branch]
+        if (gbuffer.anisotropy > 0.0) {
+            const float2 direction_ts = mul(gbuffer.anisotropy_direction, build_orthonormal_basis(gbuffer.normal)).xy;
+            if (dot(direction_ts, direction_ts) > 1e-8) {
+                res.anisotropy_direction = normalize(direction_ts);
+            }
+        }
+
         return res;
     }
 
+    // As in KHR_materials_anisotropy: the roughness along the tangent goes up with strength.
+    AnisotropicSpecularBrdf anisotropic_specular_brdf() {
+        AnisotropicSpecularBrdf res;
+        res.albedo = specular_brdf.albedo;
+        res.roughness = float2(
+            lerp(specular_brdf.roughness, 1.0, anisotropy * anisotropy),
+            specular_brdf.roughness);
+        return res;
+    }
+
+    float3 to_anisotropy_frame(float3 v) {
+        const float2 d = anisotropy_direction;
+        return float3(dot(v.xy, d), dot(v.xy, float2(-d.y, d.x)), v.z);
+    }
+
+    float3 from_anisotropy_frame(float3 v) {
+        const float2 d = anisotropy_direction;
+        return float3(v.x * d.x - v.y * d.y, v.x * d.y + v.y * d.x, v.z);
+    }
+
+    BrdfValue evaluate_base_specular(float3 wo, float3 wi) {
+        [branch]
+        if (anisotropy > 0.0) {
+            return anisotropic_specular_brdf().evaluate(to_anisotropy_frame(wo), to_anisotropy_frame(wi));
+        }
+
+        return specular_brdf.evaluate(wo, wi);
+    }
+
+    BrdfSample sample_base_specular(float3 wo, float2 urand) {
+        [branch]
+        if (anisotropy > 0.0) {
+            BrdfSample res = anisotropic_specular_brdf().sample(to_anisotropy_frame(wo), urand);
+            res.wi = from_anisotropy_frame(res.wi);
+            return res;
+        }
+
+        return specular_brdf.sample(wo, urand);
+    }
+
+    // Fraction of light which passes through the clearcoat to the layers below, and back.
+    float3 clearcoat_transmission_fraction() {
+        return lerp(1.0, clearcoat_energy_preservation.preintegrated_transmission_fraction, clearcoat);
+    }
+
+    // Directional albedo of the specular lobes; what filtered reflections get multiplied by.
+    float3 preintegrated_specular_reflection() {
+        return energy_preservation.preintegrated_reflection * sheen_albedo_scaling * clearcoat_transmission_fraction()
+            + clearcoat_energy_preservation.preintegrated_reflection * clearcoat;
+    }
+
+    // Adds the sheen lobe to the response of the layers below it, scaling them by the energy it reflects.
+    float3 apply_sheen(float3 wo, float3 wi, float3 base_value) {
+        [branch]
+        if (all(sheen_brdf.color <= 0.0)) {
+            return base_value;
+        }
+
+        return base_value * sheen_albedo_scaling + sheen_brdf.evaluate(wo, wi).value;
+    }
+
+    // Adds the clearcoat lobe to the response of the layers below it.
+    float3 apply_clearcoat(float3 wo, float3 wi, float3 base_value) {
+        [branch]
+        if (clearcoat <= 0.0) {
+            return base_value;
+        }
+
+        const BrdfValue coat = clearcoat_brdf.evaluate(wo, wi);
+        return base_value * lerp(1.0, coat.transmission_fraction, clearcoat)
+            + coat.value * clearcoat_energy_preservation.preintegrated_reflection_mult * clearcoat;
+    }
+
+    // Probability of picking the clearcoat over the base specular lobe, proportional to their directional albedo.
+    float specular_lobe_clearcoat_probability() {
+        const float base_wt = sRGB_to_luminance(energy_preservation.preintegrated_reflection * sheen_albedo_scaling * clearcoat_transmission_fraction());
+        const float coat_wt = sRGB_to_luminance(clearcoat_energy_preservation.preintegrated_reflection) * clearcoat;
+        return coat_wt / max(1e-8, base_wt + coat_wt);
+    }
+
+    // Both specular lobes, with multi-scatter compensation, but without the diffuse and sheen layers.
+    // The `pdf` is that of picking a lobe with `specular_lobe_clearcoat_probability`, then sampling it.
+    BrdfValue evaluate_specular_lobes(float3 wo, float3 wi) {
+        BrdfValue spec = evaluate_base_specular(wo, wi);
+        if (spec.pdf <= 0.0) {
+            spec.value = 0.0;
+            return spec;
+        }
+
+        spec.value *= energy_preservation.preintegrated_reflection_mult * sheen_albedo_scaling;
+        spec.value_over_pdf *= energy_preservation.preintegrated_reflection_mult * sheen_albedo_scaling;
+
+        [branch]
+        if (clearcoat > 0.0) {
+            const BrdfValue coat = clearcoat_brdf.evaluate(wo, wi);
+            const float coat_p = specular_lobe_clearcoat_probability();
+
+            spec.value = spec.value * lerp(1.0, coat.transmission_fraction, clearcoat)
+                + coat.value * clearcoat_energy_preservation.preintegrated_reflection_mult * clearcoat;
+            spec.pdf = lerp(spec.pdf, coat.pdf, coat_p);
+            spec.value_over_pdf = spec.value / max(1e-10, spec.pdf);
+        }
+
+        return spec;
+    }
+
     float3 evaluate(float3 wo, float3 wi) {
         if (wo.z <= 0 || wi.z <= 0) {
             return 0;
@@ -75,16 +221,16 @@ struct LayeredBrdf {
             return diff.value;
         #endif
 
-        const BrdfValue spec = specular_brdf.evaluate(wo, wi);
+        const BrdfValue spec = evaluate_base_specular(wo, wi);
 
         #if LAYERED_BRDF_FORCE_SPECULAR_ONLY
             return spec.value;
         #endif
 
-        return (
+        return apply_clearcoat(wo, wi, apply_sheen(wo, wi,
             spec.value * energy_preservation.preintegrated_reflection_mult +
             diff.value * spec.transmission_fraction
-        );
+        ));
     }
 
     float3 evaluate_directional_light(float3 wo, float3 wi) {
@@ -98,7 +244,7 @@ struct LayeredBrdf {
             return diff.value;
         #endif
 
-        const BrdfValue spec = specular_brdf.evaluate(wo, wi);
+        const BrdfValue spec = evaluate_base_specular(wo, wi);
 
         #if LAYERED_BRDF_FORCE_SPECULAR_ONLY
             return spec.value;
@@ -113,10 +259,10 @@ struct LayeredBrdf {
             //energy_preservation.preintegrated_reflection_mult;
             lerp(1.0, energy_preservation.preintegrated_reflection_mult, sqrt(abs(wi.z)));
 
-        return (
+        return apply_clearcoat(wo, wi, apply_sheen(wo, wi,
             spec.value * preintegrated_reflection_mult_directional +
             diff.value * spec.transmission_fraction
-        );
+        ));
     }
 
     BrdfSample sample(float3 wo, float3 urand) {
@@ -125,46 +271,95 @@ struct LayeredBrdf {
         #endif
 
         #if LAYERED_BRDF_FORCE_SPECULAR_ONLY
-            return specular_brdf.sample(wo, urand.xy);
+            return sample_base_specular(wo, urand.xy);
         #endif
 
+        // Like between the specular and diffuse layers below, toss a coin to choose between
+        // reflecting off the clearcoat, and passing through it.
+        const float coat_wt = sRGB_to_luminance(clearcoat_energy_preservation.preintegrated_reflection) * clearcoat;
+        const float3 under_sheen_reflection =
+            energy_preservation.preintegrated_reflection
+            + energy_preservation.preintegrated_transmission_fraction * diffuse_brdf.albedo;
+        const float under_coat_wt = sRGB_to_luminance(
+            (sheen_preintegrated_reflection + under_sheen_reflection * sheen_albedo_scaling)
+            * clearcoat_transmission_fraction());
+        const float coat_p = coat_wt / max(1e-8, coat_wt + under_coat_wt);
+
+        [branch]
+        if (urand.z < coat_p) {
+            BrdfSample brdf_sample = clearcoat_brdf.sample(wo, urand.xy);
+
+            brdf_sample.value_over_pdf *= clearcoat * clearcoat_energy_preservation.preintegrated_reflection_mult / coat_p;
+            brdf_sample.value *= clearcoat * clearcoat_energy_preservation.preintegrated_reflection_mult;
+            brdf_sample.pdf *= coat_p;
+            return brdf_sample;
+        }
+
+        // Remap the coin so it can be reused below.
+        urand.z = (urand.z - coat_p) / max(1e-8, 1.0 - coat_p);
+
         BrdfSample brdf_sample;
 
-        // We should transmit with throughput equal to `brdf_sample.transmission_fraction`,
-        // and reflect with the complement of that. However since we use a single ray,
-        // we toss a coin, and choose between reflection and transmission.
+        // Same again for the sheen, and the layers below it.
+        const float sheen_wt = sRGB_to_luminance(sheen_preintegrated_reflection);
+        const float under_sheen_wt = sRGB_to_luminance(under_sheen_reflection) * sheen_albedo_scaling;
+        const float sheen_p = sheen_wt / max(1e-8, sheen_wt + under_sheen_wt);
 
-        const float spec_wt = sRGB_to_luminance(energy_preservation.preintegrated_reflection);
-        const float diffuse_wt = sRGB_to_luminance(energy_preservation.preintegrated_transmission_fraction * diffuse_brdf.albedo);
-        const float transmission_p = diffuse_wt / (spec_wt + diffuse_wt);
+        [branch]
+        if (urand.z < sheen_p) {
+            brdf_sample = sheen_brdf.sample(wo, urand.xy);
+            brdf_sample.value_over_pdf /= sheen_p;
+            brdf_sample.pdf *= sheen_p;
+        } else {
+            urand.z = (urand.z - sheen_p) / max(1e-8, 1.0 - sheen_p);
 
-        const float lobe_xi = urand.z;
-        if (lobe_xi < transmission_p) {
-            // Transmission wins! Now sample the bottom layer (diffuse)
+            // We should transmit with throughput equal to `brdf_sample.transmission_fraction`,
+            // and reflect with the complement of that. However since we use a single ray,
+            // we toss a coin, and choose between reflection and transmission.
 
-            brdf_sample = diffuse_brdf.sample(wo, urand.xy);
+            const float spec_wt = sRGB_to_luminance(energy_preservation.preintegrated_reflection);
+            const float diffuse_wt = sRGB_to_luminance(energy_preservation.preintegrated_transmission_fraction * diffuse_brdf.albedo);
+            const float transmission_p = diffuse_wt / (spec_wt + diffuse_wt);
 
-            const float lobe_pdf = transmission_p;
-            brdf_sample.value_over_pdf /= lobe_pdf;
-            brdf_sample.pdf *= lobe_pdf;
+            const float lobe_xi = urand.z;
+            if (lobe_xi < transmission_p) {
+                // Transmission wins! Now sample the bottom layer (diffuse)
 
-            // Account for the masking that the top level exerts on the bottom.
-            brdf_sample.value_over_pdf *= energy_preservation.preintegrated_transmission_fraction;
-            brdf_sample.value *= energy_preservation.preintegrated_transmission_fraction;
-        } else {
-            // Reflection wins!
+                brdf_sample = diffuse_brdf.sample(wo, urand.xy);
+
+                const float lobe_pdf = transmission_p;
+                brdf_sample.value_over_pdf /= lobe_pdf;
+                brdf_sample.pdf *= lobe_pdf;
 
-            brdf_sample = specular_brdf.sample(wo, urand.xy);
+                // Account for the masking that the top level exerts on the bottom.
+                brdf_sample.value_over_pdf *= energy_preservation.preintegrated_transmission_fraction;
+                brdf_sample.value *= energy_preservation.preintegrated_transmission_fraction;
+            } else {
+                // Reflection wins!
 
-            const float lobe_pdf = (1.0 - transmission_p);
-            brdf_sample.value_over_pdf /= lobe_pdf;
-            brdf_sample.pdf *= lobe_pdf;
+                brdf_sample = sample_base_specular(wo, urand.xy);
 
-            // Apply approximate multi-scatter energy preservation
-            brdf_sample.value_over_pdf *= energy_preservation.preintegrated_reflection_mult;
-            brdf_sample.value *= energy_preservation.preintegrated_reflection_mult;
+                const float lobe_pdf = (1.0 - transmission_p);
+                brdf_sample.value_over_pdf /= lobe_pdf;
+                brdf_sample.pdf *= lobe_pdf;
+
+                // Apply approximate multi-scatter energy preservation
+                brdf_sample.value_over_pdf *= energy_preservation.preintegrated_reflection_mult;
+                brdf_sample.value *= energy_preservation.preintegrated_reflection_mult;
+            }
+
+            // Account for the light reflected by the sheen.
+            brdf_sample.value_over_pdf *= sheen_albedo_scaling / max(1e-8, 1.0 - sheen_p);
+            brdf_sample.value *= sheen_albedo_scaling;
+            brdf_sample.pdf *= 1.0 - sheen_p;
         }
 
+        // Account for the light reflected by the clearcoat.
+        const float3 coat_transmission = clearcoat_transmission_fraction();
+        brdf_sample.value_over_pdf *= coat_transmission / max(1e-8, 1.0 - coat_p);
+        brdf_sample.value *= coat_transmission;
+        brdf_sample.pdf *= 1.0 - coat_p;
+
         return brdf_sample;
     }
 };
@@ -2,7 +2,7 @@
 #define LIGHTS_PACKED_HLSL
 
 struct TriangleLightPacked {
-    float packed[12];
+    float packed[14];
 };
 
 #endif
\ No newline at end of file
@@ -5,7 +5,7 @@ struct Triangle {
 };
 
 struct TriangleLight {
-    float packed[12];
+    float packed[14];
 
     static TriangleLight from_packed(TriangleLightPacked p) {
         TriangleLight res;
@@ -36,6 +36,29 @@ struct TriangleLight {
     float3 radiance() {
         return float3(packed[9], packed[10], packed[11]);
     }
+
+    // Radiance emitted along `dir`, which points away from the light.
+    // Spot lights confine it to a cone around the triangle normal, with the
+    // KHR_lights_punctual falloff between the inner and outer angles.
+    float3 radiance_towards(float3 dir) {
+        const float cos_outer = packed[12];
+        const float cos_inner = packed[13];
+
+        if (cos_inner <= cos_outer) {
+            return radiance();
+        }
+
+        const float cos_angle = dot(normalize(cross(e0(), e1())), dir);
+
+        // As recommended by KHR_lights_punctual.
+        const float light_angle_scale = 1.0 / max(0.001, cos_inner - cos_outer);
+        const float light_angle_offset = -cos_outer * light_angle_scale;
+        float attenuation = saturate(cos_angle * light_angle_scale + light_angle_offset);
+        attenuation *= attenuation;
+
+        // Intensity is constant inside the cone, so undo the cosine falloff of the flat emitter.
+        return radiance() * attenuation / max(1e-3, cos_angle);
+    }
 };
 
 float3 sample_point_on_triangle(Triangle tri, float2 urand) {
@@ -0,0 +1,47 @@
+#ifndef MATERIAL_SAMPLERS_HLSL
+#define MATERIAL_SAMPLERS_HLSL
+
+#include "samplers.hlsl"
+#include "mesh.hlsl"
+
+// Every sampler state a material map can use; see `TexSampler::pack` on the Rust side.
+// Bit 0: nearest texel filter; bit 1: nearest mip filter; bits 2..3: repeat / mirrored repeat / clamp.
+[[vk::binding(36)]] SamplerState sampler_nnr;
+[[vk::binding(37)]] SamplerState sampler_nlr;
+[[vk::binding(38)]] SamplerState sampler_lnr;
+[[vk::binding(39)]] SamplerState sampler_llmr;
+[[vk::binding(40)]] SamplerState sampler_nlmr;
+[[vk::binding(41)]] SamplerState sampler_lnmr;
+[[vk::binding(42)]] SamplerState sampler_nnmr;
+[[vk::binding(43)]] SamplerState sampler_nlc;
+
+// Resources can't be selected dynamically, so branch over the sample calls instead.
+#define MATERIAL_SAMPLER_SWITCH(sampler_id, SAMPLE) \
+    switch (sampler_id) { \
+        case 1: return SAMPLE(sampler_nlr); \
+        case 2: return SAMPLE(sampler_lnr); \
+        case 3: return SAMPLE(sampler_nnr); \
+        case 4: return SAMPLE(sampler_llmr); \
+        case 5: return SAMPLE(sampler_nlmr); \
+        case 6: return SAMPLE(sampler_lnmr); \
+        case 7: return SAMPLE(sampler_nnmr); \
+        case 8: return SAMPLE(sampler_llc); \
+        case 9: return SAMPLE(sampler_nlc); \
+        case 10: return SAMPLE(sampler_lnc); \
+        case 11: return SAMPLE(sampler_nnc); \
+        default: return SAMPLE(sampler_llr); \
+    }
+
+float4 sample_material_map_bias(MeshMaterial mat, uint map_idx, Texture2D tex, float2 uv, float bias) {
+    #define SAMPLE_BIAS(smp) tex.SampleBias(smp, uv, bias)
+    MATERIAL_SAMPLER_SWITCH(mat.map_samplers[map_idx], SAMPLE_BIAS)
+    #undef SAMPLE_BIAS
+}
+
+float4 sample_material_map_level(MeshMaterial mat, uint map_idx, Texture2D tex, float2 uv, float lod) {
+    #define SAMPLE_LEVEL(smp) tex.SampleLevel(smp, uv, lod)
+    MATERIAL_SAMPLER_SWITCH(mat.map_samplers[map_idx], SAMPLE_LEVEL)
+    #undef SAMPLE_LEVEL
+}
+
+#endif
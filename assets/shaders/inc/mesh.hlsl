@@ -15,6 +15,11 @@ struct Mesh {
     uint vertex_tangent_offset;
     uint mat_data_offset;
     uint index_offset;
+    uint vertex_uv1_offset;
+    uint vertex_prev_pos_offset;
+    uint single_sided_triangle_count;
+    uint single_sided_non_opaque_triangle_count;
+    uint double_sided_non_opaque_triangle_count;
 };
 
 struct Vertex {
@@ -46,6 +51,17 @@ VertexPacked pack_vertex(Vertex v) {
 }
 
 static const uint MESH_MATERIAL_FLAG_EMISSIVE_USED_AS_LIGHT = 1;
+static const uint MESH_MATERIAL_FLAG_ALPHA_MASK = 2;
+static const uint MESH_MATERIAL_FLAG_DOUBLE_SIDED = 4;
+
+static const uint MESH_MATERIAL_MAP_COUNT = 5;
+
+// Chance of light passing straight through a surface which is treated as infinitely thin.
+float thin_surface_transmission_probability(float transmission, float ior, float cos_theta) {
+    const float f0 = (ior - 1.0) * (ior - 1.0) / ((ior + 1.0) * (ior + 1.0));
+    const float fresnel = f0 + (1.0 - f0) * pow(1.0 - saturate(cos_theta), 5.0);
+    return transmission * (1.0 - fresnel);
+}
 
 struct MeshMaterial {
     float base_color_mult[4];
@@ -53,14 +69,38 @@ struct MeshMaterial {
     uint spec_map;
     uint albedo_map;
     uint emissive_map;
+    uint occlusion_map;
     float roughness_mult;
     float metalness_factor;
     float emissive[3];
     uint flags;
-    float map_transforms[6 * 4];
+    float map_transforms[6 * MESH_MATERIAL_MAP_COUNT];
+    float alpha_cutoff;
+    uint map_samplers[MESH_MATERIAL_MAP_COUNT];
+    float occlusion_strength;
+    uint map_uv_sets[MESH_MATERIAL_MAP_COUNT];
+    float clearcoat_factor;
+    float clearcoat_roughness;
+    float transmission_factor;
+    float ior;
+    float sheen_color[3];
+    float sheen_roughness;
+    float anisotropy_strength;
+    float anisotropy_rotation;
+
+    bool is_alpha_masked_out(float alpha) {
+        return (flags & MESH_MATERIAL_FLAG_ALPHA_MASK) != 0 && alpha < alpha_cutoff;
+    }
+
+    // Direction of the anisotropic specular lobe in the space of the given tangent frame.
+    float3 anisotropy_direction(float3 tangent, float3 bitangent) {
+        return cos(anisotropy_rotation) * tangent + sin(anisotropy_rotation) * bitangent;
+    }
 };
 
-float2 transform_material_uv(MeshMaterial mat, float2 uv, uint map_idx) {
+// `uv1` is the second UV set; each map chooses which one it uses.
+float2 transform_material_uv(MeshMaterial mat, float2 uv0, float2 uv1, uint map_idx) {
+    float2 uv = mat.map_uv_sets[map_idx] == 1 ? uv1 : uv0;
     uint xo = map_idx * 6;
     float2x2 rot_scl = float2x2(mat.map_transforms[xo+0], mat.map_transforms[xo+1], mat.map_transforms[xo+2], mat.map_transforms[xo+3]);
     float2 offset = float2(mat.map_transforms[xo+4], mat.map_transforms[xo+5]);
@@ -3,6 +3,7 @@
 
 #include "math_const.hlsl"
 #include "gbuffer.hlsl"
+#include "mesh.hlsl"
 #include "ray_cone.hlsl"
 
 struct GbufferRayPayload {
@@ -11,11 +12,22 @@ struct GbufferRayPayload {
     RayCone ray_cone;
     uint path_length;
 
+    // Thin-surface transmission of the material hit; not part of the G-buffer,
+    // since only the reference path tracer needs it.
+    float transmission;
+    float ior;
+
+    // Sheen and anisotropy, as stored in the G-buffer extension target.
+    GbufferExtDataPacked gbuffer_ext_packed;
+
     static GbufferRayPayload new_miss() {
         GbufferRayPayload res;
         res.t = FLT_MAX;
         res.ray_cone = RayCone::from_spread_angle(0.0);
         res.path_length = 0;
+        res.transmission = 0.0;
+        res.ior = 1.5;
+        res.gbuffer_ext_packed = GbufferExtDataPacked::from_uint2(0);
         return res;
     }
 
@@ -46,6 +58,26 @@ struct ShadowRayPayload {
     }
 };
 
+// Each instance is in the TLAS twice: its single-sided triangles at `2 * i`, and its
+// double-sided ones, which can be hit from behind, at `2 * i + 1`. Only valid in hit shaders.
+uint rt_hit_instance_index() {
+    return InstanceIndex() / 2;
+}
+
+// Index of the triangle hit within `mesh.index_offset`. Meshes are split into a single-sided
+// and a double-sided BLAS, in that order, and each of those into a non-opaque and an opaque
+// geometry, in that order, but `PrimitiveIndex()` is relative to the geometry.
+// Only valid in hit shaders.
+uint rt_hit_triangle_index(Mesh mesh) {
+    if (InstanceIndex() & 1) {
+        return mesh.single_sided_triangle_count
+            + PrimitiveIndex()
+            + (GeometryIndex() > 0 ? mesh.double_sided_non_opaque_triangle_count : 0);
+    }
+
+    return PrimitiveIndex() + (GeometryIndex() > 0 ? mesh.single_sided_non_opaque_triangle_count : 0);
+}
+
 RayDesc new_ray(float3 origin, float3 direction, float tmin, float tmax) {
     RayDesc ray;
     ray.Origin = origin;
@@ -55,6 +87,8 @@ RayDesc new_ray(float3 origin, float3 direction, float tmin, float tmax) {
     return ray;
 }
 
+// Uses hit group 1 and miss shader 1, which need to take a `ShadowRayPayload`;
+// see `shadow.rahit.hlsl` and `shadow.rmiss.hlsl`.
 bool rt_is_shadowed(
     RaytracingAccelerationStructure acceleration_structure,
     RayDesc ray
@@ -63,7 +97,7 @@ bool rt_is_shadowed(
     TraceRay(
         acceleration_structure,
         RAY_FLAG_ACCEPT_FIRST_HIT_AND_END_SEARCH | RAY_FLAG_SKIP_CLOSEST_HIT_SHADER,
-        0xff, 0, 0, 1, ray, shadow_payload
+        0xff, 1, 0, 1, ray, shadow_payload
     );
 
     return shadow_payload.is_shadowed;
@@ -74,12 +108,18 @@ struct GbufferPathVertex {
     GbufferDataPacked gbuffer_packed;
     float3 position;
     float ray_t;
+    float transmission;
+    float ior;
+    GbufferExtDataPacked gbuffer_ext_packed;
 };
 
 struct GbufferRaytrace {
     RayDesc ray;
     RayCone ray_cone;
     uint path_length;
+
+    // If set, back faces of single-sided materials are culled, like in raster.
+    // Otherwise, all back faces can be hit.
     bool cull_back_faces;
 
     static GbufferRaytrace with_ray(RayDesc ray) {
@@ -127,11 +167,17 @@ struct GbufferRaytrace {
             res.position = ray.Origin + ray.Direction * payload.t;
             res.gbuffer_packed = payload.gbuffer_packed;
             res.ray_t = payload.t;
+            res.transmission = payload.transmission;
+            res.ior = payload.ior;
+            res.gbuffer_ext_packed = payload.gbuffer_ext_packed;
             return res;
         } else {
             GbufferPathVertex res;
             res.is_hit = false;
             res.ray_t = FLT_MAX;
+            res.transmission = 0.0;
+            res.ior = 1.5;
+            res.gbuffer_ext_packed = GbufferExtDataPacked::from_uint2(0);
             return res;
         }
     }
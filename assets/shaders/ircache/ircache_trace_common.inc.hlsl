@@ -172,7 +172,7 @@ IrcacheTraceResult ircache_trace(Vertex entry, DiffuseBrdf brdf, SampleParams sa
 
                         irradiance_sum +=
                             select(is_shadowed, 0,
-                                throughput * triangle_light.radiance() * brdf.evaluate(wo, wi) / light_sample.pdf.value * to_psa_metric / light_selection_pmf);
+                                throughput * triangle_light.radiance_towards(-to_light_norm_ws) * brdf.evaluate(wo, wi) / light_sample.pdf.value * to_psa_metric / light_selection_pmf);
                     }
                 }
             }
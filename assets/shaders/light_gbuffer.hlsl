@@ -36,10 +36,13 @@ DEFINE_WRC_BINDINGS(14)
 [[vk::binding(16)]] RWTexture2D<float4> output_tex;
 [[vk::binding(17)]] TextureCube<float4> unconvolved_sky_cube_tex;
 [[vk::binding(18)]] TextureCube<float4> sky_cube_tex;
-[[vk::binding(19)]] cbuffer _ {
+[[vk::binding(19)]] Texture2D<uint2> gbuffer_ext_tex;
+[[vk::binding(20)]] Texture2D<float> ssao_tex;
+[[vk::binding(21)]] cbuffer _ {
     float4 output_tex_size;
     uint debug_shading_mode;
     uint debug_show_wrc;
+    uint rtdgi_enabled;
 };
 
 #define IRCACHE_LOOKUP_DONT_KEEP_ALIVE
@@ -130,6 +133,7 @@ void main(in uint2 px : SV_DispatchThreadID) {
     }
 
     GbufferData gbuffer = GbufferDataPacked::from_uint4(asuint(gbuffer_tex[px])).unpack();
+    GbufferExtDataPacked::from_uint2(gbuffer_ext_tex[px]).unpack_into(gbuffer);
 
     [branch]
     if (debug_shading_mode == SHADING_MODE_NO_TEXTURES) {
@@ -157,24 +161,39 @@ void main(in uint2 px : SV_DispatchThreadID) {
 
     float3 gi_irradiance = 0.0.xxx;
 
-    if (debug_shading_mode != SHADING_MODE_RTX_OFF) {
-        if (USE_RTDGI) {
-            gi_irradiance = rtdgi_tex[px].rgb;
-        }
+    if (USE_RTDGI && rtdgi_enabled && debug_shading_mode != SHADING_MODE_RTX_OFF) {
+        gi_irradiance = rtdgi_tex[px].rgb;
+    } else {
+        // Without ray tracing, approximate diffuse GI with the convolved sky, occluded by SSAO.
+        gi_irradiance = sky_cube_tex.SampleLevel(sampler_llr, gbuffer.normal, 0).rgb * ssao_tex[px];
     }
 
+    // Baked material occlusion only affects diffuse-like lobes; keep the unoccluded
+    // irradiance around for the rough specular fallback below.
+    const float3 spec_fallback_irradiance = gi_irradiance;
+    gi_irradiance *= gbuffer.ao;
+
     total_radiance += gi_irradiance
         * brdf.diffuse_brdf.albedo
         #if !LAYERED_BRDF_FORCE_DIFFUSE_ONLY
             * brdf.energy_preservation.preintegrated_transmission_fraction
+            * brdf.sheen_albedo_scaling
+            * brdf.clearcoat_transmission_fraction()
         #endif
         ;
 
+    // Sheen is wide enough for diffuse GI to stand in for its incident light.
+    #if !LAYERED_BRDF_FORCE_DIFFUSE_ONLY
+        total_radiance += gi_irradiance
+            * brdf.sheen_preintegrated_reflection
+            * brdf.clearcoat_transmission_fraction();
+    #endif
+
     if (USE_RTR && !LAYERED_BRDF_FORCE_DIFFUSE_ONLY && debug_shading_mode != SHADING_MODE_RTX_OFF) {
         float3 rtr_radiance;
 
         #if !RTR_RENDER_SCALED_BY_FG
-            rtr_radiance = rtr_tex[px].xyz * brdf.energy_preservation.preintegrated_reflection;
+            rtr_radiance = rtr_tex[px].xyz * brdf.preintegrated_specular_reflection();
         #else
             rtr_radiance = rtr_tex[px].xyz;
         #endif
@@ -182,7 +201,7 @@ void main(in uint2 px : SV_DispatchThreadID) {
         if (USE_DIFFUSE_GI_FOR_ROUGH_SPEC) {
             rtr_radiance = lerp(
                 rtr_radiance,
-                gi_irradiance * brdf.energy_preservation.preintegrated_reflection,
+                spec_fallback_irradiance * brdf.preintegrated_specular_reflection(),
                 smoothstep(USE_DIFFUSE_GI_FOR_ROUGH_SPEC_MIN_ROUGHNESS, lerp(USE_DIFFUSE_GI_FOR_ROUGH_SPEC_MIN_ROUGHNESS, 1.0, 0.5), gbuffer.roughness));
         }
 
@@ -190,7 +209,7 @@ void main(in uint2 px : SV_DispatchThreadID) {
         if (debug_shading_mode == SHADING_MODE_NO_TEXTURES) {
             GbufferData true_gbuffer = GbufferDataPacked::from_uint4(asuint(gbuffer_tex[px])).unpack();
             LayeredBrdf true_brdf = LayeredBrdf::from_gbuffer_ndotv(true_gbuffer, wo.z);
-            rtr_radiance /= true_brdf.energy_preservation.preintegrated_reflection;
+            rtr_radiance /= true_brdf.preintegrated_specular_reflection();
         }
         
         total_radiance += rtr_radiance;
@@ -203,7 +222,7 @@ void main(in uint2 px : SV_DispatchThreadID) {
     [branch]
     if (debug_shading_mode == SHADING_MODE_REFLECTIONS) {
         #if !RTR_RENDER_SCALED_BY_FG
-            output = rtr_tex[px].xyz * brdf.energy_preservation.preintegrated_reflection;
+            output = rtr_tex[px].xyz * brdf.preintegrated_specular_reflection();
         #else
             output = rtr_tex[px].xyz;
         #endif
@@ -211,13 +230,13 @@ void main(in uint2 px : SV_DispatchThreadID) {
         if (USE_DIFFUSE_GI_FOR_ROUGH_SPEC) {
             output = lerp(
                 output,
-                gi_irradiance * brdf.energy_preservation.preintegrated_reflection,
+                spec_fallback_irradiance * brdf.preintegrated_specular_reflection(),
                 smoothstep(USE_DIFFUSE_GI_FOR_ROUGH_SPEC_MIN_ROUGHNESS, 1.0, gbuffer.roughness));
         }
 
         GbufferData true_gbuffer = GbufferDataPacked::from_uint4(asuint(gbuffer_tex[px])).unpack();
         LayeredBrdf true_brdf = LayeredBrdf::from_gbuffer_ndotv(true_gbuffer, wo.z);
-        output /= true_brdf.energy_preservation.preintegrated_reflection;
+        output /= true_brdf.preintegrated_specular_reflection();
     }
 
     [branch]
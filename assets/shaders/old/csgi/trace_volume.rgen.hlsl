@@ -208,7 +208,7 @@ void main() {
                                     radiance_contribution +=
                                         !is_shadowed ?
                                         (
-                                            triangle_light.radiance() * bounce_albedo / light_sample.pdf.value * to_psa_metric / M_PI / light_sample_count
+                                            triangle_light.radiance_towards(-to_light_norm_ws) * bounce_albedo / light_sample.pdf.value * to_psa_metric / M_PI / light_sample_count
                                         ) : 0;
                                 }
                             }
@@ -1,12 +1,15 @@
 #include "inc/math.hlsl"
 #include "inc/samplers.hlsl"
+#include "inc/material_samplers.hlsl"
 #include "inc/frame_constants.hlsl"
 #include "inc/mesh.hlsl"
 #include "inc/pack_unpack.hlsl"
 #include "inc/bindless.hlsl"
 #include "inc/gbuffer.hlsl"
+#include "inc/hash.hlsl"
 
 struct PsIn {
+    float4 position: SV_Position;
     [[vk::location(0)]] float4 color: TEXCOORD0;
     [[vk::location(1)]] float2 uv: TEXCOORD1;
     [[vk::location(2)]] float3 normal: TEXCOORD2;
@@ -15,6 +18,7 @@ struct PsIn {
     [[vk::location(5)]] float3 bitangent: TEXCOORD5;
     [[vk::location(6)]] float3 vs_pos: TEXCOORD6;
     [[vk::location(7)]] float3 prev_vs_pos: TEXCOORD7;
+    [[vk::location(8)]] float2 uv1: TEXCOORD8;
 };
 
 [[vk::push_constant]]
@@ -34,6 +38,7 @@ struct PsOut {
     float3 geometric_normal: SV_TARGET0;
     float4 gbuffer: SV_TARGET1;
     float4 velocity: SV_TARGET2;
+    uint2 gbuffer_ext: SV_TARGET3;
 };
 
 PsOut main(PsIn ps) {
@@ -42,18 +47,18 @@ PsOut main(PsIn ps) {
 
     const float lod_bias = -0.5;
 
-    float2 albedo_uv = transform_material_uv(material, ps.uv, 0);
+    float2 albedo_uv = transform_material_uv(material, ps.uv, ps.uv1, 0);
     Texture2D albedo_tex = bindless_textures[NonUniformResourceIndex(material.albedo_map)];
-    float4 albedo_texel = albedo_tex.SampleBias(sampler_llr, albedo_uv, lod_bias);
-    if (albedo_texel.a < 0.5) {
+    float4 albedo_texel = sample_material_map_bias(material, 0, albedo_tex, albedo_uv, lod_bias);
+    if (material.is_alpha_masked_out(albedo_texel.a * material.base_color_mult[3] * ps.color.a)) {
         discard;
     }
 
     float3 albedo = albedo_texel.xyz * float4(material.base_color_mult).xyz * ps.color.xyz;
 
-    float2 spec_uv = transform_material_uv(material, ps.uv, 2);
+    float2 spec_uv = transform_material_uv(material, ps.uv, ps.uv1, 2);
     Texture2D spec_tex = bindless_textures[NonUniformResourceIndex(material.spec_map)];
-    const float4 metalness_roughness = spec_tex.SampleBias(sampler_llr, spec_uv, lod_bias);
+    const float4 metalness_roughness = sample_material_map_bias(material, 2, spec_tex, spec_uv, lod_bias);
     float perceptual_roughness = material.roughness_mult * metalness_roughness.x;
     float roughness = clamp(perceptual_roughness_to_roughness(perceptual_roughness), 1e-4, 1.0);
     float metalness = metalness_roughness.y * material.metalness_factor;
@@ -73,13 +78,14 @@ PsOut main(PsIn ps) {
 
         [branch]
         if (!frame_constants.render_overrides.has_flag(RenderOverrideFlags::NO_NORMAL_MAPS)) {
+            float2 normal_uv = transform_material_uv(material, ps.uv, ps.uv1, 1);
             Texture2D normal_tex = bindless_textures[NonUniformResourceIndex(material.normal_map)];
 
 #if 1
-            float3 ts_normal = float3(normal_tex.SampleBias(sampler_llr, ps.uv, lod_bias).xy * 2.0 - 1.0, 0);
+            float3 ts_normal = float3(sample_material_map_bias(material, 1, normal_tex, normal_uv, lod_bias).xy * 2.0 - 1.0, 0);
             ts_normal.z = sqrt(max(0.01, 1.0 - dot(ts_normal.xy, ts_normal.xy)));
 #else
-            float3 ts_normal = normal_tex.SampleBias(sampler_llr, ps.uv, lod_bias).xyz * 2.0 - 1.0;
+            float3 ts_normal = sample_material_map_bias(material, 1, normal_tex, normal_uv, lod_bias).xyz * 2.0 - 1.0;
 #endif
 
             if (frame_constants.render_overrides.has_flag(RenderOverrideFlags::FLIP_NORMAL_MAP_YZ)) {
@@ -104,6 +110,22 @@ PsOut main(PsIn ps) {
     }
     float3 geometric_normal_ws = direction_view_to_world(geometric_normal_vs);
 
+    // Cheap stand-in for thin-surface transmission: stochastically drop the surface,
+    // and let the temporal filters blend it with what's behind.
+    if (material.transmission_factor > 0.0) {
+        const float cos_theta = abs(dot(normalize(ps.vs_pos), geometric_normal_vs));
+        const uint seed = hash_combine2(
+            hash_combine2(uint(ps.position.x), hash1(uint(ps.position.y))),
+            frame_constants.frame_index);
+
+        const float transmission_p =
+            thin_surface_transmission_probability(material.transmission_factor, material.ior, cos_theta);
+
+        if (uint_to_u01_float(hash1(seed)) < transmission_p) {
+            discard;
+        }
+    }
+
     // Fix invalid normals
     if (dot(normal_ws, geometric_normal_ws) < 0.0) {
         normal_ws *= -1;
@@ -113,14 +135,18 @@ PsOut main(PsIn ps) {
         normal_ws = geometric_normal_ws;
     }
 
-    float2 emissive_uv = transform_material_uv(material, ps.uv, 3);
+    float2 emissive_uv = transform_material_uv(material, ps.uv, ps.uv1, 3);
     Texture2D emissive_tex = bindless_textures[NonUniformResourceIndex(material.emissive_map)];
     float3 emissive = 1.0.xxx
-        * emissive_tex.SampleBias(sampler_llr, emissive_uv, lod_bias).rgb
+        * sample_material_map_bias(material, 3, emissive_tex, emissive_uv, lod_bias).rgb
         * float3(material.emissive)
         * instance_dynamic_parameters_dyn[push_constants.draw_index].emissive_multiplier
         * frame_constants.pre_exposure;
 
+    Texture2D occlusion_tex = bindless_textures[NonUniformResourceIndex(material.occlusion_map)];
+    float2 occlusion_uv = transform_material_uv(material, ps.uv, ps.uv1, 4);
+    const float occlusion = sample_material_map_bias(material, 4, occlusion_tex, occlusion_uv, lod_bias).r;
+
     //albedo = float3(0.966653, 0.802156, 0.323968); // Au from Mitsuba
 
     GbufferData gbuffer = GbufferData::create_zero();
@@ -130,11 +156,31 @@ PsOut main(PsIn ps) {
     //gbuffer.roughness = lerp(0.05, 0.15, roughness);  // kitchen hack
     gbuffer.metalness = metalness;
     gbuffer.emissive = emissive;
+    gbuffer.ao = lerp(1.0, occlusion, material.occlusion_strength);
+    gbuffer.clearcoat = material.clearcoat_factor;
+    gbuffer.clearcoat_roughness = clamp(perceptual_roughness_to_roughness(material.clearcoat_roughness), 1e-4, 1.0);
+    gbuffer.sheen_color = float3(material.sheen_color);
+    gbuffer.sheen_roughness = perceptual_roughness_to_roughness(material.sheen_roughness);
+
+    // Anisotropy needs a tangent frame to be oriented in; without one, it's ignored.
+    [branch]
+    if (material.anisotropy_strength > 0.0 && dot(ps.bitangent, ps.bitangent) > 0.0) {
+        const float3 direction_os = material.anisotropy_direction(ps.tangent, ps.bitangent);
+        float3 direction_ws = mul(instance_transforms_dyn[push_constants.draw_index].current, float4(direction_os, 0.0));
+
+        // Keep it in the plane of the final shading normal.
+        direction_ws -= normal_ws * dot(direction_ws, normal_ws);
+        if (dot(direction_ws, direction_ws) > 1e-8) {
+            gbuffer.anisotropy = material.anisotropy_strength;
+            gbuffer.anisotropy_direction = normalize(direction_ws);
+        }
+    }
 
     PsOut ps_out;
     ps_out.geometric_normal = geometric_normal_vs * 0.5 + 0.5;
     ps_out.gbuffer = asfloat(gbuffer.pack().data0);
     ps_out.velocity = float4(ps.prev_vs_pos - ps.vs_pos, 0);
+    ps_out.gbuffer_ext = gbuffer.pack_ext().data0;
 
     return ps_out;
 }
@@ -25,6 +25,7 @@ struct VsOut {
     [[vk::location(5)]] float3 bitangent: TEXCOORD5;
     [[vk::location(6)]] float3 vs_pos: TEXCOORD6;
     [[vk::location(7)]] float3 prev_vs_pos: TEXCOORD7;
+    [[vk::location(8)]] float2 uv1: TEXCOORD8;
 };
 
 VsOut main(uint vid: SV_VertexID, uint instance_index: SV_InstanceID) {
@@ -48,6 +49,7 @@ VsOut main(uint vid: SV_VertexID, uint instance_index: SV_InstanceID) {
             float4(1, 0, 0, 1));
 
     float2 uv = asfloat(vertices.Load2(vid * sizeof(float2) + mesh.vertex_uv_offset));
+    float2 uv1 = asfloat(vertices.Load2(vid * sizeof(float2) + mesh.vertex_uv1_offset));
     uint material_id = vertices.Load(vid * sizeof(uint) + mesh.vertex_mat_offset);
 
     //float3 ws_pos = v.position + float3(push_constants.instance_position);
@@ -56,13 +58,20 @@ VsOut main(uint vid: SV_VertexID, uint instance_index: SV_InstanceID) {
     float4 vs_pos = mul(frame_constants.view_constants.world_to_view, float4(ws_pos, 1.0));
     float4 cs_pos = mul(frame_constants.view_constants.view_to_sample, vs_pos);
 
-    float3 prev_ws_pos = mul(instance_transforms_dyn[push_constants.draw_index].previous, float4(v.position, 1.0));
+    // Deformed meshes also keep their vertex positions from the previous frame.
+    float3 prev_position =
+        select(mesh.vertex_prev_pos_offset != 0,
+            asfloat(vertices.Load3(vid * sizeof(float4) + mesh.vertex_prev_pos_offset)),
+            v.position);
+
+    float3 prev_ws_pos = mul(instance_transforms_dyn[push_constants.draw_index].previous, float4(prev_position, 1.0));
     float4 prev_vs_pos = mul(frame_constants.view_constants.world_to_view, float4(prev_ws_pos, 1.0));
     //float4 prev_cs_pos = mul(frame_constants.view_constants.view_to_sample, prev_vs_pos);
 
     vsout.position = cs_pos;
     vsout.color = v_color;
     vsout.uv = uv;
+    vsout.uv1 = uv1;
     vsout.normal = v.normal;
     vsout.material_id = material_id;
     vsout.tangent = v_tangent_packed.xyz;
@@ -0,0 +1,64 @@
+#include "../inc/material_samplers.hlsl"
+#include "../inc/mesh.hlsl"
+#include "../inc/bindless.hlsl"
+#include "../inc/rt.hlsl"
+
+struct RayHitAttrib {
+    float2 bary;
+};
+
+// The triangle an any-hit shader was invoked for.
+struct AnyHitTriangle {
+    Mesh mesh;
+    uint3 ind;
+    float3 barycentrics;
+    MeshMaterial material;
+
+    static AnyHitTriangle load(RayHitAttrib attrib) {
+        AnyHitTriangle res;
+        res.barycentrics = float3(1.0 - attrib.bary.x - attrib.bary.y, attrib.bary.x, attrib.bary.y);
+        res.mesh = meshes[InstanceID()];
+
+        const uint triangle_index = rt_hit_triangle_index(res.mesh);
+        res.ind = uint3(
+            vertices.Load((triangle_index * 3 + 0) * sizeof(uint) + res.mesh.index_offset),
+            vertices.Load((triangle_index * 3 + 1) * sizeof(uint) + res.mesh.index_offset),
+            vertices.Load((triangle_index * 3 + 2) * sizeof(uint) + res.mesh.index_offset)
+        );
+
+        const uint material_id = vertices.Load(res.ind.x * sizeof(uint) + res.mesh.vertex_mat_offset);
+        res.material = vertices.Load<MeshMaterial>(res.mesh.mat_data_offset + material_id * sizeof(MeshMaterial));
+
+        return res;
+    }
+
+    bool is_alpha_masked_out() {
+        if (0 == (material.flags & MESH_MATERIAL_FLAG_ALPHA_MASK)) {
+            return false;
+        }
+
+        float v_alpha = 1.0;
+        if (mesh.vertex_aux_offset != 0) {
+            float4 vc0 = asfloat(vertices.Load4(ind.x * sizeof(float4) + mesh.vertex_aux_offset));
+            float4 vc1 = asfloat(vertices.Load4(ind.y * sizeof(float4) + mesh.vertex_aux_offset));
+            float4 vc2 = asfloat(vertices.Load4(ind.z * sizeof(float4) + mesh.vertex_aux_offset));
+            v_alpha = vc0.a * barycentrics.x + vc1.a * barycentrics.y + vc2.a * barycentrics.z;
+        }
+
+        float2 uv0 = asfloat(vertices.Load2(ind.x * sizeof(float2) + mesh.vertex_uv_offset));
+        float2 uv1 = asfloat(vertices.Load2(ind.y * sizeof(float2) + mesh.vertex_uv_offset));
+        float2 uv2 = asfloat(vertices.Load2(ind.z * sizeof(float2) + mesh.vertex_uv_offset));
+        float2 uv = uv0 * barycentrics.x + uv1 * barycentrics.y + uv2 * barycentrics.z;
+
+        float2 uv_set1 =
+            asfloat(vertices.Load2(ind.x * sizeof(float2) + mesh.vertex_uv1_offset)) * barycentrics.x
+            + asfloat(vertices.Load2(ind.y * sizeof(float2) + mesh.vertex_uv1_offset)) * barycentrics.y
+            + asfloat(vertices.Load2(ind.z * sizeof(float2) + mesh.vertex_uv1_offset)) * barycentrics.z;
+
+        float2 albedo_uv = transform_material_uv(material, uv, uv_set1, 0);
+        Texture2D albedo_tex = bindless_textures[NonUniformResourceIndex(material.albedo_map)];
+        float alpha = sample_material_map_level(material, 0, albedo_tex, albedo_uv, 0).a * material.base_color_mult[3] * v_alpha;
+
+        return material.is_alpha_masked_out(alpha);
+    }
+};
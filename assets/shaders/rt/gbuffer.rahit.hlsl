@@ -0,0 +1,12 @@
+#include "any_hit_common.inc.hlsl"
+
+// Only invoked for non-opaque geometry: triangles with alpha-masked or transmissive materials.
+// G-buffer rays always stop at transmissive surfaces; the reference path tracer handles transmission itself.
+[shader("anyhit")]
+void main(inout GbufferRayPayload payload: SV_RayPayload, in RayHitAttrib attrib: SV_IntersectionAttributes) {
+    const AnyHitTriangle tri = AnyHitTriangle::load(attrib);
+
+    if (tri.is_alpha_masked_out()) {
+        IgnoreHit();
+    }
+}
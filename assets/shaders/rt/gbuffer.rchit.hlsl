@@ -1,5 +1,6 @@
 #include "../inc/math.hlsl"
 #include "../inc/samplers.hlsl"
+#include "../inc/material_samplers.hlsl"
 #include "../inc/mesh.hlsl"
 #include "../inc/pack_unpack.hlsl"
 #include "../inc/frame_constants.hlsl"
@@ -54,10 +55,11 @@ void main(inout GbufferRayPayload payload: SV_RayPayload, in RayHitAttrib attrib
     Mesh mesh = meshes[InstanceID()];
 
     // Indices of the triangle
+    const uint triangle_index = rt_hit_triangle_index(mesh);
     uint3 ind = uint3(
-        vertices.Load((PrimitiveIndex() * 3 + 0) * sizeof(uint) + mesh.index_offset),
-        vertices.Load((PrimitiveIndex() * 3 + 1) * sizeof(uint) + mesh.index_offset),
-        vertices.Load((PrimitiveIndex() * 3 + 2) * sizeof(uint) + mesh.index_offset)
+        vertices.Load((triangle_index * 3 + 0) * sizeof(uint) + mesh.index_offset),
+        vertices.Load((triangle_index * 3 + 1) * sizeof(uint) + mesh.index_offset),
+        vertices.Load((triangle_index * 3 + 2) * sizeof(uint) + mesh.index_offset)
     );
 
     Vertex v0 = unpack_vertex(VertexPacked(asfloat(vertices.Load4(ind.x * sizeof(float4) + mesh.vertex_core_offset))));
@@ -85,6 +87,11 @@ void main(inout GbufferRayPayload payload: SV_RayPayload, in RayHitAttrib attrib
     float2 uv2 = asfloat(vertices.Load2(ind.z * sizeof(float2) + mesh.vertex_uv_offset));
     float2 uv = uv0 * barycentrics.x + uv1 * barycentrics.y + uv2 * barycentrics.z;
 
+    float2 uv_set1 =
+        asfloat(vertices.Load2(ind.x * sizeof(float2) + mesh.vertex_uv1_offset)) * barycentrics.x
+        + asfloat(vertices.Load2(ind.y * sizeof(float2) + mesh.vertex_uv1_offset)) * barycentrics.y
+        + asfloat(vertices.Load2(ind.z * sizeof(float2) + mesh.vertex_uv1_offset)) * barycentrics.z;
+
     const float cone_width = payload.ray_cone.width_at_t(hit_dist);
     const float3 v0_pos_ws = mul(ObjectToWorld3x4(), float4(v0.position, 1.0));
     const float3 v1_pos_ws = mul(ObjectToWorld3x4(), float4(v1.position, 1.0));
@@ -94,19 +101,19 @@ void main(inout GbufferRayPayload payload: SV_RayPayload, in RayHitAttrib attrib
     uint material_id = vertices.Load(ind.x * sizeof(uint) + mesh.vertex_mat_offset);
     MeshMaterial material = vertices.Load<MeshMaterial>(mesh.mat_data_offset + material_id * sizeof(MeshMaterial));
 
-    float2 albedo_uv = transform_material_uv(material, uv, 0);
+    float2 albedo_uv = transform_material_uv(material, uv, uv_set1, 0);
     const BindlessTextureWithLod albedo_tex =
         compute_texture_lod(material.albedo_map, lod_triangle_constant, WorldRayDirection(), surf_normal_ws, cone_width);
 
     float3 albedo =
-        albedo_tex.tex.SampleLevel(sampler_llr, albedo_uv, albedo_tex.lod).xyz
+        sample_material_map_level(material, 0, albedo_tex.tex, albedo_uv, albedo_tex.lod).xyz
         * float4(material.base_color_mult).xyz
         * v_color.rgb;
 
-    float2 spec_uv = transform_material_uv(material, uv, 2);
+    float2 spec_uv = transform_material_uv(material, uv, uv_set1, 2);
     const BindlessTextureWithLod spec_tex =
         compute_texture_lod(material.spec_map, lod_triangle_constant, WorldRayDirection(), surf_normal_ws, cone_width);
-    float4 metalness_roughness = spec_tex.tex.SampleLevel(sampler_llr, spec_uv, spec_tex.lod);
+    float4 metalness_roughness = sample_material_map_level(material, 2, spec_tex.tex, spec_uv, spec_tex.lod);
     float perceptual_roughness = material.roughness_mult * metalness_roughness.x;
     float roughness = clamp(perceptual_roughness_to_roughness(perceptual_roughness), 1e-4, 1.0);
     float metalness = metalness_roughness.y * material.metalness_factor;
@@ -148,11 +155,11 @@ void main(inout GbufferRayPayload payload: SV_RayPayload, in RayHitAttrib attrib
         float3 tangent = tangent0 * barycentrics.x + tangent1 * barycentrics.y + tangent2 * barycentrics.z;
         float3 bitangent = bitangent0 * barycentrics.x + bitangent1 * barycentrics.y + bitangent2 * barycentrics.z;
 
-        float2 normal_uv = transform_material_uv(material, uv, 0);
+        float2 normal_uv = transform_material_uv(material, uv, uv_set1, 1);
         const BindlessTextureWithLod normal_tex =
             compute_texture_lod(material.normal_map, lod_triangle_constant, WorldRayDirection(), surf_normal_ws, cone_width);
 
-        float3 ts_normal = normal_tex.tex.SampleLevel(sampler_llr, normal_uv, normal_tex.lod).xyz * TODO;
+        float3 ts_normal = sample_material_map_level(material, 1, normal_tex.tex, normal_uv, normal_tex.lod).xyz * TODO;
 
         if (frame_constants.render_overrides.has_flag(RenderOverrideFlags::FLIP_NORMAL_MAP_YZ)) {
             ts_normal.zy *= -1;
@@ -166,7 +173,7 @@ void main(inout GbufferRayPayload payload: SV_RayPayload, in RayHitAttrib attrib
     }
 #endif
 
-    float2 emissive_uv = transform_material_uv(material, uv, 3);
+    float2 emissive_uv = transform_material_uv(material, uv, uv_set1, 3);
     const BindlessTextureWithLod emissive_tex =
         compute_texture_lod(material.emissive_map, lod_triangle_constant, WorldRayDirection(), surf_normal_ws, cone_width);
 
@@ -177,9 +184,9 @@ void main(inout GbufferRayPayload payload: SV_RayPayload, in RayHitAttrib attrib
     // since we need the direct contribution of the light's surface to the screen.
     if (0 == payload.path_length || 0 == (material.flags & MESH_MATERIAL_FLAG_EMISSIVE_USED_AS_LIGHT)) {
         emissive = 1.0.xxx
-            * emissive_tex.tex.SampleLevel(sampler_llr, emissive_uv, emissive_tex.lod).rgb
+            * sample_material_map_level(material, 3, emissive_tex.tex, emissive_uv, emissive_tex.lod).rgb
             * float3(material.emissive)
-            * instance_dynamic_parameters_dyn[InstanceIndex()].emissive_multiplier
+            * instance_dynamic_parameters_dyn[rt_hit_instance_index()].emissive_multiplier
             * frame_constants.pre_exposure;
     }
 
@@ -189,14 +196,42 @@ void main(inout GbufferRayPayload payload: SV_RayPayload, in RayHitAttrib attrib
     gbuffer.roughness = roughness;
     gbuffer.metalness = metalness;
     gbuffer.emissive = emissive;
+    gbuffer.clearcoat = material.clearcoat_factor;
+    gbuffer.clearcoat_roughness = clamp(perceptual_roughness_to_roughness(material.clearcoat_roughness), 1e-4, 1.0);
+    gbuffer.sheen_color = float3(material.sheen_color);
+    gbuffer.sheen_roughness = perceptual_roughness_to_roughness(material.sheen_roughness);
+
+    // Anisotropy needs a tangent frame to be oriented in; without one, it's ignored.
+    [branch]
+    if (material.anisotropy_strength > 0.0 && mesh.vertex_tangent_offset != 0) {
+        const float4 t0 = asfloat(vertices.Load4(ind.x * sizeof(float4) + mesh.vertex_tangent_offset));
+        const float4 t1 = asfloat(vertices.Load4(ind.y * sizeof(float4) + mesh.vertex_tangent_offset));
+        const float4 t2 = asfloat(vertices.Load4(ind.z * sizeof(float4) + mesh.vertex_tangent_offset));
+
+        const float3 tangent = t0.xyz * barycentrics.x + t1.xyz * barycentrics.y + t2.xyz * barycentrics.z;
+        const float3 bitangent = cross(normal, tangent) * t0.w;
+
+        float3 direction_ws = mul(ObjectToWorld3x4(), float4(material.anisotropy_direction(tangent, bitangent), 0.0));
+        direction_ws -= gbuffer.normal * dot(direction_ws, gbuffer.normal);
+        if (dot(direction_ws, direction_ws) > 1e-8) {
+            gbuffer.anisotropy = material.anisotropy_strength;
+            gbuffer.anisotropy_direction = normalize(direction_ws);
+        }
+    }
 
-    // Force double-sided
-    if (dot(WorldRayDirection(), gbuffer.normal) > 0) {
+    // Back faces of double-sided materials are shaded with flipped normals. Those of single-sided
+    // ones are culled like in raster, but rays which ask for all back faces (`cull_back_faces` off)
+    // still hit them; those keep facing away, so that they shade as occluders.
+    // Facing is determined in object space, so mirrored instances agree with raster too.
+    if (0 != (material.flags & MESH_MATERIAL_FLAG_DOUBLE_SIDED) && HitKind() == HIT_KIND_TRIANGLE_BACK_FACE) {
         gbuffer.normal *= -1;
     }
 
     //gbuffer.albedo = float3(0.966653, 0.802156, 0.323968); // Au from Mitsuba
 
     payload.gbuffer_packed = gbuffer.pack();
+    payload.gbuffer_ext_packed = gbuffer.pack_ext();
     payload.t = RayTCurrent();
+    payload.transmission = material.transmission_factor;
+    payload.ior = material.ior;
 }
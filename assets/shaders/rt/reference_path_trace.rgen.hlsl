@@ -5,6 +5,7 @@
 #include "../inc/brdf.hlsl"
 #include "../inc/brdf_lut.hlsl"
 #include "../inc/layered_brdf.hlsl"
+#include "../inc/mesh.hlsl"
 #include "../inc/rt.hlsl"
 #include "../inc/quasi_random.hlsl"
 #include "../inc/bindless_textures.hlsl"
@@ -168,6 +169,7 @@ void main() {
                         ));
 
                     GbufferData gbuffer = primary_hit.gbuffer_packed.unpack();
+                    primary_hit.gbuffer_ext_packed.unpack_into(gbuffer);
 
 
                     if (SHOW_ALBEDO) {
@@ -175,9 +177,29 @@ void main() {
                         return;
                     }
 
+                    // Thin-surface transmission: the light either passes straight through,
+                    // tinted by the base color, or interacts with the surface as usual.
+                    if (primary_hit.transmission > 0.0) {
+                        const float transmission_p = thin_surface_transmission_probability(
+                            primary_hit.transmission,
+                            primary_hit.ior,
+                            abs(dot(gbuffer.normal, outgoing_ray.Direction)));
+
+                        if (uint_to_u01_float(hash1_mut(rng)) < transmission_p) {
+                            throughput *= gbuffer.albedo;
+                            outgoing_ray.Origin = primary_hit.position;
+                            outgoing_ray.TMin = 1e-4;
+                            continue;
+                        }
+
+                        throughput /= 1.0 - transmission_p;
+                        gbuffer.albedo *= 1.0 - primary_hit.transmission;
+                    }
+
                     if (dot(gbuffer.normal, outgoing_ray.Direction) >= 0.0) {
-                        if (0 == path_length) {
-                            // Flip the normal for primary hits so we don't see blackness
+                        if (0 == path_length || primary_hit.transmission > 0.0) {
+                            // Flip the normal for primary hits so we don't see blackness,
+                            // and for thin transmissive surfaces, which are hit from both sides.
                             gbuffer.normal = -gbuffer.normal;
                         } else {
                             break;
@@ -293,7 +315,7 @@ void main() {
 
                                     total_radiance +=
                                         select(is_shadowed, 0,
-                                            throughput * triangle_light.radiance() * brdf.evaluate(wo, wi) / light_sample.pdf.value * to_psa_metric / light_selection_pmf);
+                                            throughput * triangle_light.radiance_towards(-to_light_norm_ws) * brdf.evaluate(wo, wi) / light_sample.pdf.value * to_psa_metric / light_selection_pmf);
                                 }
                             }
                         }
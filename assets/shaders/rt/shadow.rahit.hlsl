@@ -0,0 +1,25 @@
+#include "../inc/frame_constants.hlsl"
+#include "../inc/hash.hlsl"
+#include "any_hit_common.inc.hlsl"
+
+// The any-hit shader of the shadow ray hit group; see `rt_is_shadowed`.
+// Only invoked for non-opaque geometry, like `gbuffer.rahit.hlsl`.
+[shader("anyhit")]
+void main(inout ShadowRayPayload payload: SV_RayPayload, in RayHitAttrib attrib: SV_IntersectionAttributes) {
+    const AnyHitTriangle tri = AnyHitTriangle::load(attrib);
+
+    // Shadow rays can't be tinted, so let them through thin transmissive surfaces stochastically.
+    if (tri.material.transmission_factor > 0.0) {
+        const uint seed = hash_combine2(
+            hash_combine2(DispatchRaysIndex().x, hash1(DispatchRaysIndex().y)),
+            hash_combine2(PrimitiveIndex(), frame_constants.frame_index));
+
+        if (uint_to_u01_float(hash1(seed)) < thin_surface_transmission_probability(tri.material.transmission_factor, tri.material.ior, 1.0)) {
+            IgnoreHit();
+        }
+    }
+
+    if (tri.is_alpha_masked_out()) {
+        IgnoreHit();
+    }
+}
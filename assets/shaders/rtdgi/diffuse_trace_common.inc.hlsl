@@ -181,7 +181,7 @@ TraceResult do_the_thing(uint2 px, float3 normal_ws, inout uint rng, RayDesc out
                         #endif
 
                         total_radiance +=
-                            select(!is_shadowed, (triangle_light.radiance() * brdf_value / light_sample.pdf.value), 0);
+                            select(!is_shadowed, (triangle_light.radiance_towards(-to_light_norm_ws) * brdf_value / light_sample.pdf.value), 0);
                     }
                 }
             }
@@ -56,9 +56,13 @@ void main() {
     gbuffer.roughness = max(gbuffer.roughness, RTR_ROUGHNESS_CLAMP);
 
     // Initially, the candidate buffers contain candidates generated via diffuse tracing.
-    // For rough surfaces we can skip generating new candidates just for reflections.
+    // For rough surfaces we can skip generating new candidates just for reflections,
+    // unless they have a smoother clearcoat.
     // TODO: make this metric depend on spec contrast
-    if (reuse_rtdgi_rays && gbuffer.roughness > 0.6) {
+    const float min_lobe_roughness = gbuffer.clearcoat > 0.0
+        ? min(gbuffer.roughness, gbuffer.clearcoat_roughness)
+        : gbuffer.roughness;
+    if (reuse_rtdgi_rays && min_lobe_roughness > 0.6) {
         return;
     }
 
@@ -82,13 +86,24 @@ void main() {
         wo = normalize(wo);
     }
 
-    SpecularBrdf specular_brdf;
-    specular_brdf.albedo = lerp(0.04, gbuffer.albedo, gbuffer.metalness);
-    specular_brdf.roughness = gbuffer.roughness;
+    SpecularBrdf base_brdf;
+    base_brdf.albedo = lerp(0.04, gbuffer.albedo, gbuffer.metalness);
+    base_brdf.roughness = gbuffer.roughness;
 
     const uint noise_offset = frame_constants.frame_index * select(USE_TEMPORAL_JITTER, 1, 0);
     uint rng = hash3(uint3(px, noise_offset));
 
+    // The resolve pass weights the rays by both specular lobes, so pick one to sample
+    // with the same probabilities that `LayeredBrdf::evaluate_specular_lobes` assumes.
+    const LayeredBrdf layered_brdf = LayeredBrdf::from_gbuffer_ndotv(gbuffer, wo.z);
+    const float coat_p = layered_brdf.specular_lobe_clearcoat_probability();
+    const bool sample_clearcoat = uint_to_u01_float(hash1_mut(rng)) < coat_p;
+
+    SpecularBrdf specular_brdf = base_brdf;
+    if (sample_clearcoat) {
+        specular_brdf = layered_brdf.clearcoat_brdf;
+    }
+
 #if 1
     // Note: since this is pre-baked for various SPP, can run into undersampling
     float2 urand = float2(
@@ -131,7 +146,7 @@ void main() {
 
         //uint rng = hash2(px);
         rng_out_tex[px] = rng;
-        RtrTraceResult result = do_the_thing(px, gbuffer.normal, gbuffer.roughness, rng, outgoing_ray);
+        RtrTraceResult result = do_the_thing(px, gbuffer.normal, specular_brdf.roughness, rng, outgoing_ray);
 
         const float3 direction_vs = direction_world_to_view(outgoing_ray.Direction);
         const float to_surface_area_measure =
@@ -144,20 +159,33 @@ void main() {
 
         const float3 hit_offset_ws = outgoing_ray.Direction * result.hit_t;
 
-        SpecularBrdfEnergyPreservation brdf_lut = SpecularBrdfEnergyPreservation::from_brdf_ndotv(specular_brdf, wo.z);
+        // When sampling the BRDF in a path tracer, a certain fraction of samples
+        // taken will be invalid. In the specular filtering pipe we force them all to be valid
+        // in order to get the most out of our kernels. We also simply discard any rays
+        // going in the wrong direction when reusing neighborhood samples, and renormalize,
+        // whereas in a regular integration loop, we'd still count them.
+        // Here we adjust the value back to what it would be if a fraction was returned invalid.
+        const float base_valid_sample_fraction =
+            SpecularBrdfEnergyPreservation::from_brdf_ndotv(base_brdf, wo.z).valid_sample_fraction;
+        float lobe_pdf = brdf_sample.pdf / base_valid_sample_fraction;
+
+        [branch]
+        if (coat_p > 0.0) {
+            // Either lobe could have produced the direction; store the pdf of the mixture.
+            const float base_pdf = sample_clearcoat ? base_brdf.evaluate(wo, brdf_sample.wi).pdf : brdf_sample.pdf;
+            const float coat_pdf = sample_clearcoat ? brdf_sample.pdf : layered_brdf.clearcoat_brdf.evaluate(wo, brdf_sample.wi).pdf;
+
+            lobe_pdf = lerp(
+                base_pdf / base_valid_sample_fraction,
+                coat_pdf / layered_brdf.clearcoat_energy_preservation.valid_sample_fraction,
+                coat_p);
+        }
 
         const float pdf =
             #if RTR_PDF_STORED_WITH_SURFACE_AREA_METRIC
                 to_surface_area_measure *
             #endif
-            brdf_sample.pdf /
-            // When sampling the BRDF in a path tracer, a certain fraction of samples
-            // taken will be invalid. In the specular filtering pipe we force them all to be valid
-            // in order to get the most out of our kernels. We also simply discard any rays
-            // going in the wrong direction when reusing neighborhood samples, and renormalize,
-            // whereas in a regular integration loop, we'd still count them.
-            // Here we adjust the value back to what it would be if a fraction was returned invalid.
-            brdf_lut.valid_sample_fraction;
+            lobe_pdf;
 
         out0_tex[px] = float4(result.total_radiance, rtr_encode_cos_theta_for_fp16(cos_theta));
         out1_tex[px] = float4(hit_offset_ws, pdf);
@@ -196,7 +196,7 @@ RtrTraceResult do_the_thing(uint2 px, float3 normal_ws, float roughness, inout u
                                 #endif
 
                                 total_radiance +=
-                                    select(!is_shadowed, (triangle_light.radiance() * brdf_value / light_sample.pdf.value), 0);
+                                    select(!is_shadowed, (triangle_light.radiance_towards(-to_light_norm_ws) * brdf_value / light_sample.pdf.value), 0);
                             }
                         }
                     }
@@ -123,12 +123,10 @@ void main(uint2 px : SV_DispatchThreadID, uint2 px_tile: SV_GroupID, uint idx_wi
         wo = normalize(wo);
     }
 
-    SpecularBrdf specular_brdf;
-    {
-        LayeredBrdf layered_brdf = LayeredBrdf::from_gbuffer_ndotv(gbuffer, wo.z);
-        specular_brdf = layered_brdf.specular_brdf;
-    }
-    const float f0_grey = sRGB_to_luminance(specular_brdf.albedo);
+    // The reflections are resolved for both specular lobes: that of the base material,
+    // and that of the clearcoat, if any.
+    LayeredBrdf layered_brdf = LayeredBrdf::from_gbuffer_ndotv(gbuffer, wo.z);
+    const float f0_grey = sRGB_to_luminance(layered_brdf.specular_brdf.albedo);
 
     // Index used to calculate a sample set disjoint for all four pixels in the quad
     // Offsetting by frame index reduces small structured artifacts
@@ -540,7 +538,7 @@ void main(uint2 px : SV_DispatchThreadID, uint2 px_tile: SV_GroupID, uint idx_wi
         		}
             #endif
 
-            BrdfValue spec = specular_brdf.evaluate(wo, wi);
+            BrdfValue spec = layered_brdf.evaluate_specular_lobes(wo, wi);
 
             // The FG weight is included in the radiance accumulator,
             // so should not be in the ratio estimator weight.
@@ -642,16 +640,14 @@ void main(uint2 px : SV_DispatchThreadID, uint2 px_tile: SV_GroupID, uint idx_wi
     //ex2 /= contrib_norm_factor;
     ray_len_accum /= contrib_norm_factor;
 
-    SpecularBrdfEnergyPreservation brdf_lut = SpecularBrdfEnergyPreservation::from_brdf_ndotv(specular_brdf, wo.z);
-
     #if !RTR_RENDER_SCALED_BY_FG
-        contrib_accum.rgb /= brdf_lut.preintegrated_reflection;
+        contrib_accum.rgb /= layered_brdf.preintegrated_specular_reflection();
     #endif
 
-    // The invalid samples are in reality multi-scater events, and here we adjust for that.
+    // The invalid samples are in reality multi-scater events. `evaluate_specular_lobes` adjusts
+    // for that via `preintegrated_reflection_mult`, separately for each lobe.
     // Note that while the `valid_sample_fraction` is a grayscale multiplier,
     // this is chromatic, and will cause an increase in saturation on conductors.
-    contrib_accum.rgb *= brdf_lut.preintegrated_reflection_mult;
 
     ray_len_accum = exponential_unsquish(ray_len_accum, ray_squish_scale);
     
@@ -182,7 +182,7 @@ void main() {
 
                             irradiance_sum +=
                                 select(is_shadowed, 0,
-                                    gbuffer.albedo * triangle_light.radiance() * brdf.evaluate(wo, wi) / light_sample.pdf.value * to_psa_metric / light_selection_pmf);
+                                    gbuffer.albedo * triangle_light.radiance_towards(-to_light_norm_ws) * brdf.evaluate(wo, wi) / light_sample.pdf.value * to_psa_metric / light_selection_pmf);
                         }
                     }
                 }
@@ -163,7 +163,7 @@ void main() {
                     #endif
 
                     total_radiance +=
-                        select(!is_shadowed, (triangle_light.radiance() * brdf_value / light_sample.pdf.value), 0);
+                        select(!is_shadowed, (triangle_light.radiance_towards(-to_light_norm_ws) * brdf_value / light_sample.pdf.value), 0);
                 }
             }
 
@@ -0,0 +1,299 @@
+use anyhow::{Context as _, Result};
+use kajiya_asset::mesh::{
+    BakedAssetHeader, FlatAsset, GpuImage, MeshMaterialFlags, PackedTriMesh, validate_baked_asset,
+};
+use kajiya_backend::ash::vk;
+use std::path::Path;
+
+use crate::format_bytes;
+
+/// Names of the slots in `MeshMaterial::maps`, in order.
+const MATERIAL_MAP_NAMES: [&str; 5] = ["normal", "spec", "albedo", "emissive", "occlusion"];
+
+/// The contents of a baked file, kept in `u64`s so that the flattened data is aligned
+/// just like in a memory-mapped file.
+struct BakedFile {
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl BakedFile {
+    fn read(path: &Path) -> Result<Self> {
+        let bytes = std::fs::read(path).with_context(|| format!("Reading {:?}", path))?;
+
+        let mut words = vec![0u64; bytes.len().div_ceil(8)];
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                bytes.as_ptr(),
+                words.as_mut_ptr() as *mut u8,
+                bytes.len(),
+            );
+        }
+
+        Ok(Self {
+            words,
+            len: bytes.len(),
+        })
+    }
+
+    fn bytes(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.words.as_ptr() as *const u8, self.len) }
+    }
+
+    fn asset<T: FlatAsset>(&self, path: &Path) -> Result<&T> {
+        validate_baked_asset::<T>(self.bytes()).with_context(|| format!("Loading {:?}", path))
+    }
+}
+
+/// Where an image referenced by a baked mesh lives, relative to the mesh.
+fn image_path(mesh_path: &Path, identity: u64) -> std::path::PathBuf {
+    mesh_path.with_file_name(format!("{:8.8x}.image", identity))
+}
+
+/// Prints a summary of a baked `.mesh` or `.image`. With `dump_mip`, also writes
+/// mip level `mip` of an image out to a `.png` or `.dds` file.
+pub fn inspect_baked_asset(path: &Path, mip: usize, dump_mip: Option<&Path>) -> Result<()> {
+    let header = BakedAssetHeader::read_from_file(path)?;
+    let file = BakedFile::read(path)?;
+
+    println!(
+        "{:?}: format version {}, {}",
+        path,
+        header.format_version,
+        format_bytes(file.len as u64)
+    );
+
+    if header.asset_type == <PackedTriMesh::Flat as FlatAsset>::ASSET_TYPE {
+        anyhow::ensure!(dump_mip.is_none(), "Only images can be dumped");
+        inspect_mesh(path, file.asset::<PackedTriMesh::Flat>(path)?);
+        Ok(())
+    } else if header.asset_type == <GpuImage::Flat as FlatAsset>::ASSET_TYPE {
+        let image = file.asset::<GpuImage::Flat>(path)?;
+        inspect_image(image);
+
+        if let Some(dump_path) = dump_mip {
+            dump_image_mip(image, mip, dump_path)?;
+            println!("Wrote mip {} to {:?}", mip, dump_path);
+        }
+
+        Ok(())
+    } else {
+        anyhow::bail!(
+            "{:?} is a {:?}, which can't be inspected",
+            path,
+            String::from_utf8_lossy(&header.asset_type).trim_end_matches('\0')
+        )
+    }
+}
+
+fn inspect_mesh(path: &Path, mesh: &PackedTriMesh::Flat) {
+    println!("Mesh:");
+    println!("  vertices: {}", mesh.verts.len());
+    println!(
+        "  indices: {} ({} triangles)",
+        mesh.indices.len(),
+        mesh.indices.len() / 3
+    );
+    println!(
+        "  uvs: {}, uvs1: {}, tangents: {}, colors: {}",
+        mesh.uvs.len(),
+        mesh.uvs1.len(),
+        mesh.tangents.len(),
+        mesh.colors.len()
+    );
+
+    if mesh.verts.is_empty() {
+        println!("  bounds: empty");
+    } else {
+        let (min, max) = mesh.verts.iter().fold(
+            ([f32::MAX; 3], [f32::MIN; 3]),
+            |(mut min, mut max), vert| {
+                for i in 0..3 {
+                    min[i] = min[i].min(vert.pos[i]);
+                    max[i] = max[i].max(vert.pos[i]);
+                }
+                (min, max)
+            },
+        );
+        println!("  bounds: {:?} .. {:?}", min, max);
+    }
+
+    println!("  levels of detail: {}", mesh.lod_count());
+    for lod in 1..mesh.lod_count() {
+        println!(
+            "    {}: {} triangles, error {}",
+            lod,
+            mesh.lod_indices(lod).len() / 3,
+            mesh.lod_error(lod)
+        );
+    }
+
+    if !mesh.skin_joints.is_empty() {
+        println!(
+            "  skin: {} joints, {} animation clips, {} channels",
+            mesh.skin_joints.len(),
+            mesh.animation_clip_names.len(),
+            mesh.animation_channels.len()
+        );
+        for (i, name) in mesh.animation_clip_names.iter().enumerate() {
+            println!(
+                "    clip {}: {:?}",
+                i,
+                String::from_utf8_lossy(name.as_slice())
+            );
+        }
+    }
+
+    if !mesh.morph_default_weights.is_empty() {
+        println!(
+            "  morph targets: {}, default weights {:?}",
+            mesh.morph_default_weights.len(),
+            mesh.morph_default_weights.as_slice()
+        );
+    }
+
+    println!("Materials: {}", mesh.materials.len());
+    for (i, material) in mesh.materials.iter().enumerate() {
+        let mut flags = Vec::new();
+        if material.flags & MeshMaterialFlags::MESH_MATERIAL_FLAG_EMISSIVE_USED_AS_LIGHT != 0 {
+            flags.push("emissive light");
+        }
+        if material.flags & MeshMaterialFlags::MESH_MATERIAL_FLAG_ALPHA_MASK != 0 {
+            flags.push("alpha mask");
+        }
+        if material.flags & MeshMaterialFlags::MESH_MATERIAL_FLAG_DOUBLE_SIDED != 0 {
+            flags.push("double sided");
+        }
+
+        println!(
+            "  #{}: base color {:?}, roughness {}, metalness {}, emissive {:?}, flags [{}]",
+            i,
+            material.base_color_mult,
+            material.roughness_mult,
+            material.metalness_factor,
+            material.emissive,
+            flags.join(", ")
+        );
+
+        let maps = MATERIAL_MAP_NAMES
+            .iter()
+            .zip(material.maps)
+            .map(|(name, map)| format!("{} {}", name, map))
+            .collect::<Vec<_>>();
+        println!("      maps: {}", maps.join(", "));
+    }
+
+    println!("Maps: {}", mesh.maps.len());
+    for (i, map) in mesh.maps.iter().enumerate() {
+        let image_path = image_path(path, map.identity());
+        let image = BakedFile::read(&image_path).and_then(|file| {
+            let image = file.asset::<GpuImage::Flat>(&image_path)?;
+            let extent = image.extent;
+            Ok(format!(
+                "{:?} {}x{}, {} mips",
+                { image.format },
+                extent[0],
+                extent[1],
+                image.mips.len()
+            ))
+        });
+
+        match image {
+            Ok(image) => println!("  {}: {:8.8x}: {}", i, map.identity(), image),
+            Err(err) => println!("  {}: {:8.8x}: {:#}", i, map.identity(), err),
+        }
+    }
+}
+
+fn inspect_image(image: &GpuImage::Flat) {
+    let extent = image.extent;
+
+    println!("Image:");
+    println!("  format: {:?}", { image.format });
+    println!("  extent: {}x{}x{}", extent[0], extent[1], extent[2]);
+    println!("  mips: {}", image.mips.len());
+
+    let mut total_bytes = 0;
+    for (level, mip) in image.mips.iter().enumerate() {
+        let [width, height] = mip_extent(image, level);
+        println!("    {}: {}x{}, {} bytes", level, width, height, mip.len());
+        total_bytes += mip.len() as u64;
+    }
+
+    println!("  total: {}", format_bytes(total_bytes));
+}
+
+fn mip_extent(image: &GpuImage::Flat, level: usize) -> [u32; 2] {
+    [
+        (image.extent[0] >> level).max(1),
+        (image.extent[1] >> level).max(1),
+    ]
+}
+
+fn dump_image_mip(image: &GpuImage::Flat, mip: usize, path: &Path) -> Result<()> {
+    anyhow::ensure!(
+        mip < image.mips.len(),
+        "The image only has {} mips",
+        image.mips.len()
+    );
+
+    let [width, height] = mip_extent(image, mip);
+    let data = image.mips[mip].as_slice();
+    let format = image.format;
+
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("png") => {
+            anyhow::ensure!(
+                matches!(
+                    format,
+                    vk::Format::R8G8B8A8_UNORM | vk::Format::R8G8B8A8_SRGB
+                ),
+                "{:?} images can't be written to PNG; use DDS instead",
+                format
+            );
+
+            let png = image::RgbaImage::from_raw(width, height, data.to_vec())
+                .context("The mip data doesn't match its extent")?;
+            png.save(path)
+                .with_context(|| format!("Writing {:?}", path))
+        }
+        Some("dds") => {
+            let mut dds = ddsfile::Dds::new_dxgi(ddsfile::NewDxgiParams {
+                height,
+                width,
+                depth: None,
+                format: dxgi_format(format)?,
+                mipmap_levels: Some(1),
+                array_layers: None,
+                caps2: None,
+                is_cubemap: false,
+                resource_dimension: ddsfile::D3D10ResourceDimension::Texture2D,
+                alpha_mode: ddsfile::AlphaMode::Unknown,
+            })?;
+            dds.data = data.to_vec();
+
+            let mut file =
+                std::fs::File::create(path).with_context(|| format!("Creating {:?}", path))?;
+            dds.write(&mut file)
+                .with_context(|| format!("Writing {:?}", path))
+        }
+        _ => anyhow::bail!("Mips can only be dumped to .png or .dds files"),
+    }
+}
+
+fn dxgi_format(format: vk::Format) -> Result<ddsfile::DxgiFormat> {
+    use ddsfile::DxgiFormat;
+
+    Ok(match format {
+        vk::Format::R8G8B8A8_UNORM => DxgiFormat::R8G8B8A8_UNorm,
+        vk::Format::R8G8B8A8_SRGB => DxgiFormat::R8G8B8A8_UNorm_sRGB,
+        vk::Format::BC1_RGB_SRGB_BLOCK => DxgiFormat::BC1_UNorm_sRGB,
+        vk::Format::BC3_UNORM_BLOCK => DxgiFormat::BC3_UNorm,
+        vk::Format::BC3_SRGB_BLOCK => DxgiFormat::BC3_UNorm_sRGB,
+        vk::Format::BC5_UNORM_BLOCK => DxgiFormat::BC5_UNorm,
+        vk::Format::BC5_SNORM_BLOCK => DxgiFormat::BC5_SNorm,
+        vk::Format::BC7_UNORM_BLOCK => DxgiFormat::BC7_UNorm,
+        vk::Format::BC7_SRGB_BLOCK => DxgiFormat::BC7_UNorm_sRGB,
+        _ => anyhow::bail!("{:?} can't be written to DDS", format),
+    })
+}
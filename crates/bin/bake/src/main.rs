@@ -1,29 +1,150 @@
+mod inspect;
+
 use anyhow::Result;
 use kajiya_asset_pipe::*;
-use std::path::PathBuf;
+use kajiya_backend::set_vfs_mount_point;
+use std::path::{Path, PathBuf};
 use structopt::StructOpt;
 
 #[derive(Debug, StructOpt)]
 #[structopt(name = "bake", about = "Kanelbullar")]
 struct Opt {
-    #[structopt(long, parse(from_os_str))]
-    scene: PathBuf,
+    /// A glTF, or a Wavefront OBJ file with its MTL materials
+    #[structopt(long, parse(from_os_str), required_unless_one = &["gc", "scene-desc", "inspect"])]
+    scene: Option<PathBuf>,
+
+    /// A `.ron` scene description; bakes all the meshes it refers to
+    #[structopt(long, parse(from_os_str), conflicts_with = "scene")]
+    scene_desc: Option<PathBuf>,
 
     #[structopt(long, default_value = "1.0")]
     scale: f32,
 
-    #[structopt(short = "o")]
-    output_name: String,
+    #[structopt(short = "o", required_unless_one = &["gc", "scene-desc", "inspect"])]
+    output_name: Option<String>,
+
+    /// Bake each unique mesh once, plus the node hierarchy instancing them,
+    /// instead of flattening the scene into a single mesh. glTF only
+    #[structopt(long, conflicts_with = "scene-desc")]
+    instanced: bool,
+
+    /// Delete baked images which no baked mesh refers to, and report cache disk usage
+    #[structopt(long)]
+    gc: bool,
+
+    /// With `--gc`, only report what would be deleted
+    #[structopt(long, requires = "gc")]
+    dry_run: bool,
+
+    /// Print a summary of a baked `.mesh` or `.image` file
+    #[structopt(long, parse(from_os_str), conflicts_with_all = &["scene", "scene-desc", "gc"])]
+    inspect: Option<PathBuf>,
+
+    /// With `--inspect` on an image, write one of its mips to a `.png` or `.dds` file
+    #[structopt(long, parse(from_os_str), requires = "inspect")]
+    dump_mip: Option<PathBuf>,
+
+    /// The mip level written by `--dump-mip`
+    #[structopt(long, default_value = "0")]
+    mip: usize,
+}
+
+fn format_bytes(bytes: u64) -> String {
+    format!("{:.1} MiB", bytes as f64 / (1024.0 * 1024.0))
+}
+
+fn collect_garbage(dry_run: bool) -> Result<()> {
+    let report = collect_cache_garbage(Path::new("cache"), dry_run)?;
+
+    println!("Cache usage per source asset (images shared between meshes are counted for each):");
+    for mesh in &report.meshes {
+        println!(
+            "  {:?} -> {:?}: mesh {}, images {}",
+            mesh.source,
+            mesh.mesh_path,
+            format_bytes(mesh.mesh_bytes),
+            format_bytes(mesh.image_bytes)
+        );
+    }
+
+    for (path, err) in &report.unreadable_manifests {
+        println!(
+            "Ignoring unreadable manifest {:?}; its mesh will be baked again: {:#}",
+            path, err
+        );
+    }
+
+    for path in &report.deleted_files {
+        println!(
+            "{} {:?}",
+            if dry_run { "Would delete" } else { "Deleted" },
+            path
+        );
+    }
+
+    println!(
+        "{} {} in {} files; {} remaining.",
+        if dry_run { "Would free" } else { "Freed" },
+        format_bytes(report.deleted_bytes),
+        report.deleted_files.len(),
+        format_bytes(report.remaining_bytes)
+    );
+
+    Ok(())
+}
+
+fn bake_scene_desc(path: &Path) -> Result<()> {
+    let scene_desc = SceneDesc::load(path)?;
+    let results = process_scene_mesh_assets(&scene_desc);
+
+    let mut failed_count = 0;
+    for (mesh, result) in &results {
+        match result {
+            Ok(MeshBakeOutcome::Baked) => println!("Baked {}", mesh),
+            Ok(MeshBakeOutcome::UpToDate) => println!("Up to date: {}", mesh),
+            Err(err) => {
+                failed_count += 1;
+                println!("FAILED {}: {:#}", mesh, err);
+            }
+        }
+    }
+
+    if failed_count > 0 {
+        anyhow::bail!(
+            "{} of {} meshes in {:?} failed to bake",
+            failed_count,
+            results.len(),
+            path
+        );
+    }
+
+    Ok(())
 }
 
 fn main() -> Result<()> {
     env_logger::init();
 
+    // Scene descriptions refer to meshes via the same mount point as the `view` app.
+    set_vfs_mount_point("/meshes", "assets/meshes");
+
     let opt = Opt::from_args();
 
+    if opt.gc {
+        return collect_garbage(opt.dry_run);
+    }
+
+    if let Some(path) = opt.inspect.as_ref() {
+        return inspect::inspect_baked_asset(path, opt.mip, opt.dump_mip.as_deref());
+    }
+
+    if let Some(scene_desc) = opt.scene_desc.as_ref() {
+        return bake_scene_desc(scene_desc);
+    }
+
     process_mesh_asset(MeshAssetProcessParams {
-        path: opt.scene,
-        output_name: opt.output_name,
+        path: opt.scene.unwrap(),
+        output_name: opt.output_name.unwrap(),
         scale: opt.scale,
+        instanced: opt.instanced,
     })
 }
@@ -7,7 +7,7 @@ use kajiya_simple::main_loop::FrameContext;
 
 use crate::{
     PersistedState,
-    runtime::{MAX_FPS_LIMIT, RuntimeState},
+    runtime::{MAX_FPS_LIMIT, RuntimeState, remove_setup_lights, scene_cameras},
 };
 
 impl RuntimeState {
@@ -138,6 +138,11 @@ impl RuntimeState {
                         .spatial_reuse_pass_count
                         .clamp(1, 3);
 
+                    imgui::Drag::<f32, &str>::new("Mesh LOD error (pixels)")
+                        .range(0.0, 16.0)
+                        .speed(0.05)
+                        .build(ui, &mut ctx.world_renderer.mesh_lod_pixel_error);
+
                     ui.checkbox(
                         "Ray-traced reservoir visibility",
                         &mut ctx.world_renderer.rtdgi.use_raytraced_reservoir_visibility,
@@ -168,6 +173,22 @@ impl RuntimeState {
                         ui.text("Drag a sphere-mapped .hdr/.exr to load as IBL");
                     }
 
+                    let mut camera_to_jump_to = None;
+                    for (idx, (camera, transform)) in scene_cameras(persisted).enumerate() {
+                        let name = camera
+                            .name
+                            .clone()
+                            .unwrap_or_else(|| format!("Camera {}", idx));
+
+                        if ui.button(format!("{}##scene_camera{}", name, idx)) {
+                            camera_to_jump_to = Some((camera.clone(), transform));
+                        }
+                    }
+
+                    if let Some((camera, transform)) = camera_to_jump_to {
+                        self.jump_to_scene_camera(persisted, &camera, transform);
+                    }
+
                     let mut element_to_remove = None;
                     for (idx, elem) in persisted.scene.elements.iter_mut().enumerate() {
                         ui.dummy([0.0, 10.0]);
@@ -238,12 +259,64 @@ impl RuntimeState {
                                 .build(ui, &mut elem.transform.rotation_euler_degrees.z);
                         }
 
+                        // Animation clip, from the first skinned instance
+                        let clips = elem
+                            .instances
+                            .iter()
+                            .map(|instance| {
+                                ctx.world_renderer.instance_animation_clips(instance.handle)
+                            })
+                            .find(|clips| !clips.is_empty());
+                        if let Some(clips) = clips {
+                            let items: Vec<String> =
+                                std::iter::once("Rest pose".to_owned())
+                                    .chain(clips.iter().map(|clip| {
+                                        format!("{} ({:.1}s)", clip.name, clip.duration)
+                                    }))
+                                    .collect();
+
+                            let mut selected = elem.animation_clip.map_or(0, |clip| clip + 1);
+                            ui.set_next_item_width(200.0);
+                            if ui.combo("animation", &mut selected, &items, |item| {
+                                Cow::Borrowed(item.as_str())
+                            }) {
+                                elem.animation_clip = selected.checked_sub(1);
+                            }
+                        }
+
+                        // Morph target weights
+                        for (instance_idx, instance) in elem.instances.iter().enumerate() {
+                            let instance_id_token = ui.push_id(format!("{}", instance_idx as i32));
+
+                            for (target, weight) in ctx
+                                .world_renderer
+                                .get_instance_morph_weights_mut(instance.handle)
+                                .iter_mut()
+                                .enumerate()
+                            {
+                                ui.set_next_item_width(200.0);
+                                if imgui::Drag::<f32, String>::new(format!("morph {}", target))
+                                    .range(0.0, 1.0)
+                                    .speed(0.01)
+                                    .build(ui, weight)
+                                {
+                                    self.reset_path_tracer = true;
+                                }
+                            }
+
+                            instance_id_token.pop();
+                        }
+
                         id_token.pop();
                     }
 
                     if let Some(idx) = element_to_remove {
-                        let elem = persisted.scene.elements.remove(idx);
-                        ctx.world_renderer.remove_instance(elem.instance);
+                        let mut elem = persisted.scene.elements.remove(idx);
+                        for instance in elem.instances {
+                            ctx.world_renderer.remove_instance(instance.handle);
+                        }
+
+                        remove_setup_lights(ctx.world_renderer, &mut elem.setup);
                     }
                 }
 
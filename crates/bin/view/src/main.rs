@@ -4,7 +4,6 @@ mod misc;
 mod opt;
 mod persisted;
 mod runtime;
-mod scene;
 mod sequence;
 
 use std::{
@@ -63,11 +62,22 @@ impl AppState {
         )
     }
 
-    fn add_standalone_mesh(&mut self, path: PathBuf, mesh_scale: f32) -> anyhow::Result<()> {
+    fn add_standalone_mesh(
+        &mut self,
+        path: PathBuf,
+        mesh_scale: f32,
+        instanced: bool,
+    ) -> anyhow::Result<()> {
+        let source = if instanced {
+            MeshSource::InstancedFile(path)
+        } else {
+            MeshSource::File(path)
+        };
+
         self.runtime.add_mesh_instance(
             &mut self.persisted,
             &mut self.kajiya.app.world_renderer.as_mut().unwrap(),
-            MeshSource::File(path),
+            source,
             SceneElementTransform {
                 position: Vec3::ZERO,
                 rotation_euler_degrees: Vec3::ZERO,
@@ -112,7 +122,7 @@ fn main() -> anyhow::Result<()> {
     if let Some(scene) = opt.scene.as_ref() {
         state.load_scene(scene)?;
     } else if let Some(mesh) = opt.mesh.as_ref() {
-        state.add_standalone_mesh(mesh.clone(), opt.mesh_scale)?;
+        state.add_standalone_mesh(mesh.clone(), opt.mesh_scale, opt.instanced)?;
     }
 
     let state = state.run()?;
@@ -23,6 +23,14 @@ pub struct Opt {
     #[structopt(long, default_value = "1.0")]
     pub mesh_scale: f32,
 
+    /// With `--mesh`, keep the glTF node hierarchy, and instance repeated meshes.
+    #[structopt(long)]
+    pub instanced: bool,
+
+    /// Level of detail of the meshes seen by ray tracing; 0 is full resolution.
+    #[structopt(long, default_value = "0")]
+    pub blas_lod: usize,
+
     #[structopt(long)]
     pub no_vsync: bool,
 
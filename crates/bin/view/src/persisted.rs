@@ -1,6 +1,7 @@
 use std::path::PathBuf;
 
-use kajiya::world_renderer::InstanceHandle;
+use kajiya::world_renderer::{InstanceHandle, PunctualLightHandle};
+use kajiya_asset_pipe::{SceneCameraDesc, SceneLightDesc};
 
 use crate::{misc::smoothstep, sequence::Sequence};
 
@@ -8,6 +9,14 @@ use crate::{misc::smoothstep, sequence::Sequence};
 pub struct SunState {
     pub controller: SunController,
     pub size_multiplier: f32,
+
+    /// Scales the sun's default radiance; set by directional lights in loaded scenes.
+    #[serde(default = "default_sun_color_multiplier")]
+    pub color_multiplier: Vec3,
+}
+
+fn default_sun_color_multiplier() -> Vec3 {
+    Vec3::ONE
 }
 
 impl Default for SunState {
@@ -15,6 +24,7 @@ impl Default for SunState {
         Self {
             controller: SunController::default(),
             size_multiplier: 1.0,
+            color_multiplier: default_sun_color_multiplier(),
         }
     }
 }
@@ -285,15 +295,50 @@ impl SceneElementTransform {
 pub enum MeshSource {
     File(PathBuf),
     Cache(PathBuf),
+
+    /// A glTF file baked with its node hierarchy, one instance per mesh node.
+    InstancedFile(PathBuf),
+}
+
+/// A render instance of a `SceneElement`, placed at `local_transform` within it.
+#[derive(Clone, PartialEq)]
+pub struct SceneElementInstance {
+    pub handle: InstanceHandle,
+    pub local_transform: Affine3A,
+}
+
+/// Cameras and lights placed relative to a `SceneElement`, or to the world.
+#[derive(Clone, Default, serde::Serialize, serde::Deserialize, PartialEq)]
+pub struct SceneSetup {
+    #[serde(default)]
+    pub cameras: Vec<SceneCameraDesc>,
+    #[serde(default)]
+    pub lights: Vec<SceneLightDesc>,
+
+    /// One for each point and spot light in `lights`, in order.
+    #[serde(skip)]
+    pub light_handles: Vec<PunctualLightHandle>,
 }
 
 #[derive(Clone, serde::Serialize, serde::Deserialize, PartialEq)]
 pub struct SceneElement {
     #[serde(skip)]
-    pub instance: InstanceHandle,
+    pub instances: Vec<SceneElementInstance>,
+
+    /// Reloaded from the bake along with the meshes.
+    #[serde(skip)]
+    pub setup: SceneSetup,
 
     pub source: MeshSource,
     pub transform: SceneElementTransform,
+
+    /// Played on skinned instances; see `WorldRenderer::set_instance_animation`.
+    #[serde(default = "default_animation_clip")]
+    pub animation_clip: Option<usize>,
+}
+
+fn default_animation_clip() -> Option<usize> {
+    Some(0)
 }
 
 #[derive(Clone, Default, serde::Serialize, serde::Deserialize)]
@@ -302,11 +347,15 @@ pub struct SceneState {
 
     #[serde(default)]
     pub ibl: Option<PathBuf>,
+
+    /// Cameras and lights of the scene file itself, in world space.
+    #[serde(default)]
+    pub setup: SceneSetup,
 }
 
 impl ShouldResetPathTracer for SceneState {
     fn should_reset_path_tracer(&self, other: &Self) -> bool {
-        self.elements != other.elements
+        self.elements != other.elements || self.setup != other.setup
     }
 }
 
@@ -3,11 +3,14 @@
 use anyhow::Context;
 use dolly::prelude::*;
 use kajiya::{
+    asset::mesh::InvalidBakedAsset,
     camera::CameraLens,
     frame_desc::WorldFrameDesc,
-    math::{Quat, Vec3},
+    math::{Affine3A, Quat, Vec3},
     rg::GraphDebugHook,
-    world_renderer::{AddMeshOptions, MeshHandle, RenderMode, WorldRenderer},
+    world_renderer::{
+        AddMeshOptions, MeshHandle, PunctualLight, PunctualLightKind, RenderMode, WorldRenderer,
+    },
 };
 use kajiya_simple::{
     input::{KeyboardMap, KeyboardState, MouseState, PhysicalPosition},
@@ -17,19 +20,17 @@ use kajiya_simple::{
 use crate::{
     PersistedState,
     opt::Opt,
-    persisted::{MeshSource, SceneElement, SceneElementTransform, ShouldResetPathTracer as _},
-    scene::SceneDesc,
+    persisted::{
+        MeshSource, SceneElement, SceneElementInstance, SceneElementTransform, SceneSetup,
+        ShouldResetPathTracer as _, SunState,
+    },
     sequence::{CameraPlaybackSequence, MemOption, SequenceValue},
 };
 
 use crate::keymap::KeymapConfig;
+use kajiya_asset_pipe::{SceneCameraDesc, SceneDesc, SceneLightDesc, SceneLightKind};
 use log::{info, warn};
-use std::{
-    collections::{HashMap, hash_map::DefaultHasher},
-    fs::File,
-    hash::{Hash, Hasher},
-    path::PathBuf,
-};
+use std::{collections::HashMap, path::PathBuf};
 
 pub const MAX_FPS_LIMIT: u32 = 256;
 
@@ -54,7 +55,13 @@ pub struct RuntimeState {
     sequence_playback_state: SequencePlaybackState,
     pub sequence_playback_speed: f32,
 
+    /// Skinned meshes loop their first animation clip.
+    animation_time: f32,
+
     known_meshes: HashMap<PathBuf, MeshHandle>,
+
+    /// See `AddMeshOptions::blas_lod`
+    blas_lod: usize,
 }
 
 enum SequencePlaybackState {
@@ -113,16 +120,23 @@ impl RuntimeState {
             active_camera_key: None,
             sequence_playback_state: SequencePlaybackState::NotPlaying,
             sequence_playback_speed: 1.0,
+            animation_time: 0.0,
 
             known_meshes: Default::default(),
+            blas_lod: opt.blas_lod,
         };
 
         // Load meshes that the persisted scene was referring to
         persisted.scene.elements.retain_mut(|elem| {
             match res.load_mesh(world_renderer, &elem.source) {
-                Ok(mesh) => {
-                    elem.instance =
-                        world_renderer.add_instance(mesh, elem.transform.affine_transform());
+                Ok(meshes) => {
+                    elem.instances = add_element_instances(world_renderer, meshes, &elem.transform);
+                    elem.setup = load_mesh_setup(&elem.source);
+                    add_setup_lights(
+                        world_renderer,
+                        &mut elem.setup,
+                        elem.transform.affine_transform(),
+                    );
                     true
                 }
                 Err(err) => {
@@ -132,6 +146,12 @@ impl RuntimeState {
             }
         });
 
+        add_setup_lights(
+            world_renderer,
+            &mut persisted.scene.setup,
+            Affine3A::IDENTITY,
+        );
+
         // Load the IBL too
         if let Some(ibl) = persisted.scene.ibl.as_ref() {
             if world_renderer.ibl.load_image(ibl).is_err() {
@@ -147,9 +167,16 @@ impl RuntimeState {
         persisted: &mut PersistedState,
         world_renderer: &mut WorldRenderer,
     ) {
-        for elem in persisted.scene.elements.drain(..) {
-            world_renderer.remove_instance(elem.instance);
+        for mut elem in persisted.scene.elements.drain(..) {
+            for instance in elem.instances {
+                world_renderer.remove_instance(instance.handle);
+            }
+
+            remove_setup_lights(world_renderer, &mut elem.setup);
         }
+
+        remove_setup_lights(world_renderer, &mut persisted.scene.setup);
+        persisted.scene.setup = SceneSetup::default();
     }
 
     pub fn load_scene(
@@ -159,20 +186,42 @@ impl RuntimeState {
         scene_path: impl Into<PathBuf>,
     ) -> anyhow::Result<()> {
         let scene_path = scene_path.into();
-        let scene_desc: SceneDesc = ron::de::from_reader(
-            File::open(&scene_path)
-                .with_context(|| format!("Opening scene file {:?}", scene_path))?,
-        )?;
+        let scene_desc = SceneDesc::load(&scene_path)?;
 
         self.clear_scene(persisted, world_renderer);
 
+        // The scene decides how the sun looks, unless it has no directional lights.
+        persisted.light.sun.color_multiplier = Vec3::ONE;
+
+        persisted.scene.setup = SceneSetup {
+            cameras: scene_desc.cameras,
+            lights: scene_desc.lights,
+            light_handles: Vec::new(),
+        };
+        add_setup_lights(
+            world_renderer,
+            &mut persisted.scene.setup,
+            Affine3A::IDENTITY,
+        );
+        apply_setup_sun(
+            &mut persisted.light.sun,
+            &persisted.scene.setup,
+            Affine3A::IDENTITY,
+        );
+
         for instance in scene_desc.instances {
             let mesh_path = canonical_path_from_vfs(&instance.mesh)
                 .with_context(|| format!("Mesh path: {:?}", instance.mesh))
                 .expect("valid mesh path");
 
-            let mesh = self
-                .load_mesh(world_renderer, &MeshSource::File(mesh_path.clone()))
+            let source = if instance.bakes_instanced() {
+                MeshSource::InstancedFile(mesh_path)
+            } else {
+                MeshSource::File(mesh_path)
+            };
+
+            let meshes = self
+                .load_mesh(world_renderer, &source)
                 .with_context(|| format!("Mesh path: {:?}", instance.mesh))
                 .expect("valid mesh");
 
@@ -182,15 +231,31 @@ impl RuntimeState {
                 scale: instance.scale.into(),
             };
 
-            let render_instance = world_renderer.add_instance(mesh, transform.affine_transform());
+            let mut setup = load_mesh_setup(&source);
+            add_setup_lights(world_renderer, &mut setup, transform.affine_transform());
+            apply_setup_sun(
+                &mut persisted.light.sun,
+                &setup,
+                transform.affine_transform(),
+            );
 
             persisted.scene.elements.push(SceneElement {
-                source: MeshSource::File(mesh_path),
-                instance: render_instance,
+                instances: add_element_instances(world_renderer, meshes, &transform),
+                setup,
+                source,
                 transform,
+                animation_clip: Some(0),
             });
         }
 
+        let first_camera = scene_cameras(persisted)
+            .next()
+            .map(|(camera, transform)| (camera.clone(), transform));
+
+        if let Some((camera, transform)) = first_camera {
+            self.jump_to_scene_camera(persisted, &camera, transform);
+        }
+
         Ok(())
     }
 
@@ -340,6 +405,7 @@ impl RuntimeState {
             Vec3::lerp(self.sun_direction_interp, sun_direction, sun_interp_t).normalize();
 
         ctx.world_renderer.sun_size_multiplier = persisted.light.sun.size_multiplier;
+        ctx.world_renderer.sun_color_multiplier = persisted.light.sun.color_multiplier;
     }
 
     fn update_lights(&mut self, persisted: &mut PersistedState, ctx: &mut FrameContext) {
@@ -415,12 +481,31 @@ impl RuntimeState {
             0.0
         };
 
+        // Moving meshes would keep resetting the path tracer.
+        if ctx.world_renderer.render_mode != RenderMode::Reference {
+            self.animation_time += ctx.dt_filtered;
+        }
+
         for elem in persisted.scene.elements.iter() {
-            ctx.world_renderer
-                .get_instance_dynamic_parameters_mut(elem.instance)
-                .emissive_multiplier = persisted.light.emissive_multiplier * emissive_toggle_mult;
-            ctx.world_renderer
-                .set_instance_transform(elem.instance, elem.transform.affine_transform());
+            let element_transform = elem.transform.affine_transform();
+
+            for instance in &elem.instances {
+                ctx.world_renderer
+                    .get_instance_dynamic_parameters_mut(instance.handle)
+                    .emissive_multiplier =
+                    persisted.light.emissive_multiplier * emissive_toggle_mult;
+                ctx.world_renderer.set_instance_transform(
+                    instance.handle,
+                    element_transform * instance.local_transform,
+                );
+                ctx.world_renderer.set_instance_animation(
+                    instance.handle,
+                    elem.animation_clip,
+                    self.animation_time,
+                );
+            }
+
+            update_setup_lights(ctx.world_renderer, &elem.setup, element_transform);
         }
     }
 
@@ -596,6 +681,29 @@ impl RuntimeState {
         self.sequence_playback_state = SequencePlaybackState::NotPlaying;
     }
 
+    /// Moves the camera to a viewpoint authored in the scene, placed by `transform`.
+    pub fn jump_to_scene_camera(
+        &mut self,
+        persisted: &mut PersistedState,
+        camera: &SceneCameraDesc,
+        transform: Affine3A,
+    ) {
+        let (_, rotation, _) = transform.to_scale_rotation_translation();
+
+        self.camera.driver_mut::<Position>().position =
+            glam::Vec3::from(transform.transform_point3(Vec3::from(camera.position)));
+        self.camera
+            .driver_mut::<YawPitch>()
+            .set_rotation_quat(glam::Quat::from(rotation * camera.rotation_quat()));
+
+        self.camera.update(1e10);
+
+        persisted.camera.vertical_fov = camera.vertical_fov;
+
+        self.active_camera_key = None;
+        self.sequence_playback_state = SequencePlaybackState::NotPlaying;
+    }
+
     pub fn replace_camera_sequence_key(&mut self, persisted: &mut PersistedState, idx: usize) {
         persisted.sequence.each_key(|i, item| {
             if idx != i {
@@ -614,49 +722,106 @@ impl RuntimeState {
         self.active_camera_key = None;
     }
 
+    /// Loads (baking first if needed) the meshes of `source`, along with their transforms
+    /// relative to the scene element.
     pub(crate) fn load_mesh(
         &mut self,
         world_renderer: &mut WorldRenderer,
         source: &MeshSource,
-    ) -> anyhow::Result<MeshHandle> {
+    ) -> anyhow::Result<Vec<(MeshHandle, Affine3A)>> {
         log::info!("Loading a mesh from {:?}", source);
 
+        match self.load_mesh_impl(world_renderer, source, false) {
+            // A damaged or outdated bake; it can be rebuilt from the sources.
+            Err(err)
+                if !matches!(source, MeshSource::Cache(_))
+                    && err.downcast_ref::<InvalidBakedAsset>().is_some() =>
+            {
+                warn!("{:#}", err);
+                self.load_mesh_impl(world_renderer, source, true)
+            }
+            result => result,
+        }
+    }
+
+    fn load_mesh_impl(
+        &mut self,
+        world_renderer: &mut WorldRenderer,
+        source: &MeshSource,
+        force_bake: bool,
+    ) -> anyhow::Result<Vec<(MeshHandle, Affine3A)>> {
         let path = match source {
             MeshSource::File(path) => {
-                fn calculate_hash(t: &PathBuf) -> u64 {
-                    let mut s = DefaultHasher::new();
-                    t.hash(&mut s);
-                    s.finish()
-                }
+                let cached_mesh_name = kajiya_asset_pipe::cached_mesh_name(path);
+                let cached_mesh_path = PathBuf::from(format!("/cache/{}.mesh", cached_mesh_name));
 
-                let path_hash = match path.canonicalize() {
-                    Ok(canonical) => calculate_hash(&canonical),
-                    Err(_) => calculate_hash(path),
+                let process_params = kajiya_asset_pipe::MeshAssetProcessParams {
+                    path: path.clone(),
+                    output_name: cached_mesh_name,
+                    scale: 1.0,
+                    instanced: false,
                 };
 
-                let cached_mesh_name = format!("{:8.8x}", path_hash);
-                let cached_mesh_path = PathBuf::from(format!("/cache/{}.mesh", cached_mesh_name));
-
-                if !canonical_path_from_vfs(&cached_mesh_path).map_or(false, |path| path.exists()) {
-                    kajiya_asset_pipe::process_mesh_asset(
-                        kajiya_asset_pipe::MeshAssetProcessParams {
-                            path: path.clone(),
-                            output_name: cached_mesh_name,
-                            scale: 1.0,
-                        },
-                    )?;
+                // Re-bake if the mesh is missing, or if its sources changed since the last bake.
+                if force_bake
+                    || !canonical_path_from_vfs(&cached_mesh_path)
+                        .map_or(false, |path| path.exists())
+                    || kajiya_asset_pipe::mesh_asset_needs_processing(&process_params)
+                {
+                    kajiya_asset_pipe::process_mesh_asset(process_params)?;
                 }
 
                 cached_mesh_path
             }
             MeshSource::Cache(path) => path.clone(),
+            MeshSource::InstancedFile(path) => {
+                let cached_scene_name = kajiya_asset_pipe::cached_instanced_scene_name(path);
+
+                let process_params = kajiya_asset_pipe::MeshAssetProcessParams {
+                    path: path.clone(),
+                    output_name: cached_scene_name.clone(),
+                    scale: 1.0,
+                    instanced: true,
+                };
+
+                if force_bake || kajiya_asset_pipe::mesh_asset_needs_processing(&process_params) {
+                    kajiya_asset_pipe::process_mesh_asset(process_params)?;
+                }
+
+                let nodes = kajiya_asset_pipe::BakedSceneNodes::load_from(
+                    &canonical_path_from_vfs(format!("/cache/{}.nodes", cached_scene_name))?,
+                )?;
+
+                return nodes
+                    .mesh_instances()
+                    .into_iter()
+                    .map(|(mesh, transform)| {
+                        let mesh_path = PathBuf::from(format!("/cache/{}.mesh", mesh));
+                        Ok((self.load_baked_mesh(world_renderer, mesh_path)?, transform))
+                    })
+                    .collect();
+            }
         };
 
-        Ok(*self.known_meshes.entry(path.clone()).or_insert_with(|| {
-            world_renderer
-                .add_baked_mesh(path, AddMeshOptions::new())
-                .unwrap()
-        }))
+        Ok(vec![(
+            self.load_baked_mesh(world_renderer, path)?,
+            Affine3A::IDENTITY,
+        )])
+    }
+
+    fn load_baked_mesh(
+        &mut self,
+        world_renderer: &mut WorldRenderer,
+        path: PathBuf,
+    ) -> anyhow::Result<MeshHandle> {
+        if let Some(mesh) = self.known_meshes.get(&path) {
+            return Ok(*mesh);
+        }
+
+        let mesh = world_renderer
+            .add_baked_mesh(path.clone(), AddMeshOptions::new().blas_lod(self.blas_lod))?;
+        self.known_meshes.insert(path, mesh);
+        Ok(mesh)
     }
 
     pub(crate) fn add_mesh_instance(
@@ -666,13 +831,23 @@ impl RuntimeState {
         source: MeshSource,
         transform: SceneElementTransform,
     ) -> anyhow::Result<()> {
-        let mesh = self.load_mesh(world_renderer, &source)?;
-        let inst = world_renderer.add_instance(mesh, transform.affine_transform());
+        let source = with_required_instancing(source);
+        let meshes = self.load_mesh(world_renderer, &source)?;
+
+        let mut setup = load_mesh_setup(&source);
+        add_setup_lights(world_renderer, &mut setup, transform.affine_transform());
+        apply_setup_sun(
+            &mut persisted.light.sun,
+            &setup,
+            transform.affine_transform(),
+        );
 
         persisted.scene.elements.push(SceneElement {
+            instances: add_element_instances(world_renderer, meshes, &transform),
+            setup,
             source,
-            instance: inst,
             transform,
+            animation_clip: Some(0),
         });
 
         Ok(())
@@ -712,7 +887,7 @@ impl RuntimeState {
                                 log::error!("Failed to load scene: {:#}", err);
                             }
                         }
-                        "gltf" | "glb" => {
+                        "gltf" | "glb" | "obj" => {
                             // Mesh
                             if let Err(err) = self.add_mesh_instance(
                                 persisted,
@@ -737,3 +912,169 @@ pub enum LeftClickEditMode {
     MoveSun,
     //MoveLocalLights,
 }
+
+fn add_element_instances(
+    world_renderer: &mut WorldRenderer,
+    meshes: Vec<(MeshHandle, Affine3A)>,
+    transform: &SceneElementTransform,
+) -> Vec<SceneElementInstance> {
+    let element_transform = transform.affine_transform();
+
+    meshes
+        .into_iter()
+        .map(|(mesh, local_transform)| SceneElementInstance {
+            handle: world_renderer.add_instance(mesh, element_transform * local_transform),
+            local_transform,
+        })
+        .collect()
+}
+
+/// Cameras of the scene file, and then of each element, along with their placement.
+pub fn scene_cameras(
+    persisted: &PersistedState,
+) -> impl Iterator<Item = (&SceneCameraDesc, Affine3A)> {
+    let scene = persisted
+        .scene
+        .setup
+        .cameras
+        .iter()
+        .map(|camera| (camera, Affine3A::IDENTITY));
+
+    let elements = persisted.scene.elements.iter().flat_map(|elem| {
+        let transform = elem.transform.affine_transform();
+        elem.setup
+            .cameras
+            .iter()
+            .map(move |camera| (camera, transform))
+    });
+
+    scene.chain(elements)
+}
+
+/// Bakes files which use GPU instancing with their node hierarchy, since a flattened bake
+/// would place their instanced meshes only once.
+fn with_required_instancing(source: MeshSource) -> MeshSource {
+    match source {
+        MeshSource::File(path) if kajiya_asset_pipe::needs_instanced_bake(&path) => {
+            MeshSource::InstancedFile(path)
+        }
+        source => source,
+    }
+}
+
+/// Cameras and lights baked along with the meshes of `source`.
+fn load_mesh_setup(source: &MeshSource) -> SceneSetup {
+    let setup_path = match source {
+        MeshSource::File(path) => PathBuf::from(format!(
+            "/cache/{}.setup",
+            kajiya_asset_pipe::cached_mesh_name(path)
+        )),
+        MeshSource::Cache(path) => path.with_extension("setup"),
+        MeshSource::InstancedFile(path) => PathBuf::from(format!(
+            "/cache/{}.setup",
+            kajiya_asset_pipe::cached_instanced_scene_name(path)
+        )),
+    };
+
+    // Caches baked elsewhere may come without one.
+    let setup = canonical_path_from_vfs(&setup_path)
+        .ok()
+        .filter(|path| path.exists())
+        .map(|path| kajiya_asset_pipe::BakedSceneSetup::load_from(&path));
+
+    match setup {
+        Some(Ok(setup)) => SceneSetup {
+            cameras: setup.cameras,
+            lights: setup.lights,
+            light_handles: Vec::new(),
+        },
+        Some(Err(err)) => {
+            warn!("{:#}", err);
+            SceneSetup::default()
+        }
+        None => SceneSetup::default(),
+    }
+}
+
+/// The default sun is about this bright, in lux.
+const DEFAULT_SUN_ILLUMINANCE: f32 = 100_000.0;
+
+/// The renderer's radiometric units per lux; the default sun comes out at about 20.
+const RADIANCE_UNITS_PER_LUX: f32 = 20.0 / DEFAULT_SUN_ILLUMINANCE;
+
+fn punctual_light(desc: &SceneLightDesc, transform: Affine3A) -> Option<PunctualLight> {
+    let kind = match desc.kind {
+        SceneLightKind::Directional => return None,
+        SceneLightKind::Point => PunctualLightKind::Point,
+        SceneLightKind::Spot {
+            inner_cone_angle,
+            outer_cone_angle,
+        } => PunctualLightKind::Spot {
+            inner_cone_angle: inner_cone_angle.to_radians(),
+            outer_cone_angle: outer_cone_angle.to_radians(),
+        },
+    };
+
+    Some(PunctualLight {
+        kind,
+        position: transform.transform_point3(Vec3::from(desc.position)),
+        direction: transform
+            .transform_vector3(desc.direction())
+            .normalize_or_zero(),
+        // A candela is a lux at one meter.
+        intensity: Vec3::from(desc.color) * desc.intensity * RADIANCE_UNITS_PER_LUX,
+    })
+}
+
+fn add_setup_lights(
+    world_renderer: &mut WorldRenderer,
+    setup: &mut SceneSetup,
+    transform: Affine3A,
+) {
+    setup.light_handles = setup
+        .lights
+        .iter()
+        .filter_map(|light| punctual_light(light, transform))
+        .map(|light| world_renderer.add_punctual_light(light))
+        .collect();
+}
+
+fn update_setup_lights(
+    world_renderer: &mut WorldRenderer,
+    setup: &SceneSetup,
+    transform: Affine3A,
+) {
+    let lights = setup
+        .lights
+        .iter()
+        .filter_map(|light| punctual_light(light, transform));
+
+    for (handle, light) in setup.light_handles.iter().zip(lights) {
+        *world_renderer.get_punctual_light_mut(*handle) = light;
+    }
+}
+
+pub(crate) fn remove_setup_lights(world_renderer: &mut WorldRenderer, setup: &mut SceneSetup) {
+    for handle in setup.light_handles.drain(..) {
+        world_renderer.remove_punctual_light(handle);
+    }
+}
+
+/// Points and tints the sun like the first directional light of `setup`, if any.
+fn apply_setup_sun(sun: &mut SunState, setup: &SceneSetup, transform: Affine3A) {
+    let mut directional = setup
+        .lights
+        .iter()
+        .filter(|light| light.kind == SceneLightKind::Directional);
+
+    if let Some(light) = directional.next() {
+        sun.controller
+            .set_towards_sun(-transform.transform_vector3(light.direction()).normalize());
+        sun.color_multiplier =
+            Vec3::from(light.color) * (light.intensity / DEFAULT_SUN_ILLUMINANCE);
+    }
+
+    if directional.next().is_some() {
+        warn!("Only the first directional light drives the sun; ignoring the others");
+    }
+}
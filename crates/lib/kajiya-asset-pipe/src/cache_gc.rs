@@ -0,0 +1,369 @@
+use anyhow::Context as _;
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use crate::{BakedSceneNodes, MeshAssetManifest};
+
+/// Disk usage of a single baked mesh and the images it references.
+pub struct CachedMeshUsage {
+    pub source: PathBuf,
+
+    /// The `.mesh`, or the `.nodes` of an instanced bake; then `mesh_bytes` covers all its meshes.
+    pub mesh_path: PathBuf,
+    pub mesh_bytes: u64,
+
+    /// Includes images shared with other meshes.
+    pub image_bytes: u64,
+}
+
+#[derive(Default)]
+pub struct CacheGcReport {
+    pub meshes: Vec<CachedMeshUsage>,
+    pub deleted_files: Vec<PathBuf>,
+    pub deleted_bytes: u64,
+    pub remaining_bytes: u64,
+
+    /// Manifests which couldn't be loaded. Their meshes get baked again on next use,
+    /// so the images they referenced aren't kept, and the manifests themselves are overwritten.
+    pub unreadable_manifests: Vec<(PathBuf, anyhow::Error)>,
+}
+
+/// Temporary files of bakes which are older than this are assumed to be left over from a crash.
+/// Younger ones might still be written to by a bake running in parallel.
+const STALE_TEMP_FILE_AGE: Duration = Duration::from_secs(60 * 60);
+
+fn file_size(path: &Path) -> u64 {
+    std::fs::metadata(path).map_or(0, |meta| meta.len())
+}
+
+fn is_stale_temp_file(path: &Path) -> bool {
+    std::fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .is_some_and(|age| age > STALE_TEMP_FILE_AGE)
+}
+
+/// Deletes baked images which no baked mesh references, along with manifests, `.setup`
+/// and `.nodes` files whose mesh is gone, and temporary files left behind by crashed bakes.
+/// With `dry_run`, only reports what would be deleted.
+///
+/// Refuses to run if any `.mesh` in the cache has no manifest, since its images are unknown.
+/// The meshes of instanced bakes are covered by the manifest of their `.nodes`,
+/// or by the `.nodes` alone if the manifest can't be read.
+pub fn collect_cache_garbage(cache_dir: &Path, dry_run: bool) -> anyhow::Result<CacheGcReport> {
+    let mut meshes: Vec<PathBuf> = Vec::new();
+    let mut manifests: Vec<PathBuf> = Vec::new();
+    let mut setups: Vec<PathBuf> = Vec::new();
+    let mut nodes: Vec<PathBuf> = Vec::new();
+    let mut images: HashMap<u64, PathBuf> = HashMap::new();
+    let mut dead_files: Vec<PathBuf> = Vec::new();
+
+    for entry in std::fs::read_dir(cache_dir).with_context(|| format!("Reading {:?}", cache_dir))? {
+        let path = entry?.path();
+        let stem = path
+            .file_stem()
+            .map_or(String::new(), |stem| stem.to_string_lossy().into_owned());
+
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("mesh") => meshes.push(path),
+            Some("manifest") => manifests.push(path),
+            Some("setup") => setups.push(path),
+            Some("nodes") => nodes.push(path),
+            Some("image") => {
+                if let Ok(identity) = u64::from_str_radix(&stem, 16) {
+                    images.insert(identity, path);
+                }
+            }
+            Some("tmp") => {
+                if is_stale_temp_file(&path) {
+                    dead_files.push(path);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mesh_stems: HashSet<PathBuf> = meshes.iter().map(|p| p.with_extension("")).collect();
+    let manifest_stems: HashSet<PathBuf> = manifests.iter().map(|p| p.with_extension("")).collect();
+
+    // Any meshes left over from an instanced bake without a manifest have none either,
+    // so they're reported as untracked below.
+    let (nodes, dead_nodes): (Vec<PathBuf>, Vec<PathBuf>) = nodes
+        .into_iter()
+        .partition(|p| manifest_stems.contains(&p.with_extension("")));
+    dead_files.extend(dead_nodes);
+    let nodes_stems: HashSet<PathBuf> = nodes.iter().map(|p| p.with_extension("")).collect();
+
+    let mut report = CacheGcReport::default();
+    let mut live_manifests: Vec<(PathBuf, MeshAssetManifest)> = Vec::new();
+    let mut instanced_mesh_stems: HashSet<PathBuf> = HashSet::new();
+
+    for manifest_path in manifests {
+        let stem = manifest_path.with_extension("");
+
+        if !mesh_stems.contains(&stem) && !nodes_stems.contains(&stem) {
+            dead_files.push(manifest_path);
+            continue;
+        }
+
+        match MeshAssetManifest::load_from(&manifest_path) {
+            Ok(manifest) => {
+                instanced_mesh_stems.extend(
+                    manifest
+                        .instanced_meshes
+                        .iter()
+                        .map(|name| cache_dir.join(name)),
+                );
+                live_manifests.push((manifest_path, manifest));
+            }
+            Err(err) => {
+                if let Ok(nodes) = BakedSceneNodes::load_from(&stem.with_extension("nodes")) {
+                    instanced_mesh_stems
+                        .extend(nodes.meshes.iter().map(|name| cache_dir.join(name)));
+                }
+                report.unreadable_manifests.push((manifest_path, err));
+            }
+        }
+    }
+
+    for setup_path in setups {
+        let stem = setup_path.with_extension("");
+
+        if !mesh_stems.contains(&stem) && !nodes_stems.contains(&stem) {
+            dead_files.push(setup_path);
+        }
+    }
+
+    let untracked: Vec<&PathBuf> = meshes
+        .iter()
+        .filter(|p| {
+            let stem = p.with_extension("");
+            !manifest_stems.contains(&stem) && !instanced_mesh_stems.contains(&stem)
+        })
+        .collect();
+
+    if !untracked.is_empty() {
+        anyhow::bail!(
+            "Baked meshes without a manifest: {:#?}. Re-bake or delete them before collecting garbage",
+            untracked
+        );
+    }
+
+    let mut live_images: HashSet<u64> = HashSet::new();
+
+    for (manifest_path, manifest) in live_manifests {
+        live_images.extend(manifest.images.iter().copied());
+
+        let (mesh_path, mesh_bytes) = if manifest.instanced_meshes.is_empty() {
+            let mesh_path = manifest_path.with_extension("mesh");
+            let mesh_bytes = file_size(&mesh_path);
+            (mesh_path, mesh_bytes)
+        } else {
+            let nodes_path = manifest_path.with_extension("nodes");
+            let mesh_bytes = file_size(&nodes_path)
+                + manifest
+                    .instanced_meshes
+                    .iter()
+                    .map(|name| file_size(&cache_dir.join(format!("{}.mesh", name))))
+                    .sum::<u64>();
+            (nodes_path, mesh_bytes)
+        };
+
+        report.meshes.push(CachedMeshUsage {
+            source: manifest
+                .inputs
+                .sources
+                .first()
+                .map(|src| src.path.clone())
+                .unwrap_or_default(),
+            mesh_bytes,
+            image_bytes: manifest
+                .images
+                .iter()
+                .filter_map(|identity| images.get(identity))
+                .map(|path| file_size(path))
+                .sum(),
+            mesh_path,
+        });
+    }
+
+    for (identity, path) in images {
+        if live_images.contains(&identity) {
+            report.remaining_bytes += file_size(&path);
+        } else {
+            dead_files.push(path);
+        }
+    }
+
+    report.remaining_bytes += report
+        .meshes
+        .iter()
+        .map(|mesh| mesh.mesh_bytes)
+        .sum::<u64>();
+
+    for path in dead_files {
+        report.deleted_bytes += file_size(&path);
+
+        if !dry_run {
+            std::fs::remove_file(&path).with_context(|| format!("Deleting {:?}", path))?;
+        }
+
+        report.deleted_files.push(path);
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MeshAssetInputs, SourceFileHash};
+    use kajiya_asset::mesh::BAKED_ASSET_FORMAT_VERSION;
+
+    /// A flat bake of `source` named `name`, with a mesh of `mesh_len` bytes.
+    fn write_flat_bake(dir: &Path, name: &str, source: &str, mesh_len: usize, images: &[u64]) {
+        let manifest = MeshAssetManifest {
+            inputs: MeshAssetInputs {
+                format_version: BAKED_ASSET_FORMAT_VERSION,
+                scale: 1.0,
+                instanced: false,
+                sources: vec![SourceFileHash {
+                    path: PathBuf::from(source),
+                    content_hash: 0,
+                }],
+            },
+            images: images.to_vec(),
+            instanced_meshes: Vec::new(),
+        };
+
+        std::fs::write(
+            dir.join(format!("{}.manifest", name)),
+            ron::ser::to_string_pretty(&manifest, Default::default()).unwrap(),
+        )
+        .unwrap();
+        std::fs::write(dir.join(format!("{}.mesh", name)), vec![0u8; mesh_len]).unwrap();
+    }
+
+    fn write_image(dir: &Path, identity: u64, len: usize) -> PathBuf {
+        let path = dir.join(format!("{:8.8x}.image", identity));
+        std::fs::write(&path, vec![0u8; len]).unwrap();
+        path
+    }
+
+    #[test]
+    fn deletes_only_unreferenced_images() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path();
+        write_flat_bake(dir, "mesh", "assets/mesh.gltf", 4, &[1]);
+        let live_image = write_image(dir, 1, 8);
+        let dead_image = write_image(dir, 2, 16);
+
+        let report = collect_cache_garbage(dir, true).unwrap();
+        assert_eq!(report.deleted_files, [dead_image.clone()]);
+        assert_eq!(report.deleted_bytes, 16);
+        assert_eq!(report.remaining_bytes, 4 + 8);
+        assert!(dead_image.exists());
+
+        collect_cache_garbage(dir, false).unwrap();
+        assert!(!dead_image.exists());
+        assert!(live_image.exists());
+        assert!(dir.join("mesh.mesh").exists());
+        assert!(dir.join("mesh.manifest").exists());
+    }
+
+    #[test]
+    fn reports_disk_usage_per_source() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path();
+        write_flat_bake(dir, "a", "assets/a.gltf", 4, &[1, 2]);
+        write_flat_bake(dir, "b", "assets/b.obj", 32, &[2]);
+        write_image(dir, 1, 8);
+        write_image(dir, 2, 16);
+
+        let mut report = collect_cache_garbage(dir, true).unwrap();
+        report.meshes.sort_by(|a, b| a.source.cmp(&b.source));
+
+        let usage: Vec<(&Path, PathBuf, u64, u64)> = report
+            .meshes
+            .iter()
+            .map(|mesh| {
+                let mesh_name = mesh.mesh_path.strip_prefix(dir).unwrap().to_owned();
+                (
+                    mesh.source.as_path(),
+                    mesh_name,
+                    mesh.mesh_bytes,
+                    mesh.image_bytes,
+                )
+            })
+            .collect();
+
+        // Shared images count for every mesh which uses them, but only once in the total.
+        assert_eq!(
+            usage,
+            [
+                (
+                    Path::new("assets/a.gltf"),
+                    PathBuf::from("a.mesh"),
+                    4,
+                    8 + 16
+                ),
+                (Path::new("assets/b.obj"), PathBuf::from("b.mesh"), 32, 16),
+            ]
+        );
+        assert_eq!(report.remaining_bytes, 4 + 32 + 8 + 16);
+        assert!(report.deleted_files.is_empty());
+    }
+
+    #[test]
+    fn collects_orphaned_nodes_and_stale_temp_files() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path();
+        let nodes = dir.join("scene-instanced.nodes");
+        let stale_tmp = dir.join("old.mesh.1-0.tmp");
+        let fresh_tmp = dir.join("new.image.1-1.tmp");
+
+        for path in [&nodes, &stale_tmp, &fresh_tmp] {
+            std::fs::write(path, [0u8; 4]).unwrap();
+        }
+
+        std::fs::File::options()
+            .write(true)
+            .open(&stale_tmp)
+            .unwrap()
+            .set_modified(SystemTime::now() - STALE_TEMP_FILE_AGE * 2)
+            .unwrap();
+
+        let mut report = collect_cache_garbage(dir, true).unwrap();
+        report.deleted_files.sort();
+        assert_eq!(report.deleted_files, [stale_tmp.clone(), nodes.clone()]);
+        assert_eq!(report.deleted_bytes, 8);
+        assert!(stale_tmp.exists() && nodes.exists());
+
+        collect_cache_garbage(dir, false).unwrap();
+        assert!(!stale_tmp.exists());
+        assert!(!nodes.exists());
+        assert!(fresh_tmp.exists());
+    }
+
+    #[test]
+    fn unreadable_manifests_keep_no_images() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path();
+        let manifest = dir.join("mesh.manifest");
+        let image = dir.join("00000001.image");
+
+        std::fs::write(dir.join("mesh.mesh"), [0u8; 4]).unwrap();
+        std::fs::write(&manifest, "not a manifest").unwrap();
+        std::fs::write(&image, [0u8; 4]).unwrap();
+
+        let report = collect_cache_garbage(dir, true).unwrap();
+        assert_eq!(report.unreadable_manifests.len(), 1);
+        assert_eq!(report.unreadable_manifests[0].0, manifest);
+        assert_eq!(report.deleted_files, [image]);
+        assert!(report.meshes.is_empty());
+    }
+}
@@ -2,18 +2,184 @@ use async_channel::unbounded;
 use async_executor::Executor;
 use easy_parallel::Parallel;
 use glam::Quat;
-use kajiya_asset::mesh::{pack_triangle_mesh, GpuImage, LoadGltfScene, PackedTriMesh};
+use kajiya_asset::mesh::{
+    GpuImage, LoadGltfCamerasAndLights, LoadGltfScene, LoadGltfSceneInstanced, LoadObjScene,
+    PackedTriMesh, baked_asset_content_hash, pack_triangle_mesh,
+};
 use smol::future;
-use std::{collections::HashSet, fs::File, path::PathBuf};
+use std::{
+    collections::HashSet,
+    fs::File,
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
 
 use turbosloth::*;
 
-use anyhow::Result;
+use anyhow::{Context as _, Result};
+
+mod cache_gc;
+mod manifest;
+mod nodes;
+mod scene;
+mod setup;
+
+pub use cache_gc::*;
+pub use manifest::*;
+pub use nodes::*;
+pub use scene::*;
+pub use setup::*;
+
+static TEMP_FILE_COUNTER: AtomicUsize = AtomicUsize::new(0);
 
 pub struct MeshAssetProcessParams {
     pub path: PathBuf,
     pub output_name: String,
     pub scale: f32,
+
+    /// Bake every unique glTF mesh separately, along with the node hierarchy instantiating them,
+    /// instead of flattening the whole scene into one mesh. See `BakedSceneNodes`.
+    pub instanced: bool,
+}
+
+/// Whether `path` is a Wavefront OBJ file, loaded with `LoadObjScene`, rather than glTF.
+pub fn is_obj_path(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("obj"))
+}
+
+/// Whether a flattened bake of the scene at `path` would lose geometry, because it places
+/// meshes instanced via `EXT_mesh_gpu_instancing` only once.
+///
+/// Scenes which can't be read are reported as not needing it; their bake fails either way.
+pub fn needs_instanced_bake(path: &Path) -> bool {
+    !is_obj_path(path)
+        && LoadGltfScene {
+            path: path.to_owned(),
+            scale: 1.0,
+            rotation: Quat::IDENTITY,
+        }
+        .uses_gpu_instancing()
+        .unwrap_or(false)
+}
+
+/// Name of the baked `cache/{name}.mesh` for a source mesh, derived from its canonical path.
+///
+/// The hash function is fixed, so that the name doesn't change across toolchain updates.
+pub fn cached_mesh_name(path: &Path) -> String {
+    fn calculate_hash(t: &Path) -> u64 {
+        baked_asset_content_hash(t.as_os_str().as_encoded_bytes())
+    }
+
+    let path_hash = match path.canonicalize() {
+        Ok(canonical) => calculate_hash(&canonical),
+        Err(_) => calculate_hash(path),
+    };
+
+    format!("{:8.8x}", path_hash)
+}
+
+/// Name of the baked `cache/{name}.nodes` for a source scene baked with `instanced`.
+pub fn cached_instanced_scene_name(path: &Path) -> String {
+    format!("{}-instanced", cached_mesh_name(path))
+}
+
+/// A unique path next to `dst` to write it to first. Stale ones are deleted by `cache gc`.
+fn temp_file_path(dst: &Path) -> PathBuf {
+    let extension = dst
+        .extension()
+        .map_or(String::new(), |ext| ext.to_string_lossy().into_owned());
+
+    dst.with_extension(format!(
+        "{}.{}-{}.tmp",
+        extension,
+        std::process::id(),
+        TEMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ))
+}
+
+/// Writes `dst` via a temporary file, so that a bake which fails or gets interrupted midway
+/// doesn't leave a truncated file behind.
+pub(crate) fn write_file_via_temp(
+    dst: &Path,
+    write: impl FnOnce(&mut File) -> Result<()>,
+) -> Result<()> {
+    let tmp = temp_file_path(dst);
+
+    let res = File::create(&tmp)
+        .with_context(|| format!("Creating {:?}", tmp))
+        .and_then(|mut file| write(&mut file).with_context(|| format!("Writing {:?}", dst)))
+        .and_then(|()| {
+            std::fs::rename(&tmp, dst).with_context(|| format!("Moving {:?} to {:?}", tmp, dst))
+        });
+
+    if res.is_err() {
+        let _ = std::fs::remove_file(&tmp);
+    }
+
+    res
+}
+
+fn write_baked_mesh(mesh: &PackedTriMesh::Proto, name: &str) -> Result<()> {
+    write_file_via_temp(&PathBuf::from(format!("cache/{}.mesh", name)), |file| {
+        mesh.flatten_into(file);
+        Ok(())
+    })
+}
+
+/// Bakes each unique mesh of the scene into `cache/{output_name}-{index}.mesh`, and the nodes
+/// into `cache/{output_name}.nodes`. Returns the names of the meshes, and all their images.
+fn bake_instanced_meshes(
+    scene: LoadGltfScene,
+    output_name: &str,
+    lazy_cache: &Arc<LazyCache>,
+) -> Result<(Vec<String>, Vec<Lazy<GpuImage::Proto>>)> {
+    let scene = LoadGltfSceneInstanced { scene }.into_lazy();
+    let scene = &*smol::block_on(scene.eval(lazy_cache))?;
+
+    let mesh_names: Vec<String> = (0..scene.meshes.len())
+        .map(|idx| format!("{}-{}", output_name, idx))
+        .collect();
+
+    // Don't leave behind meshes of a previous bake which no longer exist in the scene.
+    if let Ok(previous) = BakedSceneNodes::load(output_name) {
+        for stale in previous
+            .meshes
+            .iter()
+            .filter(|name| !mesh_names.contains(name))
+        {
+            let _ = std::fs::remove_file(format!("cache/{}.mesh", stale));
+        }
+    }
+
+    println!("Packing {} meshes...", scene.meshes.len());
+
+    let mut maps = Vec::new();
+    for (mesh, name) in scene.meshes.iter().zip(&mesh_names) {
+        let mesh: PackedTriMesh::Proto = pack_triangle_mesh(mesh);
+        write_baked_mesh(&mesh, name)?;
+        maps.extend(mesh.maps);
+    }
+
+    BakedSceneNodes {
+        meshes: mesh_names.clone(),
+        nodes: scene
+            .nodes
+            .iter()
+            .map(|node| BakedSceneNode {
+                name: node.name.clone(),
+                parent: node.parent,
+                transform: node.transform.to_cols_array(),
+                mesh: node.mesh,
+            })
+            .collect(),
+    }
+    .save(output_name)?;
+
+    Ok((mesh_names, maps))
 }
 
 pub fn process_mesh_asset(opt: MeshAssetProcessParams) -> Result<()> {
@@ -22,27 +188,61 @@ pub fn process_mesh_asset(opt: MeshAssetProcessParams) -> Result<()> {
     std::fs::create_dir_all("cache")?;
 
     {
+        // Hash the sources before loading them, so that if they're modified during the bake,
+        // the manifest will be out of date, and the mesh will be baked again.
+        let inputs = MeshAssetInputs::current(&opt)?;
+
         println!("Loading {:?}...", opt.path);
 
-        let mesh = LoadGltfScene {
-            path: opt.path,
+        let is_obj = is_obj_path(&opt.path);
+        anyhow::ensure!(
+            !(is_obj && opt.instanced),
+            "OBJ files have no node hierarchy to bake instanced: {:?}",
+            opt.path
+        );
+
+        let scene = LoadGltfScene {
+            path: opt.path.clone(),
             scale: opt.scale,
             //rotation: Quat::from_rotation_y(std::f32::consts::FRAC_PI_2),
             rotation: Quat::IDENTITY,
+        };
+
+        if is_obj {
+            BakedSceneSetup::default().save(&opt.output_name)?;
+        } else {
+            let cameras_and_lights = LoadGltfCamerasAndLights {
+                scene: scene.clone(),
+            }
+            .into_lazy();
+            let cameras_and_lights = &*smol::block_on(cameras_and_lights.eval(&lazy_cache))?;
+            BakedSceneSetup::from_gltf(cameras_and_lights).save(&opt.output_name)?;
         }
-        .into_lazy();
 
-        let mesh = &*smol::block_on(mesh.eval(&lazy_cache))?;
+        let (instanced_meshes, maps) = if opt.instanced {
+            bake_instanced_meshes(scene, &opt.output_name, &lazy_cache)?
+        } else {
+            let mesh = if is_obj {
+                LoadObjScene {
+                    path: scene.path,
+                    scale: scene.scale,
+                    rotation: scene.rotation,
+                }
+                .into_lazy()
+            } else {
+                scene.into_lazy()
+            };
+            let mesh = &*smol::block_on(mesh.eval(&lazy_cache))?;
+
+            println!("Packing the mesh...");
+            let mesh: PackedTriMesh::Proto = pack_triangle_mesh(mesh);
 
-        println!("Packing the mesh...");
-        let mesh: PackedTriMesh::Proto = pack_triangle_mesh(mesh);
+            write_baked_mesh(&mesh, &opt.output_name)?;
 
-        mesh.flatten_into(&mut File::create(format!(
-            "cache/{}.mesh",
-            opt.output_name
-        ))?);
-        let unique_images: Vec<Lazy<GpuImage::Proto>> = mesh
-            .maps
+            (Vec::new(), mesh.maps)
+        };
+
+        let unique_images: Vec<Lazy<GpuImage::Proto>> = maps
             .into_iter()
             .collect::<HashSet<_>>()
             .into_iter()
@@ -57,16 +257,21 @@ pub fn process_mesh_asset(opt: MeshAssetProcessParams) -> Result<()> {
             let loaded = img.eval(lazy_cache).await?;
             let img_dst = PathBuf::from(format!("cache/{:8.8x}.image", img.identity()));
 
-            match File::create(&img_dst) {
-                Ok(mut file) => loaded.flatten_into(&mut file),
-                Err(err) => {
-                    if img_dst.exists() {
-                        log::info!("Could not create {:?}; ignoring", img_dst);
-                    } else {
-                        anyhow::anyhow!(err);
-                    }
+            // Meshes baked in parallel can share images, so write to a unique temporary file,
+            // and move it into place once complete.
+            let img_tmp = temp_file_path(&img_dst);
+
+            loaded.flatten_into(&mut File::create(&img_tmp)?);
+
+            if let Err(err) = std::fs::rename(&img_tmp, &img_dst) {
+                let _ = std::fs::remove_file(&img_tmp);
+
+                if img_dst.exists() {
+                    log::info!("Could not replace {:?}; ignoring", img_dst);
+                } else {
+                    return Err(err.into());
                 }
-            };
+            }
 
             anyhow::Result::<()>::Ok(())
         });
@@ -82,20 +287,113 @@ pub fn process_mesh_asset(opt: MeshAssetProcessParams) -> Result<()> {
             println!("Processing {} images...", image_count);
 
             // Now spawn threads for the executor and run it to completion
-            Parallel::new()
+            let (_, all_images) = Parallel::new()
                 .each(0..num_cpus::get(), |_| {
                     future::block_on(ex.run(shutdown.recv()))
                 })
                 .finish(|| {
                     future::block_on(async {
-                        all_images.await.expect("Failed to load mesh images");
+                        let all_images = all_images.await;
                         drop(signal);
+                        all_images
                     })
                 });
+
+            all_images.context("Failed to process mesh images")?;
         }
 
+        let mut image_identities: Vec<u64> =
+            unique_images.iter().map(|img| img.identity()).collect();
+        image_identities.sort_unstable();
+
+        MeshAssetManifest {
+            inputs,
+            images: image_identities,
+            instanced_meshes,
+        }
+        .save(&opt.output_name)?;
+
         println!("Done.");
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A single triangle, placed twice via `EXT_mesh_gpu_instancing`.
+    fn write_gpu_instanced_gltf(dir: &Path) -> PathBuf {
+        let positions: [[f32; 3]; 3] = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]];
+        let translations: [[f32; 3]; 2] = [[0.0, 0.0, 0.0], [10.0, 0.0, 0.0]];
+        let bytes: Vec<u8> = positions
+            .iter()
+            .chain(&translations)
+            .flatten()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        std::fs::write(dir.join("scene.bin"), &bytes).unwrap();
+
+        let path = dir.join("scene.gltf");
+        std::fs::write(
+            &path,
+            r#"{
+                "asset": { "version": "2.0" },
+                "extensionsUsed": ["EXT_mesh_gpu_instancing"],
+                "scene": 0,
+                "scenes": [{ "nodes": [0] }],
+                "nodes": [{
+                    "mesh": 0,
+                    "extensions": {
+                        "EXT_mesh_gpu_instancing": { "attributes": { "TRANSLATION": 1 } }
+                    }
+                }],
+                "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0 } }] }],
+                "buffers": [{ "uri": "scene.bin", "byteLength": 60 }],
+                "bufferViews": [
+                    { "buffer": 0, "byteLength": 36 },
+                    { "buffer": 0, "byteOffset": 36, "byteLength": 24 }
+                ],
+                "accessors": [
+                    {
+                        "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                        "min": [0.0, 0.0, 0.0], "max": [1.0, 1.0, 0.0]
+                    },
+                    { "bufferView": 1, "componentType": 5126, "count": 2, "type": "VEC3" }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        path
+    }
+
+    #[test]
+    fn gpu_instanced_scene_needs_instanced_bake() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path();
+
+        assert!(needs_instanced_bake(&write_gpu_instanced_gltf(dir)));
+        assert!(!needs_instanced_bake(&dir.join("scene.obj")));
+    }
+
+    #[test]
+    fn flattened_bake_places_gpu_instanced_node_once() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = write_gpu_instanced_gltf(temp_dir.path());
+
+        let mesh = smol::block_on(
+            LoadGltfScene {
+                path,
+                scale: 1.0,
+                rotation: Quat::IDENTITY,
+            }
+            .into_lazy()
+            .eval(&LazyCache::create()),
+        )
+        .unwrap();
+
+        assert_eq!(mesh.positions.len(), 3);
+    }
+}
@@ -0,0 +1,326 @@
+use anyhow::Context as _;
+use glam::Quat;
+use kajiya_asset::mesh::{
+    BAKED_ASSET_FORMAT_VERSION, BakedAssetHeader, LoadGltfScene, LoadObjScene,
+    baked_asset_content_hash,
+};
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    BakedSceneNodes, BakedSceneSetup, MeshAssetProcessParams, is_obj_path, write_file_via_temp,
+};
+
+#[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug)]
+pub struct SourceFileHash {
+    pub path: PathBuf,
+    pub content_hash: u64,
+}
+
+/// Everything a mesh bake depends on. If any of it changes, the mesh must be baked again.
+#[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug)]
+pub struct MeshAssetInputs {
+    pub format_version: u32,
+    pub scale: f32,
+
+    /// Whether the scene was baked as separate meshes plus a `.nodes` hierarchy.
+    #[serde(default)]
+    pub instanced: bool,
+
+    /// The first entry is the scene file itself.
+    pub sources: Vec<SourceFileHash>,
+}
+
+impl MeshAssetInputs {
+    /// Hashes the current contents of all the files `params` would be baked from.
+    pub fn current(params: &MeshAssetProcessParams) -> anyhow::Result<Self> {
+        let source_files = if is_obj_path(&params.path) {
+            LoadObjScene {
+                path: params.path.clone(),
+                scale: params.scale,
+                rotation: Quat::IDENTITY,
+            }
+            .source_files()?
+        } else {
+            LoadGltfScene {
+                path: params.path.clone(),
+                scale: params.scale,
+                rotation: Quat::IDENTITY,
+            }
+            .source_files()?
+        };
+
+        let sources = source_files
+            .into_iter()
+            .map(|path| {
+                let content_hash = hash_file_contents(&path)?;
+                Ok(SourceFileHash { path, content_hash })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        Ok(Self {
+            format_version: BAKED_ASSET_FORMAT_VERSION,
+            scale: params.scale,
+            instanced: params.instanced,
+            sources,
+        })
+    }
+}
+
+/// Sidecar written next to every baked mesh, recording what it was baked from,
+/// and which other files in the cache it needs.
+#[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug)]
+pub struct MeshAssetManifest {
+    pub inputs: MeshAssetInputs,
+
+    /// Identities of the `cache/{identity:8x}.image` files referenced by the mesh.
+    pub images: Vec<u64>,
+
+    /// For instanced bakes, names of the `cache/{name}.mesh` files listed in the `.nodes`.
+    #[serde(default)]
+    pub instanced_meshes: Vec<String>,
+}
+
+impl MeshAssetManifest {
+    pub fn path(output_name: &str) -> PathBuf {
+        PathBuf::from(format!("cache/{}.manifest", output_name))
+    }
+
+    pub fn load(output_name: &str) -> anyhow::Result<Self> {
+        Self::load_from(&Self::path(output_name))
+    }
+
+    pub fn load_from(path: &Path) -> anyhow::Result<Self> {
+        let file = File::open(path).with_context(|| format!("Opening {:?}", path))?;
+        ron::de::from_reader(file).with_context(|| format!("Parsing {:?}", path))
+    }
+
+    pub fn save(&self, output_name: &str) -> anyhow::Result<()> {
+        write_file_via_temp(&Self::path(output_name), |file| {
+            Ok(ron::ser::to_writer_pretty(file, self, Default::default())?)
+        })
+    }
+}
+
+/// Uses a fixed hash function, so that manifests stay valid across toolchain updates.
+fn hash_file_contents(path: &Path) -> anyhow::Result<u64> {
+    let contents =
+        std::fs::read(path).with_context(|| format!("Reading source file {:?}", path))?;
+
+    Ok(baked_asset_content_hash(&contents))
+}
+
+/// Only checks the header; the contents are checked in full when the asset gets loaded.
+fn baked_asset_is_current(path: &Path) -> bool {
+    match BakedAssetHeader::read_from_file(path) {
+        Ok(_) => true,
+        Err(err) => {
+            if path.exists() {
+                log::info!("{:#}", err);
+            }
+            false
+        }
+    }
+}
+
+fn baked_output_exists(params: &MeshAssetProcessParams) -> bool {
+    if !BakedSceneSetup::path(&params.output_name).exists() {
+        return false;
+    }
+
+    if params.instanced {
+        BakedSceneNodes::load(&params.output_name).is_ok_and(|nodes| {
+            nodes
+                .meshes
+                .iter()
+                .all(|mesh| baked_asset_is_current(&PathBuf::from(format!("cache/{}.mesh", mesh))))
+        })
+    } else {
+        baked_asset_is_current(&PathBuf::from(format!("cache/{}.mesh", params.output_name)))
+    }
+}
+
+/// Returns `true` if the mesh named `params.output_name` was never baked, if any of its baked
+/// files are missing or damaged, or if its sources, bake parameters, or the baked format
+/// changed since.
+pub fn mesh_asset_needs_processing(params: &MeshAssetProcessParams) -> bool {
+    if !baked_output_exists(params) {
+        return true;
+    }
+
+    let baked = match MeshAssetManifest::load(&params.output_name) {
+        Ok(baked) => baked,
+        Err(err) => {
+            log::info!("{:#}; the mesh will be baked again", err);
+            return true;
+        }
+    };
+
+    let images_intact = baked.images.iter().all(|identity| {
+        baked_asset_is_current(&PathBuf::from(format!("cache/{:8.8x}.image", identity)))
+    });
+    if !images_intact {
+        return true;
+    }
+
+    match MeshAssetInputs::current(params) {
+        Ok(current) => {
+            let up_to_date = baked.inputs == current;
+            if !up_to_date {
+                log::info!(
+                    "Baked mesh {:?} is out of date with {:?}",
+                    params.output_name,
+                    params.path
+                );
+            }
+
+            !up_to_date
+        }
+        Err(err) => {
+            log::warn!("{:#}; the mesh will be baked again", err);
+            true
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn process_params(path: PathBuf) -> MeshAssetProcessParams {
+        MeshAssetProcessParams {
+            path,
+            output_name: "test".to_owned(),
+            scale: 1.0,
+            instanced: false,
+        }
+    }
+
+    #[test]
+    fn sources_change_with_their_contents_and_parameters() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path();
+        let bin_path = dir.join("scene.bin");
+        std::fs::write(&bin_path, [0u8; 4]).unwrap();
+        std::fs::write(
+            dir.join("scene.gltf"),
+            r#"{
+                "asset": {"version": "2.0"},
+                "buffers": [{"uri": "scene.bin", "byteLength": 4}]
+            }"#,
+        )
+        .unwrap();
+
+        let params = process_params(dir.join("scene.gltf"));
+        let baked = MeshAssetInputs::current(&params).unwrap();
+        assert_eq!(baked.format_version, BAKED_ASSET_FORMAT_VERSION);
+        assert_eq!(baked.sources.len(), 2);
+        assert_eq!(baked.sources[0].path, params.path);
+        assert_eq!(MeshAssetInputs::current(&params).unwrap(), baked);
+
+        // External buffers are sources too.
+        std::fs::write(&bin_path, [1u8; 4]).unwrap();
+        let edited = MeshAssetInputs::current(&params).unwrap();
+        assert_ne!(edited, baked);
+        assert_eq!(edited.sources[0], baked.sources[0]);
+
+        let rescaled = MeshAssetInputs::current(&MeshAssetProcessParams {
+            scale: 2.0,
+            ..process_params(params.path.clone())
+        })
+        .unwrap();
+        assert_ne!(rescaled, edited);
+
+        let instanced = MeshAssetInputs::current(&MeshAssetProcessParams {
+            instanced: true,
+            ..process_params(params.path.clone())
+        })
+        .unwrap();
+        assert_ne!(instanced, edited);
+
+        let older_format = MeshAssetInputs {
+            format_version: BAKED_ASSET_FORMAT_VERSION - 1,
+            ..MeshAssetInputs::current(&params).unwrap()
+        };
+        assert_ne!(older_format, edited);
+
+        std::fs::remove_file(&params.path).unwrap();
+        assert!(MeshAssetInputs::current(&params).is_err());
+    }
+
+    #[test]
+    fn manifests_round_trip_and_accept_older_fields() {
+        let manifest = MeshAssetManifest {
+            inputs: MeshAssetInputs {
+                format_version: BAKED_ASSET_FORMAT_VERSION,
+                scale: 0.5,
+                instanced: true,
+                sources: vec![SourceFileHash {
+                    path: PathBuf::from("assets/scene.gltf"),
+                    content_hash: 0x1234,
+                }],
+            },
+            images: vec![1, 2],
+            instanced_meshes: vec!["scene-0".to_owned()],
+        };
+
+        let text = ron::ser::to_string_pretty(&manifest, Default::default()).unwrap();
+        let loaded: MeshAssetManifest = ron::de::from_str(&text).unwrap();
+        assert_eq!(loaded, manifest);
+
+        // Written before instanced bakes existed.
+        let loaded: MeshAssetManifest =
+            ron::de::from_str("(inputs: (format_version: 1, scale: 1.0, sources: []), images: [])")
+                .unwrap();
+        assert!(!loaded.inputs.instanced);
+        assert!(loaded.instanced_meshes.is_empty());
+    }
+
+    #[test]
+    fn material_libraries_are_sources() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path();
+        let mtl_path = dir.join("scene.mtl");
+        std::fs::write(&mtl_path, "newmtl a\nKd 1 0 0\n").unwrap();
+        std::fs::write(
+            dir.join("scene.obj"),
+            "mtllib scene.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl a\nf 1 2 3\n",
+        )
+        .unwrap();
+
+        let params = process_params(dir.join("scene.obj"));
+        let baked = MeshAssetInputs::current(&params).unwrap();
+        assert_eq!(baked.sources.len(), 2);
+        assert_eq!(baked.sources[1].path, mtl_path);
+
+        std::fs::write(&mtl_path, "newmtl a\nKd 0 1 0\n").unwrap();
+        let edited = MeshAssetInputs::current(&params).unwrap();
+        assert_ne!(edited, baked);
+        assert_eq!(edited.sources[0], baked.sources[0]);
+    }
+
+    #[test]
+    fn baked_asset_is_current_checks_the_header() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path();
+
+        let mut bytes = Vec::new();
+        kajiya_asset::mesh::GpuImage::Proto {
+            format: kajiya_backend::ash::vk::Format::R8G8B8A8_UNORM,
+            extent: [1, 1, 1],
+            mips: vec![vec![0; 4]],
+        }
+        .flatten_into(&mut bytes);
+
+        let path = dir.join("a.image");
+        std::fs::write(&path, &bytes).unwrap();
+        assert!(baked_asset_is_current(&path));
+
+        std::fs::write(&path, &bytes[..bytes.len() - 1]).unwrap();
+        assert!(!baked_asset_is_current(&path));
+
+        assert!(!baked_asset_is_current(&dir.join("missing.image")));
+    }
+}
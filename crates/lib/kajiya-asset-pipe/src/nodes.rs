@@ -0,0 +1,155 @@
+use anyhow::Context as _;
+use glam::{Affine3A, Mat4};
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+};
+
+use crate::write_file_via_temp;
+
+/// A node of an instanced bake; mirrors `kajiya_asset::mesh::GltfSceneNode`.
+#[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug)]
+pub struct BakedSceneNode {
+    pub name: Option<String>,
+
+    /// Index of the parent node. Parents precede their children.
+    pub parent: Option<usize>,
+
+    /// Column-major; relative to the parent.
+    pub transform: [f32; 16],
+
+    /// Index into `BakedSceneNodes::meshes`.
+    pub mesh: Option<usize>,
+}
+
+/// Written by instanced bakes instead of a single `.mesh`: the glTF node hierarchy,
+/// and the names of the `cache/{name}.mesh` files the nodes instantiate.
+#[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug)]
+pub struct BakedSceneNodes {
+    pub meshes: Vec<String>,
+    pub nodes: Vec<BakedSceneNode>,
+}
+
+impl BakedSceneNodes {
+    pub fn path(output_name: &str) -> PathBuf {
+        PathBuf::from(format!("cache/{}.nodes", output_name))
+    }
+
+    pub fn load(output_name: &str) -> anyhow::Result<Self> {
+        Self::load_from(&Self::path(output_name))
+    }
+
+    pub fn load_from(path: &Path) -> anyhow::Result<Self> {
+        let file = File::open(path).with_context(|| format!("Opening {:?}", path))?;
+        let nodes: Self =
+            ron::de::from_reader(file).with_context(|| format!("Parsing {:?}", path))?;
+        nodes
+            .validate()
+            .with_context(|| format!("Validating {:?}", path))?;
+        Ok(nodes)
+    }
+
+    /// Checks the indices `mesh_instances` relies on.
+    fn validate(&self) -> anyhow::Result<()> {
+        for (idx, node) in self.nodes.iter().enumerate() {
+            if let Some(parent) = node.parent {
+                anyhow::ensure!(
+                    parent < idx,
+                    "Node {} has parent {}, which doesn't precede it",
+                    idx,
+                    parent
+                );
+            }
+
+            if let Some(mesh) = node.mesh {
+                anyhow::ensure!(
+                    mesh < self.meshes.len(),
+                    "Node {} refers to mesh {}, but there are only {}",
+                    idx,
+                    mesh,
+                    self.meshes.len()
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn save(&self, output_name: &str) -> anyhow::Result<()> {
+        write_file_via_temp(&Self::path(output_name), |file| {
+            Ok(ron::ser::to_writer_pretty(file, self, Default::default())?)
+        })
+    }
+
+    /// Every node with a mesh, as the name of the baked mesh and its scene-space transform.
+    pub fn mesh_instances(&self) -> Vec<(&str, Affine3A)> {
+        let mut world_transforms: Vec<Mat4> = Vec::with_capacity(self.nodes.len());
+
+        for node in &self.nodes {
+            let local = Mat4::from_cols_array(&node.transform);
+            let world = match node.parent {
+                Some(parent) => world_transforms[parent] * local,
+                None => local,
+            };
+            world_transforms.push(world);
+        }
+
+        self.nodes
+            .iter()
+            .zip(world_transforms)
+            .filter_map(|(node, world)| {
+                let mesh = self.meshes.get(node.mesh?)?;
+                Some((mesh.as_str(), Affine3A::from_mat4(world)))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(parent: Option<usize>, mesh: Option<usize>) -> BakedSceneNode {
+        BakedSceneNode {
+            name: None,
+            parent,
+            transform: Mat4::IDENTITY.to_cols_array(),
+            mesh,
+        }
+    }
+
+    #[test]
+    fn validate_accepts_parents_preceding_children() {
+        let nodes = BakedSceneNodes {
+            meshes: vec!["a".to_owned()],
+            nodes: vec![
+                node(None, None),
+                node(Some(0), Some(0)),
+                node(Some(1), None),
+            ],
+        };
+        assert!(nodes.validate().is_ok());
+        assert_eq!(nodes.mesh_instances().len(), 1);
+    }
+
+    #[test]
+    fn validate_rejects_bad_indices() {
+        let self_parent = BakedSceneNodes {
+            meshes: Vec::new(),
+            nodes: vec![node(Some(0), None)],
+        };
+        assert!(self_parent.validate().is_err());
+
+        let later_parent = BakedSceneNodes {
+            meshes: Vec::new(),
+            nodes: vec![node(Some(1), None), node(None, None)],
+        };
+        assert!(later_parent.validate().is_err());
+
+        let missing_mesh = BakedSceneNodes {
+            meshes: vec!["a".to_owned()],
+            nodes: vec![node(None, Some(1))],
+        };
+        assert!(missing_mesh.validate().is_err());
+    }
+}
@@ -0,0 +1,137 @@
+use anyhow::Context as _;
+use easy_parallel::Parallel;
+use kajiya_backend::canonical_path_from_vfs;
+use std::{fs::File, panic::AssertUnwindSafe, path::Path, sync::Mutex};
+
+use crate::{
+    MeshAssetProcessParams, SceneCameraDesc, SceneLightDesc, cached_instanced_scene_name,
+    cached_mesh_name, mesh_asset_needs_processing, needs_instanced_bake, process_mesh_asset,
+};
+
+#[derive(serde::Deserialize)]
+pub struct SceneDesc {
+    pub instances: Vec<SceneInstanceDesc>,
+
+    /// In world space. The cameras and lights of the instanced meshes are added to these.
+    #[serde(default)]
+    pub cameras: Vec<SceneCameraDesc>,
+    #[serde(default)]
+    pub lights: Vec<SceneLightDesc>,
+}
+
+fn default_instance_scale() -> [f32; 3] {
+    [1.0, 1.0, 1.0]
+}
+
+#[derive(serde::Deserialize)]
+pub struct SceneInstanceDesc {
+    pub position: [f32; 3],
+    #[serde(default = "default_instance_scale")]
+    pub scale: [f32; 3],
+    #[serde(default)]
+    pub rotation: [f32; 3],
+    pub mesh: String,
+
+    /// Keep the glTF node hierarchy, and share the geometry of repeated meshes.
+    #[serde(default)]
+    pub instanced: bool,
+}
+
+impl SceneInstanceDesc {
+    /// Whether the mesh is baked with `instanced`: either because the scene asks for it,
+    /// or because the mesh uses GPU instancing, which a flattened bake would lose.
+    pub fn bakes_instanced(&self) -> bool {
+        self.instanced
+            || canonical_path_from_vfs(&self.mesh).is_ok_and(|path| needs_instanced_bake(&path))
+    }
+}
+
+impl SceneDesc {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let file = File::open(path).with_context(|| format!("Opening scene file {:?}", path))?;
+        ron::de::from_reader(file).with_context(|| format!("Parsing scene file {:?}", path))
+    }
+}
+
+pub enum MeshBakeOutcome {
+    Baked,
+    UpToDate,
+}
+
+fn bake_scene_mesh(mesh: &str, instanced: bool) -> anyhow::Result<MeshBakeOutcome> {
+    let path = canonical_path_from_vfs(mesh)?;
+
+    // Scene instances carry their own scale, so meshes are baked at unit scale,
+    // same as the viewer does.
+    let params = MeshAssetProcessParams {
+        output_name: if instanced {
+            cached_instanced_scene_name(&path)
+        } else {
+            cached_mesh_name(&path)
+        },
+        path,
+        scale: 1.0,
+        instanced,
+    };
+
+    if !mesh_asset_needs_processing(&params) {
+        return Ok(MeshBakeOutcome::UpToDate);
+    }
+
+    process_mesh_asset(params)?;
+    Ok(MeshBakeOutcome::Baked)
+}
+
+/// Bakes every mesh referred to by the scene in parallel, skipping ones which are up to date.
+/// Returns the outcome for each unique mesh path in the scene.
+///
+/// Each bake also processes its images on all cores, so only half as many meshes
+/// as there are cores are baked at a time.
+pub fn process_scene_mesh_assets(
+    scene_desc: &SceneDesc,
+) -> Vec<(String, anyhow::Result<MeshBakeOutcome>)> {
+    let mut meshes: Vec<(String, bool)> = scene_desc
+        .instances
+        .iter()
+        .map(|instance| (instance.mesh.clone(), instance.bakes_instanced()))
+        .collect();
+    meshes.sort();
+    meshes.dedup();
+
+    let worker_count = (num_cpus::get() / 2).clamp(1, meshes.len().max(1));
+    let pending = Mutex::new(meshes.into_iter());
+    let next_mesh = || pending.lock().unwrap().next();
+
+    let mut outcomes: Vec<(String, anyhow::Result<MeshBakeOutcome>)> = Parallel::new()
+        .each(0..worker_count, |_| {
+            let mut outcomes = Vec::new();
+
+            while let Some((mesh, instanced)) = next_mesh() {
+                // Report panics in the importers as errors of the asset which caused them.
+                let outcome = std::panic::catch_unwind(AssertUnwindSafe(|| {
+                    bake_scene_mesh(&mesh, instanced)
+                }))
+                .unwrap_or_else(|panic| {
+                    let message = panic
+                        .downcast_ref::<&str>()
+                        .map(|s| s.to_string())
+                        .or_else(|| panic.downcast_ref::<String>().cloned())
+                        .unwrap_or_else(|| "unknown panic".to_string());
+                    Err(anyhow::anyhow!("Panicked: {}", message))
+                })
+                .with_context(|| format!("Baking {:?}", mesh));
+
+                outcomes.push((mesh, outcome));
+            }
+
+            outcomes
+        })
+        .run()
+        .into_iter()
+        .flatten()
+        .collect();
+
+    // Workers finish in arbitrary order; report in a stable one.
+    outcomes.sort_by(|a, b| a.0.cmp(&b.0));
+    outcomes
+}
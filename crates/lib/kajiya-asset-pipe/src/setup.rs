@@ -0,0 +1,177 @@
+use anyhow::Context as _;
+use glam::{EulerRot, Mat4, Quat, Vec3};
+use kajiya_asset::mesh::{GltfCamerasAndLights, GltfLightKind};
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+};
+
+use crate::write_file_via_temp;
+
+/// Same convention as `SceneInstanceDesc::rotation`.
+fn quat_from_euler_degrees(rotation: [f32; 3]) -> Quat {
+    Quat::from_euler(
+        EulerRot::YXZ,
+        rotation[1].to_radians(),
+        rotation[0].to_radians(),
+        rotation[2].to_radians(),
+    )
+}
+
+fn euler_degrees_from_quat(rotation: Quat) -> [f32; 3] {
+    let (y, x, z) = rotation.to_euler(EulerRot::YXZ);
+    [x.to_degrees(), y.to_degrees(), z.to_degrees()]
+}
+
+fn default_vertical_fov() -> f32 {
+    62.0
+}
+
+/// A viewpoint authored in a scene. Looks down its local -Z.
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq, Debug)]
+pub struct SceneCameraDesc {
+    #[serde(default)]
+    pub name: Option<String>,
+    pub position: [f32; 3],
+
+    /// Euler angles in degrees, as in `SceneInstanceDesc`.
+    #[serde(default)]
+    pub rotation: [f32; 3],
+
+    /// In degrees
+    #[serde(default = "default_vertical_fov")]
+    pub vertical_fov: f32,
+}
+
+impl SceneCameraDesc {
+    pub fn rotation_quat(&self) -> Quat {
+        quat_from_euler_degrees(self.rotation)
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum SceneLightKind {
+    /// Infinitely far away, shining down its -Z.
+    Directional,
+    Point,
+    /// Shines down its -Z. Angles from the axis of the cone, in degrees.
+    Spot {
+        inner_cone_angle: f32,
+        outer_cone_angle: f32,
+    },
+}
+
+/// A light authored in a scene; the same kinds as `KHR_lights_punctual`.
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq, Debug)]
+pub struct SceneLightDesc {
+    #[serde(default)]
+    pub name: Option<String>,
+    pub kind: SceneLightKind,
+
+    #[serde(default)]
+    pub position: [f32; 3],
+
+    /// Euler angles in degrees, as in `SceneInstanceDesc`.
+    #[serde(default)]
+    pub rotation: [f32; 3],
+
+    /// Linear RGB
+    pub color: [f32; 3],
+
+    /// In lux for directional lights, and in candela for the others.
+    pub intensity: f32,
+}
+
+impl SceneLightDesc {
+    pub fn rotation_quat(&self) -> Quat {
+        quat_from_euler_degrees(self.rotation)
+    }
+
+    /// The direction the light shines in, for directional and spot lights.
+    pub fn direction(&self) -> Vec3 {
+        self.rotation_quat() * -Vec3::Z
+    }
+}
+
+/// Written by every bake next to its meshes: the cameras and lights of the scene,
+/// in the same space as the meshes.
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq, Debug, Default)]
+pub struct BakedSceneSetup {
+    pub cameras: Vec<SceneCameraDesc>,
+    pub lights: Vec<SceneLightDesc>,
+}
+
+impl BakedSceneSetup {
+    pub fn path(output_name: &str) -> PathBuf {
+        PathBuf::from(format!("cache/{}.setup", output_name))
+    }
+
+    pub fn load(output_name: &str) -> anyhow::Result<Self> {
+        Self::load_from(&Self::path(output_name))
+    }
+
+    pub fn load_from(path: &Path) -> anyhow::Result<Self> {
+        let file = File::open(path).with_context(|| format!("Opening {:?}", path))?;
+        ron::de::from_reader(file).with_context(|| format!("Parsing {:?}", path))
+    }
+
+    pub fn save(&self, output_name: &str) -> anyhow::Result<()> {
+        write_file_via_temp(&Self::path(output_name), |file| {
+            Ok(ron::ser::to_writer_pretty(file, self, Default::default())?)
+        })
+    }
+
+    pub fn from_gltf(gltf: &GltfCamerasAndLights) -> Self {
+        // Any scale in the node hierarchy is dropped; cameras and lights only get placed.
+        let decompose = |transform: &Mat4| {
+            let (_scale, rotation, translation) = transform.to_scale_rotation_translation();
+            (translation.to_array(), euler_degrees_from_quat(rotation))
+        };
+
+        let cameras = gltf
+            .cameras
+            .iter()
+            .map(|camera| {
+                let (position, rotation) = decompose(&camera.transform);
+
+                SceneCameraDesc {
+                    name: camera.name.clone(),
+                    position,
+                    rotation,
+                    vertical_fov: camera
+                        .vertical_fov
+                        .map_or_else(default_vertical_fov, f32::to_degrees),
+                }
+            })
+            .collect();
+
+        let lights = gltf
+            .lights
+            .iter()
+            .map(|light| {
+                let (position, rotation) = decompose(&light.transform);
+
+                SceneLightDesc {
+                    name: light.name.clone(),
+                    kind: match light.kind {
+                        GltfLightKind::Directional => SceneLightKind::Directional,
+                        GltfLightKind::Point => SceneLightKind::Point,
+                        GltfLightKind::Spot {
+                            inner_cone_angle,
+                            outer_cone_angle,
+                        } => SceneLightKind::Spot {
+                            inner_cone_angle: inner_cone_angle.to_degrees(),
+                            outer_cone_angle: outer_cone_angle.to_degrees(),
+                        },
+                    },
+                    position,
+                    rotation,
+                    color: light.color,
+                    intensity: light.intensity,
+                }
+            })
+            .collect();
+
+        Self { cameras, lights }
+    }
+}
@@ -6,7 +6,7 @@ use intel_tex_2::{bc5, bc7};
 use kajiya_backend::{ImageDesc, ash::vk, file::LoadFile};
 use turbosloth::*;
 
-use crate::mesh::TexCompressionMode;
+use crate::mesh::{SpecGlossFactors, SpecGlossOutput, TexCompressionMode};
 
 #[derive(Clone, Hash, PartialEq, Eq)]
 pub enum ImageSource {
@@ -121,6 +121,186 @@ impl LazyWorker for CreatePlaceholderImage {
     }
 }
 
+/// Derives a metallic-roughness map from the textures of a specular-glossiness material,
+/// following the reference conversion from the `KHR_materials_pbrSpecularGlossiness` repository.
+#[derive(Clone, Hash)]
+pub struct ConvertSpecGlossImage {
+    pub diffuse: Option<Lazy<RawImage>>,
+    pub specular_glossiness: Option<Lazy<RawImage>>,
+    pub factors: SpecGlossFactors,
+    pub output: SpecGlossOutput,
+}
+
+impl ConvertSpecGlossImage {
+    const DIELECTRIC_SPECULAR: f32 = 0.04;
+
+    fn srgb_to_linear(v: f32) -> f32 {
+        if v <= 0.04045 {
+            v / 12.92
+        } else {
+            ((v + 0.055) / 1.055).powf(2.4)
+        }
+    }
+
+    fn linear_to_srgb(v: f32) -> f32 {
+        if v <= 0.0031308 {
+            v * 12.92
+        } else {
+            1.055 * v.powf(1.0 / 2.4) - 0.055
+        }
+    }
+
+    fn perceived_brightness(c: [f32; 3]) -> f32 {
+        (0.299 * c[0] * c[0] + 0.587 * c[1] * c[1] + 0.114 * c[2] * c[2]).sqrt()
+    }
+
+    fn solve_metalness(diffuse: f32, specular: f32, one_minus_specular_strength: f32) -> f32 {
+        if specular < Self::DIELECTRIC_SPECULAR {
+            return 0.0;
+        }
+
+        let a = Self::DIELECTRIC_SPECULAR;
+        let b = diffuse * one_minus_specular_strength / (1.0 - Self::DIELECTRIC_SPECULAR)
+            + specular
+            - 2.0 * Self::DIELECTRIC_SPECULAR;
+        let c = Self::DIELECTRIC_SPECULAR - specular;
+        let d = (b * b - 4.0 * a * c).max(0.0);
+
+        ((-b + d.sqrt()) / (2.0 * a)).clamp(0.0, 1.0)
+    }
+
+    /// Converts one texel, with all inputs in linear space. Returns the output texel as stored
+    /// in the image, so base color is sRGB-encoded.
+    fn convert_texel(&self, diffuse: [f32; 4], specular: [f32; 3], glossiness: f32) -> [u8; 4] {
+        let one_minus_specular_strength = 1.0 - specular[0].max(specular[1]).max(specular[2]);
+        let metalness = Self::solve_metalness(
+            Self::perceived_brightness([diffuse[0], diffuse[1], diffuse[2]]),
+            Self::perceived_brightness(specular),
+            one_minus_specular_strength,
+        );
+
+        let to_u8 = |v: f32| (v.clamp(0.0, 1.0) * 255.0 + 0.5) as u8;
+
+        match self.output {
+            SpecGlossOutput::BaseColor => {
+                let from_diffuse_scale = one_minus_specular_strength
+                    / (1.0 - Self::DIELECTRIC_SPECULAR)
+                    / (1.0 - metalness).max(1e-4);
+                let t = metalness * metalness;
+
+                let base_color = |i: usize| {
+                    let from_diffuse = diffuse[i] * from_diffuse_scale;
+                    let from_specular = (specular[i]
+                        - Self::DIELECTRIC_SPECULAR * (1.0 - metalness))
+                        / metalness.max(1e-4);
+                    Self::linear_to_srgb(
+                        (from_diffuse + (from_specular - from_diffuse) * t).clamp(0.0, 1.0),
+                    )
+                };
+
+                [
+                    to_u8(base_color(0)),
+                    to_u8(base_color(1)),
+                    to_u8(base_color(2)),
+                    to_u8(diffuse[3]),
+                ]
+            }
+            SpecGlossOutput::RoughnessMetalness => {
+                [to_u8(1.0 - glossiness), to_u8(metalness), 0, 255]
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl LazyWorker for ConvertSpecGlossImage {
+    type Output = anyhow::Result<RawImage>;
+
+    async fn run(self, ctx: RunContext) -> Self::Output {
+        async fn eval_rgba8(
+            image: &Option<Lazy<RawImage>>,
+            ctx: &RunContext,
+        ) -> anyhow::Result<Option<ImageBuffer<Rgba<u8>, Vec<u8>>>> {
+            let Some(image) = image else {
+                return Ok(None);
+            };
+
+            match &*image.eval(ctx).await? {
+                RawImage::Rgba8(src) => Ok(Some(
+                    ImageBuffer::from_raw(src.dimensions[0], src.dimensions[1], src.data.to_vec())
+                        .unwrap(),
+                )),
+                RawImage::Dds(_) => {
+                    anyhow::bail!("DDS images can't be converted from specular-glossiness")
+                }
+            }
+        }
+
+        let diffuse = eval_rgba8(&self.diffuse, &ctx).await?;
+        let specular_glossiness = eval_rgba8(&self.specular_glossiness, &ctx).await?;
+
+        // Work at the larger of the two resolutions
+        let dimensions = [&diffuse, &specular_glossiness]
+            .into_iter()
+            .flatten()
+            .map(|img| img.dimensions())
+            .fold((1, 1), |a, b| (a.0.max(b.0), a.1.max(b.1)));
+
+        let fit = |img: Option<ImageBuffer<Rgba<u8>, Vec<u8>>>| {
+            img.map(|img| {
+                if img.dimensions() != dimensions {
+                    image::imageops::resize(&img, dimensions.0, dimensions.1, FilterType::Triangle)
+                } else {
+                    img
+                }
+            })
+        };
+
+        let diffuse = fit(diffuse);
+        let specular_glossiness = fit(specular_glossiness);
+
+        let factors = self.factors;
+        let mut data = Vec::with_capacity((dimensions.0 * dimensions.1 * 4) as usize);
+
+        for y in 0..dimensions.1 {
+            for x in 0..dimensions.0 {
+                let diffuse_px = diffuse
+                    .as_ref()
+                    .map_or([255; 4], |img| img.get_pixel(x, y).0);
+                let spec_gloss_px = specular_glossiness
+                    .as_ref()
+                    .map_or([255; 4], |img| img.get_pixel(x, y).0);
+
+                let diffuse = [
+                    Self::srgb_to_linear(diffuse_px[0] as f32 / 255.0) * factors.diffuse[0],
+                    Self::srgb_to_linear(diffuse_px[1] as f32 / 255.0) * factors.diffuse[1],
+                    Self::srgb_to_linear(diffuse_px[2] as f32 / 255.0) * factors.diffuse[2],
+                    diffuse_px[3] as f32 / 255.0 * factors.diffuse[3],
+                ];
+                let specular = [
+                    Self::srgb_to_linear(spec_gloss_px[0] as f32 / 255.0) * factors.specular[0],
+                    Self::srgb_to_linear(spec_gloss_px[1] as f32 / 255.0) * factors.specular[1],
+                    Self::srgb_to_linear(spec_gloss_px[2] as f32 / 255.0) * factors.specular[2],
+                ];
+                let glossiness = spec_gloss_px[3] as f32 / 255.0 * factors.glossiness;
+
+                data.extend_from_slice(&self.convert_texel(diffuse, specular, glossiness));
+            }
+        }
+
+        log::info!(
+            "Converted specular-glossiness to {:?}: {:?}",
+            self.output,
+            dimensions
+        );
+
+        Ok(RawImage::Rgba8(RawRgba8Image {
+            data: data.into(),
+            dimensions: [dimensions.0, dimensions.1],
+        }))
+    }
+}
+
 #[derive(Clone, Hash)]
 pub struct CreateGpuImage {
     pub image: Lazy<RawImage>,
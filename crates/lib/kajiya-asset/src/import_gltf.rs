@@ -3,7 +3,10 @@
 use base64::{DecodeSliceError::DecodeError, Engine};
 use bytes::Bytes;
 use gltf::{Document, Error, Gltf, Result, buffer, image};
-use std::{fs, io, path::Path};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
 
 use crate::image::ImageSource;
 
@@ -171,6 +174,45 @@ fn import_path(path: &Path) -> Result<Import> {
     import_impl(Gltf::from_reader_without_validation(reader)?, Some(base))
 }
 
+/// Parses just the glTF document, without loading any of its buffers or images.
+pub fn document(path: &Path) -> Result<Document> {
+    let file = fs::File::open(path).map_err(Error::Io)?;
+    let Gltf { document, .. } = Gltf::from_reader_without_validation(io::BufReader::new(file))?;
+    Ok(document)
+}
+
+/// Paths of the files which a glTF document is loaded from: the document itself,
+/// and any external buffers and images it references. Embedded data is skipped.
+pub fn file_dependencies(path: &Path) -> Result<Vec<PathBuf>> {
+    let base = path.parent().unwrap_or_else(|| Path::new("./"));
+    let document = document(path)?;
+
+    let buffer_uris = document
+        .buffers()
+        .filter_map(|buffer| match buffer.source() {
+            buffer::Source::Uri(uri) => Some(uri),
+            buffer::Source::Bin => None,
+        });
+
+    let image_uris = document.images().filter_map(|image| match image.source() {
+        image::Source::Uri { uri, .. } => Some(uri),
+        image::Source::View { .. } => None,
+    });
+
+    let mut paths = vec![path.to_owned()];
+    for uri in buffer_uris.chain(image_uris) {
+        let uri = urlencoding::decode(uri).map_err(|_| Error::UnsupportedScheme)?;
+
+        match Scheme::parse(&uri) {
+            Scheme::File(file_path) => paths.push(PathBuf::from(file_path)),
+            Scheme::Relative => paths.push(base.join(uri.as_ref())),
+            Scheme::Data(..) | Scheme::Unsupported => (),
+        }
+    }
+
+    Ok(paths)
+}
+
 /// Import some glTF 2.0 from the file system.
 pub fn import<P>(path: P) -> Result<Import>
 where
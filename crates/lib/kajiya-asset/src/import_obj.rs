@@ -0,0 +1,543 @@
+// A reader for the subset of Wavefront OBJ and MTL found in the wild: polygonal geometry,
+// and the classic plus the PBR extension material statements. Everything else is skipped.
+
+use anyhow::{Context as _, Result};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// A vertex of a face, as 0-based indices into the attribute arrays of `ObjData`.
+#[derive(Clone, Copy, Hash, PartialEq, Eq)]
+pub struct ObjCorner {
+    pub position: u32,
+    pub uv: Option<u32>,
+    pub normal: Option<u32>,
+}
+
+pub struct ObjFace {
+    /// Range of the face's corners within `ObjData::corners`.
+    pub first_corner: u32,
+    pub corner_count: u32,
+    /// Index into `ObjData::materials`, if the face uses a known material.
+    pub material: Option<u32>,
+}
+
+#[derive(Default)]
+pub struct ObjData {
+    pub positions: Vec<[f32; 3]>,
+    /// Per position if the file has `v x y z r g b` vertex colors; empty otherwise.
+    pub colors: Vec<[f32; 3]>,
+    pub uvs: Vec<[f32; 2]>,
+    pub normals: Vec<[f32; 3]>,
+    pub corners: Vec<ObjCorner>,
+    pub faces: Vec<ObjFace>,
+    /// From all the material libraries the file refers to.
+    pub materials: Vec<MtlMaterial>,
+}
+
+/// A `map_*` statement. Only the options which the renderer can follow are kept.
+#[derive(Clone)]
+pub struct MtlMap {
+    pub path: PathBuf,
+    /// `-s` and `-o`, in the OBJ texture space (V up).
+    pub scale: [f32; 2],
+    pub offset: [f32; 2],
+    /// `-clamp on`
+    pub clamp: bool,
+}
+
+#[derive(Clone, Default)]
+pub struct MtlMaterial {
+    pub name: String,
+
+    /// `Kd`, `Ks`, `Ke`
+    pub diffuse: Option<[f32; 3]>,
+    pub specular: Option<[f32; 3]>,
+    pub emissive: Option<[f32; 3]>,
+    /// `Ns`: the Phong exponent
+    pub shininess: Option<f32>,
+    /// `d`, or one minus `Tr`
+    pub dissolve: Option<f32>,
+    /// `Ni`
+    pub ior: Option<f32>,
+    /// `Pr` and `Pm` from the PBR extension
+    pub roughness: Option<f32>,
+    pub metalness: Option<f32>,
+
+    pub diffuse_map: Option<MtlMap>,
+    pub specular_map: Option<MtlMap>,
+    pub shininess_map: Option<MtlMap>,
+    pub emissive_map: Option<MtlMap>,
+    pub dissolve_map: Option<MtlMap>,
+    /// `norm`: a tangent-space normal map
+    pub normal_map: Option<MtlMap>,
+    /// `bump` or `map_Bump`: a height map
+    pub bump_map: Option<MtlMap>,
+    pub roughness_map: Option<MtlMap>,
+    pub metalness_map: Option<MtlMap>,
+}
+
+impl MtlMaterial {
+    pub fn maps(&self) -> impl Iterator<Item = &MtlMap> {
+        [
+            &self.diffuse_map,
+            &self.specular_map,
+            &self.shininess_map,
+            &self.emissive_map,
+            &self.dissolve_map,
+            &self.normal_map,
+            &self.bump_map,
+            &self.roughness_map,
+            &self.metalness_map,
+        ]
+        .into_iter()
+        .flatten()
+    }
+}
+
+/// Statement keyword and the rest of the line, with comments and surrounding whitespace removed.
+fn statements(text: &str) -> impl Iterator<Item = (usize, &str, &str)> {
+    text.lines().enumerate().filter_map(|(line_idx, line)| {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            return None;
+        }
+
+        let (keyword, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        Some((line_idx + 1, keyword, rest.trim()))
+    })
+}
+
+fn parse_floats<const N: usize>(args: &str) -> Result<[f32; N]> {
+    let mut res = [0.0; N];
+    let mut values = args.split_whitespace();
+
+    for v in &mut res {
+        let value = values.next().context("Too few values")?;
+        *v = value
+            .parse()
+            .with_context(|| format!("Invalid number {:?}", value))?;
+    }
+
+    Ok(res)
+}
+
+/// Paths in OBJ and MTL files are relative to the file, and often use backslashes.
+fn resolve_path(base: &Path, name: &str) -> PathBuf {
+    base.join(name.replace('\\', "/"))
+}
+
+/// Paths of the libraries named by a `mtllib` statement. Several are separated by whitespace,
+/// but some exporters write a single name with spaces in it, so that is tried first.
+fn mtl_library_paths(base: &Path, args: &str) -> Vec<PathBuf> {
+    let whole = resolve_path(base, args);
+    if whole.exists() {
+        return vec![whole];
+    }
+
+    args.split_whitespace()
+        .map(|name| resolve_path(base, name))
+        .collect()
+}
+
+/// Resolves a 1-based, or negative (relative to the end) OBJ index.
+fn resolve_index(index: &str, count: usize) -> Result<u32> {
+    let index: i64 = index
+        .parse()
+        .with_context(|| format!("Invalid index {:?}", index))?;
+
+    let resolved = if index < 0 {
+        count as i64 + index
+    } else {
+        index - 1
+    };
+
+    anyhow::ensure!(
+        (0..count as i64).contains(&resolved),
+        "Index {} out of range",
+        index
+    );
+
+    Ok(resolved as u32)
+}
+
+impl ObjData {
+    fn parse_corner(&self, corner: &str) -> Result<ObjCorner> {
+        let mut parts = corner.split('/');
+        let optional = |part: Option<&str>, count: usize| {
+            part.filter(|part| !part.is_empty())
+                .map(|part| resolve_index(part, count))
+                .transpose()
+        };
+
+        Ok(ObjCorner {
+            position: resolve_index(parts.next().unwrap_or_default(), self.positions.len())?,
+            uv: optional(parts.next(), self.uvs.len())?,
+            normal: optional(parts.next(), self.normals.len())?,
+        })
+    }
+}
+
+/// Parses the OBJ file at `path`, and the material libraries it refers to.
+/// Missing material libraries are skipped with a warning, and their materials are left default.
+pub fn load(path: &Path) -> Result<ObjData> {
+    let base = path.parent().unwrap_or_else(|| Path::new("./"));
+    let text = fs::read_to_string(path).with_context(|| format!("Reading {:?}", path))?;
+
+    let mut res = ObjData::default();
+    let mut material_indices: HashMap<String, u32> = HashMap::new();
+    let mut current_material = None;
+    let mut unknown_materials = Vec::new();
+
+    for (line, keyword, args) in statements(&text) {
+        (|| -> Result<()> {
+            match keyword {
+                "v" => {
+                    res.positions.push(parse_floats(args)?);
+
+                    // Vertex colors are an extension, so only keep them if every vertex has them.
+                    if args.split_whitespace().count() >= 6
+                        && res.colors.len() + 1 == res.positions.len()
+                    {
+                        let [_, _, _, r, g, b] = parse_floats(args)?;
+                        res.colors.push([r, g, b]);
+                    }
+                }
+                "vt" => {
+                    // The W coordinate is optional, and so is V, in theory.
+                    let mut values = args.split_whitespace();
+                    let u = parse_floats::<1>(values.next().unwrap_or_default())?[0];
+                    let v = values.next().map_or(Ok([0.0]), parse_floats::<1>)?[0];
+                    res.uvs.push([u, v]);
+                }
+                "vn" => res.normals.push(parse_floats(args)?),
+                "f" => {
+                    let first_corner = res.corners.len() as u32;
+                    for corner in args.split_whitespace() {
+                        let corner = res
+                            .parse_corner(corner)
+                            .with_context(|| format!("Face vertex {:?}", corner))?;
+                        res.corners.push(corner);
+                    }
+
+                    let corner_count = res.corners.len() as u32 - first_corner;
+                    if corner_count < 3 {
+                        res.corners.truncate(first_corner as usize);
+                        log::warn!(
+                            "{:?}:{}: skipping a face with fewer than 3 vertices",
+                            path,
+                            line
+                        );
+                    } else {
+                        res.faces.push(ObjFace {
+                            first_corner,
+                            corner_count,
+                            material: current_material,
+                        });
+                    }
+                }
+                "mtllib" => {
+                    for mtl_path in mtl_library_paths(base, args) {
+                        if mtl_path.exists() {
+                            for material in load_mtl(&mtl_path)? {
+                                material_indices
+                                    .insert(material.name.clone(), res.materials.len() as u32);
+                                res.materials.push(material);
+                            }
+                        } else {
+                            log::warn!(
+                                "{:?}:{}: material library {:?} not found",
+                                path,
+                                line,
+                                mtl_path
+                            );
+                        }
+                    }
+                }
+                "usemtl" => {
+                    current_material = material_indices.get(args).copied();
+                    if current_material.is_none() && !unknown_materials.iter().any(|m| m == args) {
+                        log::warn!("{:?}:{}: unknown material {:?}", path, line, args);
+                        unknown_materials.push(args.to_owned());
+                    }
+                }
+                // Grouping, smoothing groups, points and lines.
+                _ => {}
+            }
+
+            Ok(())
+        })()
+        .with_context(|| format!("{:?}:{}", path, line))?;
+    }
+
+    if res.colors.len() != res.positions.len() {
+        res.colors.clear();
+    }
+
+    Ok(res)
+}
+
+/// Parses the options and file name of a `map_*` statement.
+fn parse_map(base: &Path, args: &str) -> Result<MtlMap> {
+    let mut res = MtlMap {
+        path: PathBuf::new(),
+        scale: [1.0, 1.0],
+        offset: [0.0, 0.0],
+        clamp: false,
+    };
+
+    let mut tokens = args.split_whitespace().peekable();
+    let mut name = Vec::new();
+
+    while let Some(token) = tokens.next() {
+        if !name.is_empty() || !token.starts_with('-') {
+            name.push(token);
+            continue;
+        }
+
+        // Up to three numbers; U, V and W for the vector options.
+        let mut numbers = |max_count: usize| -> Vec<f32> {
+            let mut res = Vec::new();
+            while res.len() < max_count {
+                match tokens.peek().and_then(|v| v.parse::<f32>().ok()) {
+                    Some(v) => {
+                        res.push(v);
+                        tokens.next();
+                    }
+                    None => break,
+                }
+            }
+            res
+        };
+
+        match token {
+            "-s" => {
+                let s = numbers(3);
+                res.scale = [*s.first().unwrap_or(&1.0), *s.get(1).unwrap_or(&1.0)];
+            }
+            "-o" => {
+                let o = numbers(3);
+                res.offset = [*o.first().unwrap_or(&0.0), *o.get(1).unwrap_or(&0.0)];
+            }
+            "-t" => {
+                numbers(3);
+            }
+            "-mm" => {
+                numbers(2);
+            }
+            "-clamp" => res.clamp = tokens.next() == Some("on"),
+            "-blendu" | "-blendv" | "-cc" | "-imfchan" | "-type" => {
+                tokens.next();
+            }
+            "-bm" | "-boost" | "-texres" => {
+                numbers(1);
+            }
+            _ => log::warn!("Unknown texture option {:?}", token),
+        }
+    }
+
+    anyhow::ensure!(!name.is_empty(), "Missing the texture file name");
+    res.path = resolve_path(base, &name.join(" "));
+
+    Ok(res)
+}
+
+/// Parses the materials of the MTL file at `path`.
+pub fn load_mtl(path: &Path) -> Result<Vec<MtlMaterial>> {
+    let base = path.parent().unwrap_or_else(|| Path::new("./"));
+    let text = fs::read_to_string(path).with_context(|| format!("Reading {:?}", path))?;
+
+    let mut res: Vec<MtlMaterial> = Vec::new();
+
+    for (line, keyword, args) in statements(&text) {
+        if keyword == "newmtl" {
+            res.push(MtlMaterial {
+                name: args.to_owned(),
+                ..Default::default()
+            });
+            continue;
+        }
+
+        let Some(mat) = res.last_mut() else {
+            continue;
+        };
+
+        (|| -> Result<()> {
+            match keyword.to_ascii_lowercase().as_str() {
+                "kd" => mat.diffuse = Some(parse_floats(args)?),
+                "ks" => mat.specular = Some(parse_floats(args)?),
+                "ke" => mat.emissive = Some(parse_floats(args)?),
+                "ns" => mat.shininess = Some(parse_floats::<1>(args)?[0]),
+                "d" => mat.dissolve = Some(parse_floats::<1>(args)?[0]),
+                "tr" => mat.dissolve = Some(1.0 - parse_floats::<1>(args)?[0]),
+                "ni" => mat.ior = Some(parse_floats::<1>(args)?[0]),
+                "pr" => mat.roughness = Some(parse_floats::<1>(args)?[0]),
+                "pm" => mat.metalness = Some(parse_floats::<1>(args)?[0]),
+                "map_kd" => mat.diffuse_map = Some(parse_map(base, args)?),
+                "map_ks" => mat.specular_map = Some(parse_map(base, args)?),
+                "map_ns" => mat.shininess_map = Some(parse_map(base, args)?),
+                "map_ke" => mat.emissive_map = Some(parse_map(base, args)?),
+                "map_d" => mat.dissolve_map = Some(parse_map(base, args)?),
+                "norm" | "map_kn" => mat.normal_map = Some(parse_map(base, args)?),
+                "bump" | "map_bump" => mat.bump_map = Some(parse_map(base, args)?),
+                "map_pr" => mat.roughness_map = Some(parse_map(base, args)?),
+                "map_pm" => mat.metalness_map = Some(parse_map(base, args)?),
+                // Ambient, illumination models, and the rest.
+                _ => {}
+            }
+
+            Ok(())
+        })()
+        .with_context(|| format!("{:?}:{}", path, line))?;
+    }
+
+    Ok(res)
+}
+
+/// Paths of the files which an OBJ scene is loaded from: the OBJ itself, and the material
+/// libraries and textures it refers to. Missing ones are skipped, as they are when loading.
+pub fn file_dependencies(path: &Path) -> Result<Vec<PathBuf>> {
+    let base = path.parent().unwrap_or_else(|| Path::new("./"));
+    let text = fs::read_to_string(path).with_context(|| format!("Reading {:?}", path))?;
+
+    let mut paths = vec![path.to_owned()];
+
+    for (_, keyword, args) in statements(&text) {
+        if keyword != "mtllib" {
+            continue;
+        }
+
+        for mtl_path in mtl_library_paths(base, args) {
+            if !mtl_path.exists() {
+                continue;
+            }
+
+            let materials = load_mtl(&mtl_path)?;
+            let map_paths = materials
+                .iter()
+                .flat_map(|material| material.maps())
+                .map(|map| &map.path);
+
+            for dependency in std::iter::once(&mtl_path).chain(map_paths) {
+                if dependency.exists() && !paths.contains(dependency) {
+                    paths.push(dependency.clone());
+                }
+            }
+        }
+    }
+
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_index_handles_absolute_and_relative_indices() {
+        assert_eq!(resolve_index("1", 3).unwrap(), 0);
+        assert_eq!(resolve_index("3", 3).unwrap(), 2);
+        assert_eq!(resolve_index("-1", 3).unwrap(), 2);
+        assert_eq!(resolve_index("-3", 3).unwrap(), 0);
+
+        assert!(resolve_index("0", 3).is_err());
+        assert!(resolve_index("4", 3).is_err());
+        assert!(resolve_index("-4", 3).is_err());
+        assert!(resolve_index("x", 3).is_err());
+    }
+
+    #[test]
+    fn parse_map_reads_options_and_names() {
+        let base = Path::new("textures");
+
+        let map = parse_map(base, "-s 2 3 1 -o 0.5 0.25 -clamp on -bm 0.3 wood.png").unwrap();
+        assert_eq!(map.path, base.join("wood.png"));
+        assert_eq!(map.scale, [2.0, 3.0]);
+        assert_eq!(map.offset, [0.5, 0.25]);
+        assert!(map.clamp);
+
+        let map = parse_map(base, "-imfchan r sub\\dark oak.png").unwrap();
+        assert_eq!(map.path, base.join("sub/dark oak.png"));
+        assert_eq!(map.scale, [1.0, 1.0]);
+        assert!(!map.clamp);
+
+        assert!(parse_map(base, "-clamp on").is_err());
+    }
+
+    #[test]
+    fn load_mtl_reads_materials() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path();
+        let mtl_path = dir.join("a.mtl");
+        fs::write(
+            &mtl_path,
+            "# comment\n\
+             Kd 1 1 1\n\
+             newmtl red\n\
+             Kd 1 0 0 # trailing comment\n\
+             Ns 100\n\
+             Tr 0.25\n\
+             map_Kd -s 2 2 red.png\n\
+             newmtl glass\n\
+             d 0.5\n\
+             Ni 1.45\n\
+             Pr 0.1\n",
+        )
+        .unwrap();
+
+        let materials = load_mtl(&mtl_path).unwrap();
+        assert_eq!(materials.len(), 2);
+
+        let red = &materials[0];
+        assert_eq!(red.name, "red");
+        assert_eq!(red.diffuse, Some([1.0, 0.0, 0.0]));
+        assert_eq!(red.shininess, Some(100.0));
+        assert_eq!(red.dissolve, Some(0.75));
+        let diffuse_map = red.diffuse_map.as_ref().unwrap();
+        assert_eq!(diffuse_map.path, dir.join("red.png"));
+        assert_eq!(diffuse_map.scale, [2.0, 2.0]);
+
+        let glass = &materials[1];
+        assert_eq!(glass.name, "glass");
+        assert_eq!(glass.dissolve, Some(0.5));
+        assert_eq!(glass.ior, Some(1.45));
+        assert_eq!(glass.roughness, Some(0.1));
+        assert!(glass.diffuse.is_none());
+
+        fs::write(&mtl_path, "newmtl broken\nKd 1 x 0\n").unwrap();
+        assert!(load_mtl(&mtl_path).is_err());
+    }
+
+    #[test]
+    fn load_reads_every_material_library() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path();
+        fs::write(dir.join("a.mtl"), "newmtl a\nKd 1 0 0\n").unwrap();
+        fs::write(dir.join("b.mtl"), "newmtl b\nKd 0 1 0\n").unwrap();
+        fs::write(
+            dir.join("scene.obj"),
+            "mtllib a.mtl b.mtl\n\
+             v 0 0 0\nv 1 0 0\nv 0 1 0\nv 1 1 0\n\
+             usemtl b\n\
+             f 1 2 3\n\
+             usemtl a\n\
+             f -3 -1 -2\n",
+        )
+        .unwrap();
+
+        let obj = load(&dir.join("scene.obj")).unwrap();
+        let names: Vec<&str> = obj.materials.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, ["a", "b"]);
+        assert_eq!(obj.faces.len(), 2);
+        assert_eq!(obj.faces[0].material, Some(1));
+        assert_eq!(obj.faces[1].material, Some(0));
+
+        let corners: Vec<u32> = obj.corners[3..].iter().map(|c| c.position).collect();
+        assert_eq!(corners, [1, 3, 2]);
+
+        let dependencies = file_dependencies(&dir.join("scene.obj")).unwrap();
+        assert_eq!(dependencies.len(), 3);
+    }
+}
@@ -2,3 +2,4 @@ pub mod image;
 pub mod mesh;
 
 mod import_gltf;
+mod import_obj;
@@ -3,7 +3,6 @@
 
 use byteorder::{ByteOrder, NativeEndian, WriteBytesExt};
 use glam::{Mat4, Quat, Vec3, Vec4};
-use gltf::texture::TextureTransform;
 use kajiya_backend::bytes::into_byte_vec;
 /*use render_core::{
     constants::MAX_VERTEX_STREAMS,
@@ -17,13 +16,17 @@ use kajiya_backend::bytes::into_byte_vec;
 };*/
 use anyhow::Context as _;
 use std::{
+    collections::HashMap,
     hash::Hash,
     mem::size_of,
     path::{Path, PathBuf},
 };
 use turbosloth::*;
 
-use crate::image::ImageSource;
+use crate::{
+    image::ImageSource,
+    import_obj::{MtlMap, MtlMaterial, ObjCorner},
+};
 
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
 pub enum TexGamma {
@@ -48,6 +51,61 @@ impl TexCompressionMode {
     }
 }
 
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum TexFilter {
+    Linear,
+    Nearest,
+}
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum TexWrap {
+    Repeat,
+    MirroredRepeat,
+    ClampToEdge,
+}
+
+/// Sampler state of a material map. Shaders choose between a fixed set of device samplers,
+/// so the same wrap mode is used along both axes.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub struct TexSampler {
+    pub filter: TexFilter,
+    pub mipmap_filter: TexFilter,
+    pub wrap: TexWrap,
+}
+
+impl Default for TexSampler {
+    fn default() -> Self {
+        Self {
+            filter: TexFilter::Linear,
+            mipmap_filter: TexFilter::Linear,
+            wrap: TexWrap::Repeat,
+        }
+    }
+}
+
+impl TexSampler {
+    /// Packs into the `MeshMaterial::map_samplers` encoding understood by `material_samplers.hlsl`.
+    pub fn pack(&self) -> u32 {
+        let filter = match self.filter {
+            TexFilter::Linear => 0,
+            TexFilter::Nearest => 1,
+        };
+
+        let mipmap_filter = match self.mipmap_filter {
+            TexFilter::Linear => 0,
+            TexFilter::Nearest => 2,
+        };
+
+        let wrap = match self.wrap {
+            TexWrap::Repeat => 0,
+            TexWrap::MirroredRepeat => 4,
+            TexWrap::ClampToEdge => 8,
+        };
+
+        filter | mipmap_filter | wrap
+    }
+}
+
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
 pub struct TexParams {
     pub gamma: TexGamma,
@@ -56,6 +114,45 @@ pub struct TexParams {
     pub channel_swizzle: Option<[usize; 4]>,
 }
 
+/// Constant factors of a `KHR_materials_pbrSpecularGlossiness` material.
+#[derive(Debug, Clone, Copy)]
+pub struct SpecGlossFactors {
+    pub diffuse: [f32; 4],
+    pub specular: [f32; 3],
+    pub glossiness: f32,
+}
+
+impl SpecGlossFactors {
+    fn bits(&self) -> [u32; 8] {
+        let d = self.diffuse;
+        let s = self.specular;
+        [d[0], d[1], d[2], d[3], s[0], s[1], s[2], self.glossiness].map(f32::to_bits)
+    }
+}
+
+impl std::hash::Hash for SpecGlossFactors {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.bits().hash(state);
+    }
+}
+
+impl PartialEq for SpecGlossFactors {
+    fn eq(&self, other: &Self) -> bool {
+        self.bits() == other.bits()
+    }
+}
+
+impl Eq for SpecGlossFactors {}
+
+/// Which metallic-roughness map to derive from a specular-glossiness material.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum SpecGlossOutput {
+    /// sRGB base color in RGB, with diffuse alpha in A.
+    BaseColor,
+    /// Roughness in R and metalness in G, laid out like the swizzled metallic-roughness map.
+    RoughnessMetalness,
+}
+
 #[derive(Clone, Hash, PartialEq, Eq)]
 pub enum MeshMaterialMap {
     Image {
@@ -63,23 +160,83 @@ pub enum MeshMaterialMap {
         params: TexParams,
     },
     Placeholder([u8; 4]),
+    /// Converted from specular-glossiness to metallic-roughness when baking.
+    /// Missing textures are treated as white, as per the spec.
+    SpecGloss {
+        diffuse: Option<ImageSource>,
+        specular_glossiness: Option<ImageSource>,
+        factors: SpecGlossFactors,
+        output: SpecGlossOutput,
+        params: TexParams,
+    },
 }
 
 pub struct MeshMaterialFlags;
 impl MeshMaterialFlags {
     pub const MESH_MATERIAL_FLAG_EMISSIVE_USED_AS_LIGHT: u32 = 1;
+
+    /// Pixels with alpha below `MeshMaterial::alpha_cutoff` are discarded.
+    pub const MESH_MATERIAL_FLAG_ALPHA_MASK: u32 = 2;
+
+    /// Back faces are rendered too, with their normals flipped.
+    pub const MESH_MATERIAL_FLAG_DOUBLE_SIDED: u32 = 4;
 }
 
+/// Number of texture maps in a `MeshMaterial`; must match `mesh.hlsl`.
+pub const MESH_MATERIAL_MAP_COUNT: usize = 5;
+
 #[derive(Clone, Copy)]
 #[repr(C)]
 pub struct MeshMaterial {
     pub base_color_mult: [f32; 4],
-    pub maps: [u32; 4],
+    pub maps: [u32; MESH_MATERIAL_MAP_COUNT],
     pub roughness_mult: f32,
     pub metalness_factor: f32,
+    /// Emitted radiance in the renderer's units, not nits; `KHR_materials_emissive_strength`
+    /// is applied as a multiplier.
     pub emissive: [f32; 3],
     pub flags: u32,
-    pub map_transforms: [[f32; 6]; 4],
+    pub map_transforms: [[f32; 6]; MESH_MATERIAL_MAP_COUNT],
+    pub alpha_cutoff: f32,
+
+    /// `TexSampler::pack`ed; indexed like `map_transforms`.
+    pub map_samplers: [u32; MESH_MATERIAL_MAP_COUNT],
+    pub occlusion_strength: f32,
+
+    /// Index of the UV set (0 or 1) each map is sampled with; indexed like `map_transforms`.
+    pub map_uv_sets: [u32; MESH_MATERIAL_MAP_COUNT],
+
+    /// Strength of the clearcoat layer; zero disables it.
+    pub clearcoat_factor: f32,
+    /// Perceptual roughness of the clearcoat layer.
+    pub clearcoat_roughness: f32,
+
+    /// Fraction of light passing through the surface, treated as infinitely thin.
+    pub transmission_factor: f32,
+    pub ior: f32,
+
+    /// Color of the sheen layer; black disables it.
+    pub sheen_color: [f32; 3],
+    /// Perceptual roughness of the sheen layer.
+    pub sheen_roughness: f32,
+
+    /// Strength of specular anisotropy, and the angle in radians by which its direction
+    /// is rotated from the tangent, counter-clockwise.
+    pub anisotropy_strength: f32,
+    pub anisotropy_rotation: f32,
+}
+
+/// A simplified version of a mesh, generated when baking. Its indices refer to the same
+/// vertices as the full-resolution ones.
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct MeshLod {
+    /// Range of `PackedTriMesh::lod_indices`.
+    pub first_index: u32,
+    pub index_count: u32,
+
+    /// How far the simplified surface may stray from the original one, in mesh space.
+    pub error: f32,
 }
 
 #[derive(Clone, Default)]
@@ -88,12 +245,98 @@ pub struct TriangleMesh {
     pub normals: Vec<[f32; 3]>,
     pub colors: Vec<[f32; 4]>,
     pub uvs: Vec<[f32; 2]>,
+    /// Empty unless some primitive has a second uv set. Vertices of those which don't
+    /// use the first set here, so that maps which refer to the second set fall back to it.
+    pub uvs1: Vec<[f32; 2]>,
     pub tangents: Vec<[f32; 4]>,
     pub material_ids: Vec<u32>, // per index, but can be flat shaded
     pub indices: Vec<u32>,
     pub materials: Vec<MeshMaterial>, // global
     pub maps: Vec<MeshMaterialMap>,   // global
     pub images: Vec<ImageSource>,
+
+    /// Per vertex if any primitive is skinned; empty otherwise. Only meaningful with `skin`.
+    pub joints: Vec<[u16; 4]>,
+    pub weights: Vec<[f32; 4]>,
+    pub skin: Option<MeshSkin>,
+
+    /// Position and normal offsets of each morph target, vertex-major: the offsets of vertex `v`
+    /// start at `v * morph_default_weights.len()`. Empty without morph targets.
+    pub morph_position_deltas: Vec<[f32; 3]>,
+    pub morph_normal_deltas: Vec<[f32; 3]>,
+    /// One per morph target.
+    pub morph_default_weights: Vec<f32>,
+}
+
+/// A joint of the skeleton deforming a skinned mesh.
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct SkinJoint {
+    /// Index of the parent joint, or `SkinJoint::NO_PARENT`. Parents precede their children.
+    pub parent: u32,
+
+    /// Column-major; the static transform from the space of the parent joint
+    /// (or of the mesh, for roots) to that of the joint's parent node.
+    pub parent_offset: [f32; 16],
+
+    /// The rest pose, relative to `parent_offset`. Overridden by animation channels.
+    pub translation: [f32; 3],
+    pub rotation: [f32; 4],
+    pub scale: [f32; 3],
+
+    /// Column-major.
+    pub inverse_bind_matrix: [f32; 16],
+}
+
+impl SkinJoint {
+    pub const NO_PARENT: u32 = u32::MAX;
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[repr(u32)]
+pub enum AnimationPath {
+    Translation,
+    Rotation,
+    Scale,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[repr(u32)]
+pub enum AnimationInterpolation {
+    Step,
+    Linear,
+    CubicSpline,
+}
+
+/// Keyframes animating one property of a `SkinJoint`.
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct AnimationChannel {
+    /// Index into `MeshSkin::animation_clip_names`.
+    pub clip: u32,
+    pub joint: u32,
+    pub path: AnimationPath,
+    pub interpolation: AnimationInterpolation,
+
+    /// Range of `MeshSkin::animation_key_times`.
+    pub first_key: u32,
+    pub key_count: u32,
+
+    /// Start of the values in `MeshSkin::animation_key_values`: one per key, or three
+    /// (in-tangent, value, out-tangent) with cubic spline interpolation.
+    pub first_value: u32,
+}
+
+/// The skeleton of a skinned mesh, and the animation clips which move it.
+#[derive(Clone, Default)]
+pub struct MeshSkin {
+    pub joints: Vec<SkinJoint>,
+    pub animation_clip_names: Vec<String>,
+    pub animation_channels: Vec<AnimationChannel>,
+    pub animation_key_times: Vec<f32>,
+
+    /// Translations and scales in `xyz`; rotations as `xyzw` quaternions.
+    pub animation_key_values: Vec<[f32; 4]>,
 }
 
 fn iter_gltf_node_tree<F: FnMut(&gltf::scene::Node, Mat4)>(
@@ -110,6 +353,124 @@ fn iter_gltf_node_tree<F: FnMut(&gltf::scene::Node, Mat4)>(
     }
 }
 
+/// Transforms of the instances of a node using `EXT_mesh_gpu_instancing`, relative to the node.
+/// `None` if the node isn't instanced.
+fn gltf_node_gpu_instances(
+    node: &gltf::scene::Node,
+    document: &gltf::Document,
+    buffers: &[bytes::Bytes],
+) -> anyhow::Result<Option<Vec<Mat4>>> {
+    use gltf::accessor::{DataType, Dimensions, Iter};
+
+    let Some(attributes) = node
+        .extensions()
+        .and_then(|ext| ext.get("EXT_mesh_gpu_instancing"))
+        .and_then(|ext| ext.get("attributes"))
+        .and_then(|attributes| attributes.as_object())
+    else {
+        return Ok(None);
+    };
+
+    let get_buffer_data = |buffer: gltf::Buffer| Some(&buffers[buffer.index()][..]);
+
+    let accessor = |name: &str, dimensions: Dimensions| -> anyhow::Result<Option<gltf::Accessor>> {
+        let Some(index) = attributes.get(name) else {
+            return Ok(None);
+        };
+
+        let accessor = index
+            .as_u64()
+            .and_then(|index| document.accessors().nth(index as usize))
+            .with_context(|| format!("Invalid accessor for the instance {}", name))?;
+
+        anyhow::ensure!(
+            accessor.dimensions() == dimensions,
+            "Instance {} must be a {:?}, not {:?}",
+            name,
+            dimensions,
+            accessor.dimensions()
+        );
+
+        Ok(Some(accessor))
+    };
+
+    let read_vec3 = |name: &str| -> anyhow::Result<Option<Vec<Vec3>>> {
+        let Some(accessor) = accessor(name, Dimensions::Vec3)? else {
+            return Ok(None);
+        };
+
+        anyhow::ensure!(
+            accessor.data_type() == DataType::F32,
+            "Quantized instance {} is not supported",
+            name
+        );
+
+        let iter = Iter::<[f32; 3]>::new(accessor, get_buffer_data)
+            .with_context(|| format!("Reading the instance {}", name))?;
+
+        Ok(Some(iter.map(Vec3::from).collect()))
+    };
+
+    let translations = read_vec3("TRANSLATION")?;
+    let scales = read_vec3("SCALE")?;
+
+    // Rotations may also be normalized signed integers.
+    let rotations: Option<Vec<Quat>> = match accessor("ROTATION", Dimensions::Vec4)? {
+        Some(accessor) => {
+            let rotations: Option<Vec<[f32; 4]>> = match accessor.data_type() {
+                DataType::F32 => {
+                    Iter::<[f32; 4]>::new(accessor, get_buffer_data).map(|iter| iter.collect())
+                }
+                DataType::I16 => Iter::<[i16; 4]>::new(accessor, get_buffer_data).map(|iter| {
+                    iter.map(|q| q.map(|c| (c as f32 / 32767.0).max(-1.0)))
+                        .collect()
+                }),
+                DataType::I8 => Iter::<[i8; 4]>::new(accessor, get_buffer_data).map(|iter| {
+                    iter.map(|q| q.map(|c| (c as f32 / 127.0).max(-1.0)))
+                        .collect()
+                }),
+                data_type => anyhow::bail!("Unsupported instance ROTATION type {:?}", data_type),
+            };
+
+            let rotations = rotations.context("Reading the instance ROTATION")?;
+            Some(
+                rotations
+                    .into_iter()
+                    .map(|q| Quat::from_array(q).normalize())
+                    .collect(),
+            )
+        }
+        None => None,
+    };
+
+    let counts = [
+        translations.as_ref().map(Vec::len),
+        rotations.as_ref().map(Vec::len),
+        scales.as_ref().map(Vec::len),
+    ];
+    let Some(count) = counts.iter().flatten().copied().next() else {
+        return Ok(None);
+    };
+
+    anyhow::ensure!(
+        counts.iter().flatten().all(|&c| c == count),
+        "Instance attributes of node {:?} differ in length",
+        node.name().unwrap_or_default()
+    );
+
+    Ok(Some(
+        (0..count)
+            .map(|i| {
+                Mat4::from_scale_rotation_translation(
+                    scales.as_ref().map_or(Vec3::ONE, |v| v[i]),
+                    rotations.as_ref().map_or(Quat::IDENTITY, |v| v[i]),
+                    translations.as_ref().map_or(Vec3::ZERO, |v| v[i]),
+                )
+            })
+            .collect(),
+    ))
+}
+
 fn get_gltf_texture_source(tex: gltf::texture::Texture) -> Option<String> {
     match tex.source().source() {
         gltf::image::Source::Uri { uri, .. } => Some(uri.to_string()),
@@ -117,43 +478,149 @@ fn get_gltf_texture_source(tex: gltf::texture::Texture) -> Option<String> {
     }
 }
 
+fn load_gltf_sampler(
+    mat: &gltf::material::Material,
+    texture: &gltf::texture::Texture,
+) -> TexSampler {
+    use gltf::texture::{MagFilter, MinFilter, WrappingMode};
+
+    let sampler = texture.sampler();
+
+    let filter = match sampler.mag_filter() {
+        Some(MagFilter::Nearest) => TexFilter::Nearest,
+        Some(MagFilter::Linear) | None => TexFilter::Linear,
+    };
+
+    let mipmap_filter = match sampler.min_filter() {
+        Some(MinFilter::NearestMipmapNearest) | Some(MinFilter::LinearMipmapNearest) => {
+            TexFilter::Nearest
+        }
+        _ => TexFilter::Linear,
+    };
+
+    // Only one wrap mode is supported for both axes. Clamping wins, since that's what
+    // decals and atlases rely on to avoid bleeding.
+    let wrap = match (sampler.wrap_s(), sampler.wrap_t()) {
+        (WrappingMode::ClampToEdge, _) | (_, WrappingMode::ClampToEdge) => TexWrap::ClampToEdge,
+        (WrappingMode::MirroredRepeat, _) | (_, WrappingMode::MirroredRepeat) => {
+            TexWrap::MirroredRepeat
+        }
+        (WrappingMode::Repeat, WrappingMode::Repeat) => TexWrap::Repeat,
+    };
+
+    if sampler.wrap_s() != sampler.wrap_t() {
+        log::warn!(
+            "Texture {} {:?} of material {:?} wraps as {:?} along U, but {:?} along V; \
+            using {:?} for both",
+            texture.index(),
+            texture.name().unwrap_or_default(),
+            mat.name().unwrap_or_default(),
+            sampler.wrap_s(),
+            sampler.wrap_t(),
+            wrap,
+        );
+    }
+
+    TexSampler {
+        filter,
+        mipmap_filter,
+        wrap,
+    }
+}
+
 fn load_gltf_material(
     mat: &gltf::material::Material,
     document_images: &[ImageSource],
 ) -> (Vec<MeshMaterialMap>, MeshMaterial) {
     const DEFAULT_MAP_TRANSFORM: [f32; 6] = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
-    let mut map_transforms: [[f32; 6]; 4] = [DEFAULT_MAP_TRANSFORM; 4];
-
-    fn texture_transform_to_matrix(xform: Option<TextureTransform>) -> [f32; 6] {
-        if let Some(xform) = xform {
-            let r = xform.rotation();
-            let s = xform.scale();
-            let o = xform.offset();
-
-            [
-                r.cos() * s[0],
-                r.sin() * s[1],
-                -r.sin() * s[0],
-                r.cos() * s[1],
-                o[0],
-                o[1],
-            ]
+    let mut map_transforms = [DEFAULT_MAP_TRANSFORM; MESH_MATERIAL_MAP_COUNT];
+    let mut map_samplers = [TexSampler::default().pack(); MESH_MATERIAL_MAP_COUNT];
+    let mut map_uv_sets = [0u32; MESH_MATERIAL_MAP_COUNT];
+
+    fn texture_transform_to_matrix(offset: [f32; 2], rotation: f32, scale: [f32; 2]) -> [f32; 6] {
+        let r = rotation;
+        let s = scale;
+        let o = offset;
+
+        [
+            r.cos() * s[0],
+            r.sin() * s[1],
+            -r.sin() * s[0],
+            r.cos() * s[1],
+            o[0],
+            o[1],
+        ]
+    }
+
+    fn uv_set(tex_coord: u32) -> u32 {
+        if tex_coord > 1 {
+            log::warn!(
+                "TEXCOORD_{} is not supported; using TEXCOORD_0 instead",
+                tex_coord
+            );
+            0
         } else {
-            DEFAULT_MAP_TRANSFORM
+            tex_coord
         }
     }
 
-    let (albedo_map, albedo_map_transform) = mat
-        .pbr_metallic_roughness()
-        .base_color_texture()
-        .or_else(|| mat.pbr_specular_glossiness()?.diffuse_texture())
-        .map_or(
+    // Transform and UV set of a texture which the `gltf` crate parses `KHR_texture_transform` for.
+    fn info_transform(tex: &gltf::texture::Info) -> ([f32; 6], u32) {
+        if let Some(xform) = tex.texture_transform() {
+            (
+                texture_transform_to_matrix(xform.offset(), xform.rotation(), xform.scale()),
+                uv_set(xform.tex_coord().unwrap_or(tex.tex_coord())),
+            )
+        } else {
+            (DEFAULT_MAP_TRANSFORM, uv_set(tex.tex_coord()))
+        }
+    }
+
+    // Normal and occlusion textures don't get `KHR_texture_transform` parsed by the `gltf` crate,
+    // so read the extension's JSON directly.
+    fn extension_transform(
+        extension: Option<&gltf::json::Value>,
+        tex_coord: u32,
+    ) -> ([f32; 6], u32) {
+        let Some(xform) = extension else {
+            return (DEFAULT_MAP_TRANSFORM, uv_set(tex_coord));
+        };
+
+        let vec2 = |name: &str, default: [f32; 2]| -> [f32; 2] {
+            xform
+                .get(name)
+                .and_then(|v| v.as_array())
+                .and_then(|v| Some([v.first()?.as_f64()? as f32, v.get(1)?.as_f64()? as f32]))
+                .unwrap_or(default)
+        };
+
+        let offset = vec2("offset", [0.0, 0.0]);
+        let scale = vec2("scale", [1.0, 1.0]);
+        let rotation = xform
+            .get("rotation")
+            .and_then(|v| v.as_f64())
+            .unwrap_or(0.0) as f32;
+        let tex_coord = xform
+            .get("texCoord")
+            .and_then(|v| v.as_u64())
+            .map_or(tex_coord, |v| v as u32);
+
+        (
+            texture_transform_to_matrix(offset, rotation, scale),
+            uv_set(tex_coord),
+        )
+    }
+
+    let (mut albedo_map, albedo_map_transform) =
+        mat.pbr_metallic_roughness().base_color_texture().map_or(
             (
                 MeshMaterialMap::Placeholder([255, 255, 255, 255]),
                 DEFAULT_MAP_TRANSFORM,
             ),
             |tex| {
-                let transform = texture_transform_to_matrix(tex.texture_transform());
+                let (transform, uv_set) = info_transform(&tex);
+                map_uv_sets[0] = uv_set;
+                map_samplers[0] = load_gltf_sampler(mat, &tex.texture()).pack();
 
                 (
                     MeshMaterialMap::Image {
@@ -172,10 +639,16 @@ fn load_gltf_material(
 
     map_transforms[0] = albedo_map_transform;
 
-    // TODO: add texture transform to the normal map in the `gltf` crate
     let normal_map =
         mat.normal_texture()
             .map_or(MeshMaterialMap::Placeholder([127, 127, 255, 255]), |tex| {
+                (map_transforms[1], map_uv_sets[1]) = extension_transform(
+                    tex.extensions()
+                        .and_then(|ext| ext.get("KHR_texture_transform")),
+                    tex.tex_coord(),
+                );
+                map_samplers[1] = load_gltf_sampler(mat, &tex.texture()).pack();
+
                 MeshMaterialMap::Image {
                     source: document_images[tex.texture().source().index()].clone(),
                     params: TexParams {
@@ -187,7 +660,7 @@ fn load_gltf_material(
                 }
             });
 
-    let (spec_map, spec_map_transform) = mat
+    let (mut spec_map, spec_map_transform) = mat
         .pbr_metallic_roughness()
         .metallic_roughness_texture()
         .map_or_else(
@@ -200,6 +673,10 @@ fn load_gltf_material(
                 )
             },
             |tex| {
+                let (transform, uv_set) = info_transform(&tex);
+                map_uv_sets[2] = uv_set;
+                map_samplers[2] = load_gltf_sampler(mat, &tex.texture()).pack();
+
                 (
                     MeshMaterialMap::Image {
                         source: document_images[tex.texture().source().index()].clone(),
@@ -210,7 +687,7 @@ fn load_gltf_material(
                             channel_swizzle: Some([1, 2, 0, 3]),
                         },
                     },
-                    texture_transform_to_matrix(tex.texture_transform()),
+                    transform,
                 )
             },
         );
@@ -219,7 +696,8 @@ fn load_gltf_material(
 
     let mut emissive_map = MeshMaterialMap::Placeholder([255, 255, 255, 255]);
     if let Some(tex) = mat.emissive_texture() {
-        map_transforms[3] = texture_transform_to_matrix(tex.texture_transform());
+        (map_transforms[3], map_uv_sets[3]) = info_transform(&tex);
+        map_samplers[3] = load_gltf_sampler(mat, &tex.texture()).pack();
         emissive_map = MeshMaterialMap::Image {
             source: document_images[tex.texture().source().index()].clone(),
             params: TexParams {
@@ -231,24 +709,230 @@ fn load_gltf_material(
         }
     }
 
-    let emissive = mat.emissive_factor();
+    let mut occlusion_map = MeshMaterialMap::Placeholder([255, 255, 255, 255]);
+    let mut occlusion_strength = 1.0;
+    if let Some(tex) = mat.occlusion_texture() {
+        (map_transforms[4], map_uv_sets[4]) = extension_transform(
+            tex.extensions()
+                .and_then(|ext| ext.get("KHR_texture_transform")),
+            tex.tex_coord(),
+        );
+        map_samplers[4] = load_gltf_sampler(mat, &tex.texture()).pack();
+        occlusion_strength = tex.strength();
+        occlusion_map = MeshMaterialMap::Image {
+            source: document_images[tex.texture().source().index()].clone(),
+            params: TexParams {
+                gamma: TexGamma::Linear,
+                use_mips: true,
+                compression: TexCompressionMode::Rg,
+                channel_swizzle: None,
+            },
+        }
+    }
+
+    // glTF clamps `emissiveFactor` to [0, 1]; brighter emitters scale it by `emissiveStrength`.
+    // The extension specifies the product in nits, but glTF emitters without it are used
+    // as-is in the renderer's radiance units, in which the default sun is about 20.
+    // To keep the two consistent, `emissiveStrength` is applied as a plain multiplier
+    // rather than converted from nits like the intensities of punctual lights are.
+    let emissive_strength = mat
+        .extensions()
+        .and_then(|ext| ext.get("KHR_materials_emissive_strength"))
+        .and_then(|ext| ext.get("emissiveStrength"))
+        .and_then(|v| v.as_f64())
+        .map_or(1.0, |v| v as f32);
+    let emissive = mat.emissive_factor().map(|v| v * emissive_strength);
+
+    let mut base_color_mult = mat.pbr_metallic_roughness().base_color_factor();
+    let mut roughness_mult = mat.pbr_metallic_roughness().roughness_factor();
+    let mut metalness_factor = mat.pbr_metallic_roughness().metallic_factor();
+
+    if let Some(spec_gloss) = mat.pbr_specular_glossiness() {
+        let diffuse_tex = spec_gloss.diffuse_texture();
+        let spec_gloss_tex = spec_gloss.specular_glossiness_texture();
+
+        // Both derived maps are computed texel-by-texel from both textures, so they can only
+        // be sampled one way. Use the diffuse texture's mapping, which exporters share anyway.
+        if let Some(tex) = diffuse_tex.as_ref().or(spec_gloss_tex.as_ref()) {
+            let (transform, uv_set) = info_transform(tex);
+            let sampler = load_gltf_sampler(mat, &tex.texture()).pack();
+
+            for map_idx in [0, 2] {
+                map_transforms[map_idx] = transform;
+                map_uv_sets[map_idx] = uv_set;
+                map_samplers[map_idx] = sampler;
+            }
+        }
+
+        let source =
+            |tex: &gltf::texture::Info| document_images[tex.texture().source().index()].clone();
+
+        let spec_gloss_map = |output, params| MeshMaterialMap::SpecGloss {
+            diffuse: diffuse_tex.as_ref().map(source),
+            specular_glossiness: spec_gloss_tex.as_ref().map(source),
+            factors: SpecGlossFactors {
+                diffuse: spec_gloss.diffuse_factor(),
+                specular: spec_gloss.specular_factor(),
+                glossiness: spec_gloss.glossiness_factor(),
+            },
+            output,
+            params,
+        };
+
+        albedo_map = spec_gloss_map(
+            SpecGlossOutput::BaseColor,
+            TexParams {
+                gamma: TexGamma::Srgb,
+                use_mips: true,
+                compression: TexCompressionMode::Rgba,
+                channel_swizzle: None,
+            },
+        );
+        spec_map = spec_gloss_map(
+            SpecGlossOutput::RoughnessMetalness,
+            TexParams {
+                gamma: TexGamma::Linear,
+                use_mips: true,
+                compression: TexCompressionMode::Rg,
+                channel_swizzle: None,
+            },
+        );
 
-    let base_color_mult = mat.pbr_metallic_roughness().base_color_factor();
-    let roughness_mult = mat.pbr_metallic_roughness().roughness_factor();
-    let metalness_factor = mat.pbr_metallic_roughness().metallic_factor();
+        // The factors are baked into the derived maps.
+        base_color_mult = [1.0; 4];
+        roughness_mult = 1.0;
+        metalness_factor = 1.0;
+    }
 
     //mata.normal_texture().and_then(|tex| tex.transform())
 
+    let mut flags = 0;
+    let alpha_cutoff = match mat.alpha_mode() {
+        gltf::material::AlphaMode::Opaque => 0.0,
+        gltf::material::AlphaMode::Mask => {
+            flags |= MeshMaterialFlags::MESH_MATERIAL_FLAG_ALPHA_MASK;
+            mat.alpha_cutoff().unwrap_or(0.5)
+        }
+        // There's no blending in the renderer; approximate with alpha testing.
+        gltf::material::AlphaMode::Blend => {
+            flags |= MeshMaterialFlags::MESH_MATERIAL_FLAG_ALPHA_MASK;
+            0.5
+        }
+    };
+
+    let (clearcoat_factor, clearcoat_roughness) = mat
+        .extensions()
+        .and_then(|ext| ext.get("KHR_materials_clearcoat"))
+        .map_or((0.0, 0.0), |clearcoat| {
+            let factor = |name: &str| clearcoat.get(name).and_then(|v| v.as_f64()).unwrap_or(0.0);
+
+            if clearcoat.get("clearcoatTexture").is_some()
+                || clearcoat.get("clearcoatRoughnessTexture").is_some()
+            {
+                log::warn!("Clearcoat textures are not supported; using the factors only");
+            }
+
+            (
+                factor("clearcoatFactor") as f32,
+                factor("clearcoatRoughnessFactor") as f32,
+            )
+        });
+
+    let transmission_factor = mat
+        .extensions()
+        .and_then(|ext| ext.get("KHR_materials_transmission"))
+        .map_or(0.0, |transmission| {
+            if transmission.get("transmissionTexture").is_some() {
+                log::warn!("Transmission textures are not supported; using the factor only");
+            }
+
+            transmission
+                .get("transmissionFactor")
+                .and_then(|v| v.as_f64())
+                .unwrap_or(0.0) as f32
+        });
+
+    let ior = mat
+        .extensions()
+        .and_then(|ext| ext.get("KHR_materials_ior"))
+        .and_then(|ext| ext.get("ior"))
+        .and_then(|v| v.as_f64())
+        .map_or(1.5, |v| v as f32);
+
+    let (sheen_color, sheen_roughness) = mat
+        .extensions()
+        .and_then(|ext| ext.get("KHR_materials_sheen"))
+        .map_or(([0.0; 3], 0.0), |sheen| {
+            if sheen.get("sheenColorTexture").is_some()
+                || sheen.get("sheenRoughnessTexture").is_some()
+            {
+                log::warn!("Sheen textures are not supported; using the factors only");
+            }
+
+            let color = sheen
+                .get("sheenColorFactor")
+                .and_then(|v| v.as_array())
+                .filter(|v| v.len() == 3)
+                .map_or([0.0; 3], |v| {
+                    [0, 1, 2].map(|i| v[i].as_f64().unwrap_or(0.0) as f32)
+                });
+
+            let roughness = sheen
+                .get("sheenRoughnessFactor")
+                .and_then(|v| v.as_f64())
+                .unwrap_or(0.0) as f32;
+
+            (color, roughness)
+        });
+
+    let (anisotropy_strength, anisotropy_rotation) = mat
+        .extensions()
+        .and_then(|ext| ext.get("KHR_materials_anisotropy"))
+        .map_or((0.0, 0.0), |anisotropy| {
+            if anisotropy.get("anisotropyTexture").is_some() {
+                log::warn!("Anisotropy textures are not supported; using the factors only");
+            }
+
+            let factor = |name: &str| anisotropy.get(name).and_then(|v| v.as_f64()).unwrap_or(0.0);
+
+            (
+                factor("anisotropyStrength") as f32,
+                factor("anisotropyRotation") as f32,
+            )
+        });
+
+    if mat.double_sided() {
+        flags |= MeshMaterialFlags::MESH_MATERIAL_FLAG_DOUBLE_SIDED;
+    }
+
     (
-        vec![normal_map, spec_map, albedo_map, emissive_map],
+        vec![
+            normal_map,
+            spec_map,
+            albedo_map,
+            emissive_map,
+            occlusion_map,
+        ],
         MeshMaterial {
             base_color_mult,
-            maps: [0, 1, 2, 3],
+            maps: [0, 1, 2, 3, 4],
             roughness_mult,
             metalness_factor,
             emissive,
-            flags: 0,
+            flags,
             map_transforms,
+            alpha_cutoff,
+            map_samplers,
+            occlusion_strength,
+            map_uv_sets,
+            clearcoat_factor,
+            clearcoat_roughness,
+            transmission_factor,
+            ior,
+            sheen_color,
+            sheen_roughness,
+            anisotropy_strength,
+            anisotropy_rotation,
         },
     )
 }
@@ -271,174 +955,1218 @@ impl Hash for LoadGltfScene {
     }
 }
 
-#[async_trait]
-impl LazyWorker for LoadGltfScene {
-    type Output = anyhow::Result<TriangleMesh>;
+impl LoadGltfScene {
+    /// Files which the scene is loaded from. Used for invalidating baked assets.
+    pub fn source_files(&self) -> anyhow::Result<Vec<PathBuf>> {
+        crate::import_gltf::file_dependencies(&self.path)
+            .with_context(|| format!("Finding the source files of GLTF scene {:?}", self.path))
+    }
 
-    async fn run(self, _ctx: RunContext) -> Self::Output {
-        let (gltf, buffers, imgs) = crate::import_gltf::import(&self.path)
+    /// Whether the scene uses `EXT_mesh_gpu_instancing`, which a flattened bake places only once.
+    pub fn uses_gpu_instancing(&self) -> anyhow::Result<bool> {
+        let document = crate::import_gltf::document(&self.path)
             .with_context(|| format!("Loading GLTF scene from {:?}", self.path))?;
+        Ok(document
+            .extensions_used()
+            .any(|ext| ext == "EXT_mesh_gpu_instancing"))
+    }
+}
 
-        if let Some(scene) = gltf.default_scene().or_else(|| gltf.scenes().next()) {
-            let mut res: TriangleMesh = TriangleMesh::default();
+/// Converts the indices of a triangle strip or fan into a triangle list, as per the glTF spec.
+/// Degenerate triangles, used to stitch strips together, are dropped.
+fn triangle_list_indices(mode: gltf::mesh::Mode, indices: Vec<u32>) -> Vec<u32> {
+    let triangles: Vec<[u32; 3]> = match mode {
+        gltf::mesh::Mode::TriangleStrip => (0..indices.len().saturating_sub(2))
+            .map(|i| {
+                if i % 2 == 0 {
+                    [indices[i], indices[i + 1], indices[i + 2]]
+                } else {
+                    [indices[i], indices[i + 2], indices[i + 1]]
+                }
+            })
+            .collect(),
+        gltf::mesh::Mode::TriangleFan => (1..indices.len().saturating_sub(1))
+            .map(|i| [indices[i], indices[i + 1], indices[0]])
+            .collect(),
+        _ => return indices,
+    };
 
-            let mut process_node = |node: &gltf::scene::Node, xform: Mat4| {
-                if let Some(mesh) = node.mesh() {
-                    let flip_winding_order = xform.determinant() < 0.0;
+    triangles
+        .into_iter()
+        .filter(|[a, b, c]| a != b && b != c && a != c)
+        .flatten()
+        .collect()
+}
 
-                    for prim in mesh.primitives() {
-                        let reader = prim.reader(|buffer| Some(&buffers[buffer.index()]));
+/// Angle-weighted vertex normals for a counter-clockwise triangle list.
+fn generate_smooth_normals(positions: &[[f32; 3]], indices: &[u32]) -> Vec<[f32; 3]> {
+    let mut normals = vec![Vec3::ZERO; positions.len()];
 
-                        let res_material_index = res.materials.len() as u32;
+    for tri in indices.chunks_exact(3) {
+        let p = [
+            Vec3::from(positions[tri[0] as usize]),
+            Vec3::from(positions[tri[1] as usize]),
+            Vec3::from(positions[tri[2] as usize]),
+        ];
 
-                        {
-                            let (mut maps, mut material) =
-                                load_gltf_material(&prim.material(), imgs.as_slice());
+        let face_normal = (p[1] - p[0]).cross(p[2] - p[0]).normalize_or_zero();
+        if face_normal == Vec3::ZERO {
+            continue;
+        }
 
-                            let map_base = res.maps.len() as u32;
-                            for id in material.maps.iter_mut() {
-                                *id += map_base;
-                            }
+        for i in 0..3 {
+            let e0 = p[(i + 1) % 3] - p[i];
+            let e1 = p[(i + 2) % 3] - p[i];
+            normals[tri[i] as usize] += face_normal * e0.angle_between(e1);
+        }
+    }
 
-                            res.materials.push(material);
-                            res.maps.append(&mut maps);
-                        }
+    normals
+        .into_iter()
+        .map(|n| {
+            // Vertices not referenced by any (non-degenerate) triangle still need a valid normal.
+            let n = n.try_normalize().unwrap_or(Vec3::Z);
+            n.into()
+        })
+        .collect()
+}
 
-                        // Collect positions (required)
-                        let positions = if let Some(iter) = reader.read_positions() {
-                            iter.collect::<Vec<_>>()
-                        } else {
-                            return;
-                        };
-
-                        // Collect normals (required)
-                        let normals = if let Some(iter) = reader.read_normals() {
-                            iter.collect::<Vec<_>>()
-                        } else {
-                            return;
-                        };
-
-                        // Collect tangents (optional)
-                        let (mut tangents, tangents_found) =
-                            if let Some(iter) = reader.read_tangents() {
-                                (iter.collect::<Vec<_>>(), true)
-                            } else {
-                                (vec![[1.0, 0.0, 0.0, 0.0]; positions.len()], false)
-                            };
-
-                        // Collect uvs (optional)
-                        let (mut uvs, uvs_found) = if let Some(iter) = reader.read_tex_coords(0) {
-                            (iter.into_f32().collect::<Vec<_>>(), true)
-                        } else {
-                            (vec![[0.0, 0.0]; positions.len()], false)
-                        };
-
-                        // Collect colors (optional)
-                        let mut colors = if let Some(iter) = reader.read_colors(0) {
-                            iter.into_rgba_f32().collect::<Vec<_>>()
-                        } else {
-                            vec![[1.0, 1.0, 1.0, 1.0]; positions.len()]
-                        };
-
-                        // Collect material ids
-                        let mut material_ids = vec![res_material_index; positions.len()];
-
-                        // Collect indices
-                        let mut indices: Vec<u32>;
-                        {
-                            if let Some(indices_reader) = reader.read_indices() {
-                                indices = indices_reader.into_u32().collect();
-                            } else {
-                                if positions.is_empty() {
-                                    return;
-                                }
-
-                                match prim.mode() {
-                                    gltf::mesh::Mode::Triangles => {
-                                        indices = (0..positions.len() as u32).collect();
-                                    }
-                                    _ => {
-                                        panic!(
-                                            "Primitive mode {:?} not supported yet",
-                                            prim.mode()
-                                        );
-                                    }
-                                }
-                            }
-
-                            if flip_winding_order {
-                                for tri in indices.chunks_exact_mut(3) {
-                                    tri.swap(0, 2);
-                                }
-                            }
-                        }
+/// Appends the second uv set of a primitive to `res`, before its first set `uvs` gets appended.
+/// See `TriangleMesh::uvs1`.
+fn append_uvs1(res: &mut TriangleMesh, uvs: &[[f32; 2]], uvs1: Option<Vec<[f32; 2]>>) {
+    match uvs1 {
+        Some(mut uvs1) => {
+            // Pad for the earlier primitives, which don't have a second set.
+            if res.uvs1.is_empty() {
+                res.uvs1 = res.uvs.clone();
+            }
+            res.uvs1.append(&mut uvs1);
+        }
+        None if !res.uvs1.is_empty() => res.uvs1.extend_from_slice(uvs),
+        None => {}
+    }
+}
 
-                        if !tangents_found && uvs_found {
-                            log::trace!(
-                                "Mesh had UVs but no tangents. Calculating the tangents..."
-                            );
-
-                            mikktspace::generate_tangents(&mut TangentCalcContext {
-                                indices: indices.as_slice(),
-                                positions: positions.as_slice(),
-                                normals: normals.as_slice(),
-                                uvs: uvs.as_slice(),
-                                tangents: tangents.as_mut_slice(),
-                            });
-                        }
+/// Appends the triangle primitives of `mesh` to `res`, transformed by `xform`.
+fn append_gltf_mesh(
+    res: &mut TriangleMesh,
+    mesh: &gltf::Mesh,
+    xform: Mat4,
+    buffers: &[bytes::Bytes],
+    document_images: &[ImageSource],
+    load_morph_targets: bool,
+) {
+    let flip_winding_order = xform.determinant() < 0.0;
+
+    // All primitives of a glTF mesh share the same set of morph targets.
+    let morph_target_count = if load_morph_targets {
+        mesh.primitives()
+            .map(|prim| prim.morph_targets().count())
+            .max()
+            .unwrap_or(0)
+    } else {
+        0
+    };
 
-                        // --------------------------------------------------------
-                        // Write it all to the output
+    if morph_target_count > 0 && res.morph_default_weights.is_empty() {
+        res.morph_default_weights = mesh
+            .weights()
+            .filter(|weights| weights.len() == morph_target_count)
+            .map_or_else(|| vec![0.0; morph_target_count], <[f32]>::to_vec);
+    }
 
-                        {
-                            // log::info!("Loading a mesh with {} indices", indices.len());
-                            let base_index = res.positions.len() as u32;
-                            for i in &mut indices {
-                                *i += base_index;
-                            }
+    for prim in mesh.primitives() {
+        match prim.mode() {
+            gltf::mesh::Mode::Triangles
+            | gltf::mesh::Mode::TriangleStrip
+            | gltf::mesh::Mode::TriangleFan => {}
+            mode => {
+                log::warn!(
+                    "Skipping primitive {} of mesh {:?}: mode {:?} is not supported",
+                    prim.index(),
+                    mesh.name().unwrap_or_default(),
+                    mode
+                );
+                continue;
+            }
+        }
 
-                            res.indices.append(&mut indices);
-                            res.colors.append(&mut colors);
-                            res.material_ids.append(&mut material_ids);
-                        }
+        let reader = prim.reader(|buffer| Some(&buffers[buffer.index()]));
 
-                        for v in positions {
-                            let pos = (xform * Vec3::from(v).extend(1.0)).truncate();
-                            res.positions.push(pos.into());
-                        }
+        let res_material_index = res.materials.len() as u32;
 
-                        for v in normals {
-                            let norm = (xform * Vec3::from(v).extend(0.0)).truncate().normalize();
-                            res.normals.push(norm.into());
-                        }
+        {
+            let (mut maps, mut material) = load_gltf_material(&prim.material(), document_images);
 
-                        for v in tangents {
-                            let v = Vec4::from(v);
-                            let t = (xform * v.truncate().extend(0.0)).truncate().normalize();
-                            res.tangents.push(
-                                t.extend(v.w * if flip_winding_order { -1.0 } else { 1.0 })
-                                    .into(),
-                            );
-                        }
+            let map_base = res.maps.len() as u32;
+            for id in material.maps.iter_mut() {
+                *id += map_base;
+            }
 
-                        res.uvs.append(&mut uvs);
-                    }
-                }
-            };
+            res.materials.push(material);
+            res.maps.append(&mut maps);
+        }
 
-            let xform = Mat4::from_scale_rotation_translation(
-                Vec3::splat(self.scale),
-                self.rotation,
-                Vec3::ZERO,
+        // Collect positions (required)
+        let positions = if let Some(iter) = reader.read_positions() {
+            iter.collect::<Vec<_>>()
+        } else {
+            log::warn!(
+                "Skipping primitive {} of mesh {:?}: it has no positions",
+                prim.index(),
+                mesh.name().unwrap_or_default()
             );
-            for node in scene.nodes() {
-                iter_gltf_node_tree(&node, xform, &mut process_node);
-            }
+            continue;
+        };
 
-            Ok(res)
+        // Collect normals (generated below if missing)
+        let normals = reader.read_normals().map(|iter| iter.collect::<Vec<_>>());
+
+        // Collect tangents (optional)
+        let (mut tangents, tangents_found) = if let Some(iter) = reader.read_tangents() {
+            (iter.collect::<Vec<_>>(), true)
         } else {
-            Err(anyhow::anyhow!("No default scene found in gltf"))
+            (vec![[1.0, 0.0, 0.0, 0.0]; positions.len()], false)
+        };
+
+        // Collect uvs (optional)
+        let (mut uvs, uvs_found) = if let Some(iter) = reader.read_tex_coords(0) {
+            (iter.into_f32().collect::<Vec<_>>(), true)
+        } else {
+            (vec![[0.0, 0.0]; positions.len()], false)
+        };
+
+        // Collect the second uv set (optional); see `TriangleMesh::uvs1`.
+        let uvs1 = reader
+            .read_tex_coords(1)
+            .map(|iter| iter.into_f32().collect::<Vec<_>>());
+
+        // Collect colors (optional)
+        let mut colors = if let Some(iter) = reader.read_colors(0) {
+            iter.into_rgba_f32().collect::<Vec<_>>()
+        } else {
+            vec![[1.0, 1.0, 1.0, 1.0]; positions.len()]
+        };
+
+        // Collect joints and weights (optional)
+        let skinning = match (reader.read_joints(0), reader.read_weights(0)) {
+            (Some(joints), Some(weights)) => Some((
+                joints.into_u16().collect::<Vec<_>>(),
+                weights.into_f32().collect::<Vec<_>>(),
+            )),
+            _ => None,
+        }
+        .filter(|(joints, weights)| {
+            joints.len() == positions.len() && weights.len() == positions.len()
+        });
+
+        // Collect morph target offsets (optional); targets missing an attribute leave it as is.
+        let mut morph_position_deltas = vec![[0.0; 3]; positions.len() * morph_target_count];
+        let mut morph_normal_deltas = vec![[0.0; 3]; positions.len() * morph_target_count];
+        if morph_target_count > 0 {
+            for (target, (target_positions, target_normals, _)) in
+                reader.read_morph_targets().enumerate()
+            {
+                for (v, delta) in target_positions
+                    .into_iter()
+                    .flatten()
+                    .take(positions.len())
+                    .enumerate()
+                {
+                    morph_position_deltas[v * morph_target_count + target] = delta;
+                }
+
+                for (v, delta) in target_normals
+                    .into_iter()
+                    .flatten()
+                    .take(positions.len())
+                    .enumerate()
+                {
+                    morph_normal_deltas[v * morph_target_count + target] = delta;
+                }
+            }
+        }
+
+        // Collect material ids
+        let mut material_ids = vec![res_material_index; positions.len()];
+
+        // Collect indices
+        let mut indices: Vec<u32>;
+        {
+            if let Some(indices_reader) = reader.read_indices() {
+                indices = indices_reader.into_u32().collect();
+            } else {
+                if positions.is_empty() {
+                    continue;
+                }
+
+                indices = (0..positions.len() as u32).collect();
+            }
+
+            indices = triangle_list_indices(prim.mode(), indices);
+        }
+
+        let normals = if let Some(normals) = normals {
+            normals
+        } else {
+            log::info!(
+                "Primitive {} of mesh {:?} has no normals. Generating smooth normals...",
+                prim.index(),
+                mesh.name().unwrap_or_default()
+            );
+
+            generate_smooth_normals(&positions, &indices)
+        };
+
+        // Must happen after normal generation, which expects the glTF winding order.
+        if flip_winding_order {
+            for tri in indices.chunks_exact_mut(3) {
+                tri.swap(0, 2);
+            }
+        }
+
+        if !tangents_found && uvs_found {
+            log::trace!("Mesh had UVs but no tangents. Calculating the tangents...");
+
+            mikktspace::generate_tangents(&mut TangentCalcContext {
+                indices: indices.as_slice(),
+                positions: positions.as_slice(),
+                normals: normals.as_slice(),
+                uvs: uvs.as_slice(),
+                tangents: tangents.as_mut_slice(),
+            });
+        }
+
+        // --------------------------------------------------------
+        // Write it all to the output
+
+        {
+            // log::info!("Loading a mesh with {} indices", indices.len());
+            let base_index = res.positions.len() as u32;
+            for i in &mut indices {
+                *i += base_index;
+            }
+
+            res.indices.append(&mut indices);
+            res.colors.append(&mut colors);
+            res.material_ids.append(&mut material_ids);
+        }
+
+        for v in positions {
+            let pos = (xform * Vec3::from(v).extend(1.0)).truncate();
+            res.positions.push(pos.into());
+        }
+
+        for v in normals {
+            let norm = (xform * Vec3::from(v).extend(0.0)).truncate().normalize();
+            res.normals.push(norm.into());
+        }
+
+        for v in tangents {
+            let v = Vec4::from(v);
+            let t = (xform * v.truncate().extend(0.0)).truncate().normalize();
+            res.tangents.push(
+                t.extend(v.w * if flip_winding_order { -1.0 } else { 1.0 })
+                    .into(),
+            );
+        }
+
+        append_uvs1(res, &uvs, uvs1);
+        res.uvs.append(&mut uvs);
+
+        for delta in morph_position_deltas {
+            let delta = (xform * Vec3::from(delta).extend(0.0)).truncate();
+            res.morph_position_deltas.push(delta.into());
+        }
+
+        for delta in morph_normal_deltas {
+            let delta = (xform * Vec3::from(delta).extend(0.0)).truncate();
+            res.morph_normal_deltas.push(delta.into());
+        }
+
+        // Keep joints and weights aligned with the vertices if any primitive is skinned.
+        // Unskinned vertices follow the first joint.
+        if let Some((mut joints, weights)) = skinning {
+            res.joints
+                .resize(res.positions.len() - joints.len(), [0; 4]);
+            res.weights
+                .resize(res.positions.len() - joints.len(), [1.0, 0.0, 0.0, 0.0]);
+
+            res.joints.append(&mut joints);
+            res.weights.extend(weights.into_iter().map(|w| {
+                let sum: f32 = w.iter().sum();
+                if sum > 0.0 {
+                    w.map(|w| w / sum)
+                } else {
+                    [1.0, 0.0, 0.0, 0.0]
+                }
+            }));
+        } else if !res.joints.is_empty() {
+            res.joints.resize(res.positions.len(), [0; 4]);
+            res.weights
+                .resize(res.positions.len(), [1.0, 0.0, 0.0, 0.0]);
+        }
+    }
+}
+
+#[async_trait]
+impl LazyWorker for LoadGltfScene {
+    type Output = anyhow::Result<TriangleMesh>;
+
+    async fn run(self, _ctx: RunContext) -> Self::Output {
+        let (gltf, buffers, imgs) = crate::import_gltf::import(&self.path)
+            .with_context(|| format!("Loading GLTF scene from {:?}", self.path))?;
+
+        if let Some(scene) = gltf.default_scene().or_else(|| gltf.scenes().next()) {
+            let mut res: TriangleMesh = TriangleMesh::default();
+
+            let mut process_node = |node: &gltf::scene::Node, xform: Mat4| {
+                if let Some(mesh) = node.mesh() {
+                    if node.skin().is_some() {
+                        log::warn!(
+                            "Baking skinned node {:?} without its skeleton; bake with `--instanced` to animate it",
+                            node.name().unwrap_or_default()
+                        );
+                    }
+
+                    if mesh
+                        .primitives()
+                        .any(|prim| prim.morph_targets().next().is_some())
+                    {
+                        log::warn!(
+                            "Baking node {:?} without its morph targets; bake with `--instanced` to animate them",
+                            node.name().unwrap_or_default()
+                        );
+                    }
+
+                    // Without a node hierarchy to instance, every copy would have to be
+                    // baked as separate geometry, which easily runs out of memory.
+                    match gltf_node_gpu_instances(node, &gltf, &buffers) {
+                        Ok(Some(instances)) => log::warn!(
+                            "Baking node {:?} once instead of at its {} instances via EXT_mesh_gpu_instancing; bake with `--instanced` to place them all",
+                            node.name().unwrap_or_default(),
+                            instances.len()
+                        ),
+                        Ok(None) => {}
+                        Err(err) => log::warn!(
+                            "Baking node {:?} once, ignoring its EXT_mesh_gpu_instancing: {:#}",
+                            node.name().unwrap_or_default(),
+                            err
+                        ),
+                    }
+
+                    append_gltf_mesh(&mut res, &mesh, xform, &buffers, &imgs, false);
+                }
+            };
+
+            let xform = Mat4::from_scale_rotation_translation(
+                Vec3::splat(self.scale),
+                self.rotation,
+                Vec3::ZERO,
+            );
+            for node in scene.nodes() {
+                iter_gltf_node_tree(&node, xform, &mut process_node);
+            }
+
+            Ok(res)
+        } else {
+            Err(anyhow::anyhow!("No default scene found in gltf"))
+        }
+    }
+}
+
+/// Loads `skin` and the animations of its joints into `mesh`, posed relative to `mesh_node`,
+/// and remaps the joint indices of the vertices to `MeshSkin::joints`.
+fn load_gltf_skin(
+    document: &gltf::Document,
+    buffers: &[bytes::Bytes],
+    skin: &gltf::Skin,
+    mesh_node: &gltf::scene::Node,
+    mesh: &mut TriangleMesh,
+) -> anyhow::Result<()> {
+    use gltf::animation::util::ReadOutputs;
+
+    let mut node_parents: Vec<Option<usize>> = vec![None; document.nodes().len()];
+    for node in document.nodes() {
+        for child in node.children() {
+            node_parents[child.index()] = Some(node.index());
+        }
+    }
+
+    let node_transforms: Vec<Mat4> = document
+        .nodes()
+        .map(|node| Mat4::from_cols_array_2d(&node.transform().matrix()))
+        .collect();
+
+    let joint_nodes: Vec<gltf::scene::Node> = skin.joints().collect();
+    anyhow::ensure!(!joint_nodes.is_empty(), "The skin has no joints");
+
+    let joint_of_node: HashMap<usize, usize> = joint_nodes
+        .iter()
+        .enumerate()
+        .map(|(joint, node)| (node.index(), joint))
+        .collect();
+
+    let mesh_node_to_world = {
+        let mut xform = node_transforms[mesh_node.index()];
+        let mut node = mesh_node.index();
+        while let Some(parent) = node_parents[node] {
+            xform = node_transforms[parent] * xform;
+            node = parent;
+        }
+        xform
+    };
+
+    // The closest ancestor of every joint which is a joint too, and the transform
+    // of the nodes in between. Root joints are offset from the mesh node instead.
+    let joint_parents: Vec<(Option<usize>, Mat4)> = joint_nodes
+        .iter()
+        .map(|node| {
+            let mut offset = Mat4::IDENTITY;
+            let mut ancestor = node_parents[node.index()];
+
+            while let Some(node) = ancestor {
+                if let Some(&joint) = joint_of_node.get(&node) {
+                    return (Some(joint), offset);
+                }
+
+                offset = node_transforms[node] * offset;
+                ancestor = node_parents[node];
+            }
+
+            (None, mesh_node_to_world.inverse() * offset)
+        })
+        .collect();
+
+    // Sort the joints by depth, so that parents precede their children.
+    let joint_depth = |mut joint: usize| {
+        let mut depth = 0;
+        while let Some(parent) = joint_parents[joint].0 {
+            joint = parent;
+            depth += 1;
+        }
+        depth
+    };
+
+    let mut joint_order: Vec<usize> = (0..joint_nodes.len()).collect();
+    joint_order.sort_by_key(|&joint| joint_depth(joint));
+
+    let mut joint_remap = vec![0u32; joint_nodes.len()];
+    for (i, &joint) in joint_order.iter().enumerate() {
+        joint_remap[joint] = i as u32;
+    }
+
+    let inverse_bind_matrices: Vec<[[f32; 4]; 4]> = skin
+        .reader(|buffer| Some(&buffers[buffer.index()][..]))
+        .read_inverse_bind_matrices()
+        .map_or_else(
+            || vec![Mat4::IDENTITY.to_cols_array_2d(); joint_nodes.len()],
+            |iter| iter.collect(),
+        );
+
+    anyhow::ensure!(
+        inverse_bind_matrices.len() == joint_nodes.len(),
+        "The skin has {} joints, but {} inverse bind matrices",
+        joint_nodes.len(),
+        inverse_bind_matrices.len()
+    );
+
+    let mut res = MeshSkin {
+        joints: joint_order
+            .iter()
+            .map(|&joint| {
+                let (parent, parent_offset) = joint_parents[joint];
+                let (translation, rotation, scale) = joint_nodes[joint].transform().decomposed();
+
+                SkinJoint {
+                    parent: parent.map_or(SkinJoint::NO_PARENT, |parent| joint_remap[parent]),
+                    parent_offset: parent_offset.to_cols_array(),
+                    translation,
+                    rotation,
+                    scale,
+                    inverse_bind_matrix: Mat4::from_cols_array_2d(&inverse_bind_matrices[joint])
+                        .to_cols_array(),
+                }
+            })
+            .collect(),
+        ..Default::default()
+    };
+
+    for joints in &mut mesh.joints {
+        for joint in joints {
+            *joint = joint_remap.get(*joint as usize).copied().unwrap_or(0) as u16;
+        }
+    }
+
+    for (clip, animation) in document.animations().enumerate() {
+        res.animation_clip_names.push(
+            animation
+                .name()
+                .map_or_else(|| format!("animation {}", clip), str::to_owned),
+        );
+
+        for channel in animation.channels() {
+            let Some(&joint) = joint_of_node.get(&channel.target().node().index()) else {
+                continue;
+            };
+
+            let reader = channel.reader(|buffer| Some(&buffers[buffer.index()][..]));
+
+            let times: Vec<f32> = reader
+                .read_inputs()
+                .context("Reading animation keyframe times")?
+                .collect();
+
+            let (path, values): (AnimationPath, Vec<[f32; 4]>) = match reader
+                .read_outputs()
+                .context("Reading animation keyframe values")?
+            {
+                ReadOutputs::Translations(iter) => (
+                    AnimationPath::Translation,
+                    iter.map(|[x, y, z]| [x, y, z, 0.0]).collect(),
+                ),
+                ReadOutputs::Rotations(iter) => {
+                    (AnimationPath::Rotation, iter.into_f32().collect())
+                }
+                ReadOutputs::Scales(iter) => (
+                    AnimationPath::Scale,
+                    iter.map(|[x, y, z]| [x, y, z, 0.0]).collect(),
+                ),
+                ReadOutputs::MorphTargetWeights(_) => continue,
+            };
+
+            let interpolation = match channel.sampler().interpolation() {
+                gltf::animation::Interpolation::Step => AnimationInterpolation::Step,
+                gltf::animation::Interpolation::Linear => AnimationInterpolation::Linear,
+                gltf::animation::Interpolation::CubicSpline => AnimationInterpolation::CubicSpline,
+            };
+
+            let values_per_key = if interpolation == AnimationInterpolation::CubicSpline {
+                3
+            } else {
+                1
+            };
+
+            if times.is_empty() || values.len() != times.len() * values_per_key {
+                log::warn!(
+                    "Skipping a channel of animation {:?}: {} keyframe times, but {} values",
+                    res.animation_clip_names[clip],
+                    times.len(),
+                    values.len()
+                );
+                continue;
+            }
+
+            res.animation_channels.push(AnimationChannel {
+                clip: clip as u32,
+                joint: joint_remap[joint],
+                path,
+                interpolation,
+                first_key: res.animation_key_times.len() as u32,
+                key_count: times.len() as u32,
+                first_value: res.animation_key_values.len() as u32,
+            });
+            res.animation_key_times.extend(times);
+            res.animation_key_values.extend(values);
+        }
+    }
+
+    mesh.skin = Some(res);
+
+    Ok(())
+}
+
+/// A node of a glTF scene loaded by `LoadGltfSceneInstanced`.
+#[derive(Clone)]
+pub struct GltfSceneNode {
+    pub name: Option<String>,
+
+    /// Index of the parent in `GltfSceneInstances::nodes`. Parents precede their children.
+    pub parent: Option<usize>,
+
+    /// Relative to the parent. Root nodes also carry the scale and rotation of the load.
+    pub transform: Mat4,
+
+    /// Index into `GltfSceneInstances::meshes`.
+    pub mesh: Option<usize>,
+}
+
+/// A glTF scene with its node hierarchy intact, and every mesh loaded once, in its own space.
+#[derive(Clone, Default)]
+pub struct GltfSceneInstances {
+    pub meshes: Vec<TriangleMesh>,
+    pub nodes: Vec<GltfSceneNode>,
+}
+
+/// Like `LoadGltfScene`, but keeps the nodes separate instead of baking their transforms
+/// into the vertices of one big mesh.
+#[derive(Clone, Hash)]
+pub struct LoadGltfSceneInstanced {
+    pub scene: LoadGltfScene,
+}
+
+#[async_trait]
+impl LazyWorker for LoadGltfSceneInstanced {
+    type Output = anyhow::Result<GltfSceneInstances>;
+
+    async fn run(self, _ctx: RunContext) -> Self::Output {
+        let LoadGltfScene {
+            path,
+            scale,
+            rotation,
+        } = self.scene;
+
+        let (gltf, buffers, imgs) = crate::import_gltf::import(&path)
+            .with_context(|| format!("Loading GLTF scene from {:?}", path))?;
+
+        let scene = gltf
+            .default_scene()
+            .or_else(|| gltf.scenes().next())
+            .ok_or_else(|| anyhow::anyhow!("No default scene found in gltf"))?;
+
+        let mut res = GltfSceneInstances::default();
+
+        // From glTF mesh indices. `None` for meshes without any supported primitives.
+        let mut mesh_indices: HashMap<usize, Option<usize>> = HashMap::new();
+
+        let root_xform =
+            Mat4::from_scale_rotation_translation(Vec3::splat(scale), rotation, Vec3::ZERO);
+
+        // Depth-first, so that parents are pushed before their children.
+        let mut stack: Vec<(gltf::scene::Node, Option<usize>)> =
+            scene.nodes().map(|node| (node, None)).collect();
+        stack.reverse();
+
+        while let Some((node, parent)) = stack.pop() {
+            let mut transform = Mat4::from_cols_array_2d(&node.transform().matrix());
+            if parent.is_none() {
+                transform = root_xform * transform;
+            }
+
+            let mesh = match (node.mesh(), node.skin()) {
+                // Skinned meshes are posed relative to their node, so they aren't shared.
+                (Some(mesh), Some(skin)) => {
+                    let mut triangle_mesh = TriangleMesh::default();
+                    append_gltf_mesh(
+                        &mut triangle_mesh,
+                        &mesh,
+                        Mat4::IDENTITY,
+                        &buffers,
+                        &imgs,
+                        true,
+                    );
+
+                    if triangle_mesh.indices.is_empty() {
+                        None
+                    } else {
+                        load_gltf_skin(&gltf, &buffers, &skin, &node, &mut triangle_mesh)
+                            .with_context(|| {
+                                format!(
+                                    "Loading the skin of node {:?} from {:?}",
+                                    node.name().unwrap_or_default(),
+                                    path
+                                )
+                            })?;
+
+                        res.meshes.push(triangle_mesh);
+                        Some(res.meshes.len() - 1)
+                    }
+                }
+                (Some(mesh), None) => *mesh_indices.entry(mesh.index()).or_insert_with(|| {
+                    let mut triangle_mesh = TriangleMesh::default();
+                    append_gltf_mesh(
+                        &mut triangle_mesh,
+                        &mesh,
+                        Mat4::IDENTITY,
+                        &buffers,
+                        &imgs,
+                        true,
+                    );
+
+                    if triangle_mesh.indices.is_empty() {
+                        return None;
+                    }
+
+                    res.meshes.push(triangle_mesh);
+                    Some(res.meshes.len() - 1)
+                }),
+                (None, _) => None,
+            };
+
+            let gpu_instances = gltf_node_gpu_instances(&node, &gltf, &buffers)
+                .with_context(|| format!("Loading GLTF scene from {:?}", path))?;
+
+            let node_index = res.nodes.len();
+            res.nodes.push(GltfSceneNode {
+                name: node.name().map(str::to_owned),
+                parent,
+                transform,
+                mesh: if gpu_instances.is_some() { None } else { mesh },
+            });
+
+            // `EXT_mesh_gpu_instancing` places the mesh at every instance instead of the node.
+            for instance_transform in gpu_instances.into_iter().flatten() {
+                res.nodes.push(GltfSceneNode {
+                    name: None,
+                    parent: Some(node_index),
+                    transform: instance_transform,
+                    mesh,
+                });
+            }
+
+            let children: Vec<_> = node.children().collect();
+            stack.extend(
+                children
+                    .into_iter()
+                    .rev()
+                    .map(|child| (child, Some(node_index))),
+            );
+        }
+
+        Ok(res)
+    }
+}
+
+/// A camera authored in a glTF scene.
+#[derive(Clone)]
+pub struct GltfCamera {
+    pub name: Option<String>,
+
+    /// In the space of the loaded scene. The camera looks down its -Z.
+    pub transform: Mat4,
+
+    /// In radians. Orthographic cameras have none.
+    pub vertical_fov: Option<f32>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum GltfLightKind {
+    Directional,
+    Point,
+    /// Angles from the axis of the cone, in radians.
+    Spot {
+        inner_cone_angle: f32,
+        outer_cone_angle: f32,
+    },
+}
+
+/// A light from `KHR_lights_punctual`.
+#[derive(Clone)]
+pub struct GltfLight {
+    pub name: Option<String>,
+    pub kind: GltfLightKind,
+
+    /// In the space of the loaded scene. Directional and spot lights shine down their -Z.
+    pub transform: Mat4,
+
+    /// Linear RGB
+    pub color: [f32; 3],
+
+    /// In lux for directional lights, and in candela for the others.
+    pub intensity: f32,
+}
+
+#[derive(Clone, Default)]
+pub struct GltfCamerasAndLights {
+    pub cameras: Vec<GltfCamera>,
+    pub lights: Vec<GltfLight>,
+}
+
+/// Loads the cameras and lights of a glTF scene, placed the same way as the meshes
+/// loaded by `LoadGltfScene`.
+#[derive(Clone, Hash)]
+pub struct LoadGltfCamerasAndLights {
+    pub scene: LoadGltfScene,
+}
+
+#[async_trait]
+impl LazyWorker for LoadGltfCamerasAndLights {
+    type Output = anyhow::Result<GltfCamerasAndLights>;
+
+    async fn run(self, _ctx: RunContext) -> Self::Output {
+        let LoadGltfScene {
+            path,
+            scale,
+            rotation,
+        } = self.scene;
+
+        let document = crate::import_gltf::document(&path)
+            .with_context(|| format!("Loading GLTF scene from {:?}", path))?;
+
+        let mut res = GltfCamerasAndLights::default();
+
+        let Some(scene) = document
+            .default_scene()
+            .or_else(|| document.scenes().next())
+        else {
+            return Ok(res);
+        };
+
+        let mut process_node = |node: &gltf::scene::Node, xform: Mat4| {
+            let name = node.name().map(str::to_owned);
+
+            if let Some(camera) = node.camera() {
+                res.cameras.push(GltfCamera {
+                    name: name.clone().or_else(|| camera.name().map(str::to_owned)),
+                    transform: xform,
+                    vertical_fov: match camera.projection() {
+                        gltf::camera::Projection::Perspective(perspective) => {
+                            Some(perspective.yfov())
+                        }
+                        gltf::camera::Projection::Orthographic(_) => None,
+                    },
+                });
+            }
+
+            if let Some(light) = node.light() {
+                res.lights.push(GltfLight {
+                    name: name.or_else(|| light.name().map(str::to_owned)),
+                    kind: match light.kind() {
+                        gltf::khr_lights_punctual::Kind::Directional => GltfLightKind::Directional,
+                        gltf::khr_lights_punctual::Kind::Point => GltfLightKind::Point,
+                        gltf::khr_lights_punctual::Kind::Spot {
+                            inner_cone_angle,
+                            outer_cone_angle,
+                        } => GltfLightKind::Spot {
+                            inner_cone_angle,
+                            outer_cone_angle,
+                        },
+                    },
+                    transform: xform,
+                    color: light.color(),
+                    intensity: light.intensity(),
+                });
+            }
+        };
+
+        let xform = Mat4::from_scale_rotation_translation(Vec3::splat(scale), rotation, Vec3::ZERO);
+        for node in scene.nodes() {
+            iter_gltf_node_tree(&node, xform, &mut process_node);
+        }
+
+        Ok(res)
+    }
+}
+
+/// Loads a Wavefront OBJ file, and the MTL material libraries it refers to,
+/// into the same kind of mesh as `LoadGltfScene`.
+#[derive(Clone)]
+pub struct LoadObjScene {
+    pub path: PathBuf,
+    pub scale: f32,
+    pub rotation: Quat,
+}
+
+impl Hash for LoadObjScene {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.path.hash(state);
+        self.scale.to_ne_bytes().hash(state);
+        self.rotation.x.to_ne_bytes().hash(state);
+        self.rotation.y.to_ne_bytes().hash(state);
+        self.rotation.z.to_ne_bytes().hash(state);
+        self.rotation.w.to_ne_bytes().hash(state);
+    }
+}
+
+impl LoadObjScene {
+    /// Files which the scene is loaded from. Used for invalidating baked assets.
+    pub fn source_files(&self) -> anyhow::Result<Vec<PathBuf>> {
+        crate::import_obj::file_dependencies(&self.path)
+            .with_context(|| format!("Finding the source files of OBJ scene {:?}", self.path))
+    }
+}
+
+fn load_obj_material(mtl: &MtlMaterial) -> (Vec<MeshMaterialMap>, MeshMaterial) {
+    const DEFAULT_MAP_TRANSFORM: [f32; 6] = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+    let mut map_transforms = [DEFAULT_MAP_TRANSFORM; MESH_MATERIAL_MAP_COUNT];
+    let mut map_samplers = [TexSampler::default().pack(); MESH_MATERIAL_MAP_COUNT];
+
+    let mut load_map = |map_idx: usize, map: &Option<MtlMap>, placeholder, params| {
+        let Some(map) = map else {
+            return MeshMaterialMap::Placeholder(placeholder);
+        };
+
+        // Archives often lack some of the textures; render without them.
+        if !map.path.exists() {
+            log::warn!(
+                "Texture {:?} of material {:?} not found",
+                map.path,
+                mtl.name
+            );
+            return MeshMaterialMap::Placeholder(placeholder);
+        }
+
+        // UVs get flipped on import, so that V points down, as in glTF.
+        // Flip the texture space of the map to match.
+        let [su, sv] = map.scale;
+        let [ou, ov] = map.offset;
+        map_transforms[map_idx] = [su, 0.0, 0.0, sv, ou, 1.0 - ov - sv];
+
+        if map.clamp {
+            map_samplers[map_idx] = TexSampler {
+                wrap: TexWrap::ClampToEdge,
+                ..Default::default()
+            }
+            .pack();
+        }
+
+        MeshMaterialMap::Image {
+            source: ImageSource::File(map.path.clone()),
+            params,
+        }
+    };
+
+    let albedo_map = load_map(
+        0,
+        &mtl.diffuse_map,
+        [255, 255, 255, 255],
+        TexParams {
+            gamma: TexGamma::Srgb,
+            use_mips: true,
+            compression: TexCompressionMode::Rgba,
+            channel_swizzle: None,
+        },
+    );
+
+    let normal_map = load_map(
+        1,
+        &mtl.normal_map,
+        [127, 127, 255, 255],
+        TexParams {
+            gamma: TexGamma::Linear,
+            use_mips: true,
+            compression: TexCompressionMode::Rg,
+            channel_swizzle: None,
+        },
+    );
+
+    let emissive_map = load_map(
+        3,
+        &mtl.emissive_map,
+        [255, 255, 255, 255],
+        TexParams {
+            gamma: TexGamma::Srgb,
+            use_mips: true,
+            compression: TexCompressionMode::Rgba,
+            channel_swizzle: None,
+        },
+    );
+
+    // There's no texel-by-texel conversion of Phong materials, so only the factors are used.
+    for (map, statement) in [
+        (&mtl.specular_map, "map_Ks"),
+        (&mtl.shininess_map, "map_Ns"),
+        (&mtl.roughness_map, "map_Pr"),
+        (&mtl.metalness_map, "map_Pm"),
+        (&mtl.bump_map, "bump"),
+    ] {
+        if map.is_some() {
+            log::warn!(
+                "{} of material {:?} is not supported; ignoring it",
+                statement,
+                mtl.name
+            );
+        }
+    }
+
+    // Without a specular color, a Phong material has no highlights. Otherwise, convert the
+    // Phong exponent to Beckmann roughness, and then to perceptual roughness.
+    let has_specular = mtl
+        .specular
+        .is_some_and(|specular| specular.iter().any(|&c| c > 0.0));
+    let roughness_mult = mtl.roughness.unwrap_or_else(|| {
+        if has_specular {
+            let shininess = mtl.shininess.unwrap_or(0.0).max(0.0);
+            (2.0 / (shininess + 2.0)).sqrt().sqrt()
+        } else {
+            1.0
+        }
+    });
+    let metalness_factor = mtl.metalness.unwrap_or(0.0);
+
+    let mut flags = 0;
+    let mut alpha_cutoff = 0.0;
+    if let Some(dissolve_map) = &mtl.dissolve_map {
+        if mtl
+            .diffuse_map
+            .as_ref()
+            .is_none_or(|diffuse_map| diffuse_map.path != dissolve_map.path)
+        {
+            log::warn!(
+                "Material {:?} has a separate map_d; using the alpha of map_Kd instead",
+                mtl.name
+            );
+        }
+
+        flags |= MeshMaterialFlags::MESH_MATERIAL_FLAG_ALPHA_MASK;
+        alpha_cutoff = 0.5;
+    }
+
+    // A constant dissolve is coverage. As with glTF's blended materials, it's alpha-tested.
+    let dissolve = mtl.dissolve.unwrap_or(1.0).clamp(0.0, 1.0);
+    if dissolve < 1.0 {
+        flags |= MeshMaterialFlags::MESH_MATERIAL_FLAG_ALPHA_MASK;
+        alpha_cutoff = 0.5;
+    }
+
+    // Exporters write `Ni 1` for materials which don't refract at all,
+    // which would also leave them without any Fresnel reflection.
+    let ior = mtl.ior.filter(|&ior| ior > 1.0).unwrap_or(1.5);
+
+    let emissive = mtl.emissive.unwrap_or(if mtl.emissive_map.is_some() {
+        [1.0; 3]
+    } else {
+        [0.0; 3]
+    });
+
+    let diffuse = mtl.diffuse.unwrap_or([1.0; 3]);
+
+    (
+        vec![
+            normal_map,
+            MeshMaterialMap::Placeholder([255, 255, 127, 255]),
+            albedo_map,
+            emissive_map,
+            MeshMaterialMap::Placeholder([255, 255, 255, 255]),
+        ],
+        MeshMaterial {
+            base_color_mult: [diffuse[0], diffuse[1], diffuse[2], dissolve],
+            maps: [0, 1, 2, 3, 4],
+            roughness_mult,
+            metalness_factor,
+            emissive,
+            flags,
+            map_transforms,
+            alpha_cutoff,
+            map_samplers,
+            occlusion_strength: 1.0,
+            map_uv_sets: [0; MESH_MATERIAL_MAP_COUNT],
+            clearcoat_factor: 0.0,
+            clearcoat_roughness: 0.0,
+            transmission_factor: 0.0,
+            ior,
+            sheen_color: [0.0; 3],
+            sheen_roughness: 0.0,
+            anisotropy_strength: 0.0,
+            anisotropy_rotation: 0.0,
+        },
+    )
+}
+
+#[async_trait]
+impl LazyWorker for LoadObjScene {
+    type Output = anyhow::Result<TriangleMesh>;
+
+    async fn run(self, _ctx: RunContext) -> Self::Output {
+        let obj = crate::import_obj::load(&self.path)
+            .with_context(|| format!("Loading OBJ scene from {:?}", self.path))?;
+
+        let mut res = TriangleMesh::default();
+
+        // Faces without a known material get a white one, after those from the libraries.
+        let default_material_index = obj.materials.len() as u32;
+        let default_material = MtlMaterial {
+            name: "default".to_owned(),
+            ..Default::default()
+        };
+
+        for mtl in obj.materials.iter().chain([&default_material]) {
+            let (mut maps, mut material) = load_obj_material(mtl);
+
+            let map_base = res.maps.len() as u32;
+            for id in material.maps.iter_mut() {
+                *id += map_base;
+            }
+
+            res.materials.push(material);
+            res.maps.append(&mut maps);
+        }
+
+        // OBJ faces index positions, UVs and normals separately. Each unique combination
+        // becomes a vertex, and vertices aren't shared between materials.
+        let mut vertex_indices: HashMap<(ObjCorner, u32), u32> = HashMap::new();
+        let mut vertex_corners: Vec<ObjCorner> = Vec::new();
+        let mut position_indices: Vec<u32> = Vec::new();
+
+        for face in &obj.faces {
+            let material = face.material.unwrap_or(default_material_index);
+            let corners = &obj.corners
+                [face.first_corner as usize..(face.first_corner + face.corner_count) as usize];
+
+            let mut vertex = |corner: ObjCorner| {
+                *vertex_indices.entry((corner, material)).or_insert_with(|| {
+                    vertex_corners.push(corner);
+                    res.material_ids.push(material);
+                    vertex_corners.len() as u32 - 1
+                })
+            };
+
+            let first = vertex(corners[0]);
+            for pair in corners[1..].windows(2) {
+                res.indices
+                    .extend([first, vertex(pair[0]), vertex(pair[1])]);
+                position_indices.extend([corners[0].position, pair[0].position, pair[1].position]);
+            }
+        }
+
+        // Smooth normals for the vertices which have none, shared by all faces using a position.
+        let smooth_normals = if vertex_corners.iter().any(|corner| corner.normal.is_none()) {
+            log::info!(
+                "{:?} lacks some normals. Generating smooth normals...",
+                self.path
+            );
+            generate_smooth_normals(&obj.positions, &position_indices)
+        } else {
+            Vec::new()
+        };
+
+        let xform = Mat4::from_scale_rotation_translation(
+            Vec3::splat(self.scale),
+            self.rotation,
+            Vec3::ZERO,
+        );
+
+        for corner in &vertex_corners {
+            let position = Vec3::from(obj.positions[corner.position as usize]);
+            res.positions
+                .push((xform * position.extend(1.0)).truncate().into());
+
+            let normal = Vec3::from(corner.normal.map_or_else(
+                || smooth_normals[corner.position as usize],
+                |idx| obj.normals[idx as usize],
+            ));
+            res.normals.push(
+                (xform * normal.extend(0.0))
+                    .truncate()
+                    .normalize_or(Vec3::Z)
+                    .into(),
+            );
+
+            res.uvs.push(corner.uv.map_or([0.0, 0.0], |idx| {
+                let [u, v] = obj.uvs[idx as usize];
+                [u, 1.0 - v]
+            }));
+
+            res.colors.push(
+                obj.colors
+                    .get(corner.position as usize)
+                    .map_or([1.0; 4], |&[r, g, b]| [r, g, b, 1.0]),
+            );
+        }
+
+        res.tangents = vec![[1.0, 0.0, 0.0, 0.0]; res.positions.len()];
+
+        if !obj.uvs.is_empty() {
+            mikktspace::generate_tangents(&mut TangentCalcContext {
+                indices: res.indices.as_slice(),
+                positions: res.positions.as_slice(),
+                normals: res.normals.as_slice(),
+                uvs: res.uvs.as_slice(),
+                tangents: res.tangents.as_mut_slice(),
+            });
         }
+
+        Ok(res)
     }
 }
 
@@ -563,7 +2291,8 @@ impl FlattenCtx {
         }
     }
 
-    fn finish(mut self, writer: &mut impl std::io::Write) {
+    /// Writes the flattened sections after a `BakedAssetHeader`.
+    fn finish(mut self, asset_type: [u8; 16], writer: &mut impl std::io::Write) {
         self.allocate_section_indices();
 
         type FixupAddr = usize;
@@ -574,38 +2303,35 @@ impl FlattenCtx {
             fixups: Vec<(FixupAddr, SectionIdx)>,
         }
 
-        // Build flattened sections over the nested structures
+        // Build flattened sections over the nested structures, in the depth-first order
+        // which `allocate_section_indices` numbers them in.
         let mut sections: Vec<Section> = Vec::new();
 
-        let mut ctx_list = vec![self];
-        while !ctx_list.is_empty() {
-            let mut next_ctx_list: Vec<Self> = vec![];
-
-            for ctx in ctx_list {
-                sections.push(Section {
-                    bytes: ctx.bytes,
-                    fixups: ctx
-                        .deferred
-                        .iter()
-                        .map(|deferred| (deferred.fixup_addr, deferred.nested.section_idx.unwrap()))
-                        .collect(),
-                });
+        let mut ctx_stack = vec![self];
+        while let Some(ctx) = ctx_stack.pop() {
+            sections.push(Section {
+                bytes: ctx.bytes,
+                fixups: ctx
+                    .deferred
+                    .iter()
+                    .map(|deferred| (deferred.fixup_addr, deferred.nested.section_idx.unwrap()))
+                    .collect(),
+            });
 
-                for deferred in ctx.deferred {
-                    next_ctx_list.push(deferred.nested);
-                }
+            for deferred in ctx.deferred.into_iter().rev() {
+                ctx_stack.push(deferred.nested);
             }
-
-            ctx_list = next_ctx_list;
         }
 
-        // Lay out the sections
+        // Lay out the sections, keeping each one aligned for any of the types stored in it
+        const SECTION_ALIGN: usize = 8;
+
         let mut total_bytes = 0usize;
         let section_base_addr: Vec<usize> = sections
             .iter()
             .map(|s| {
-                let base_addr = total_bytes;
-                total_bytes += s.bytes.len();
+                let base_addr = total_bytes.next_multiple_of(SECTION_ALIGN);
+                total_bytes = base_addr + s.bytes.len();
                 base_addr
             })
             .collect();
@@ -625,9 +2351,326 @@ impl FlattenCtx {
         }
 
         // Write sections out
-        for section in sections {
-            writer.write_all(section.bytes.as_slice()).unwrap();
+        let mut content: Vec<u8> = Vec::with_capacity(total_bytes);
+        for (section, section_addr) in sections.into_iter().zip(section_base_addr) {
+            content.resize(section_addr, 0);
+            content.extend(section.bytes);
+        }
+
+        flatten_plain_field(writer, &BakedAssetHeader::new(asset_type, &content));
+        writer.write_all(content.as_slice()).unwrap();
+    }
+}
+
+/// Identifies baked asset files.
+pub const BAKED_ASSET_MAGIC: [u8; 8] = *b"KAJIYABA";
+
+/// Written natively, so that files baked on a machine of different endianness are rejected.
+const BAKED_ASSET_ENDIANNESS_MARKER: u32 = 0x01020304;
+
+/// Written at the start of every baked asset file, followed by the flattened asset.
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct BakedAssetHeader {
+    pub magic: [u8; 8],
+    pub format_version: u32,
+    pub endianness_marker: u32,
+    /// `FlatAsset::ASSET_TYPE` of the contents.
+    pub asset_type: [u8; 16],
+    pub content_bytes: u64,
+    /// `baked_asset_content_hash` of the contents.
+    pub content_hash: u64,
+}
+
+/// A baked asset which can't be used: truncated, corrupted, of the wrong type, or baked by
+/// a different version of the code. Baking it again fixes it.
+#[derive(Debug)]
+pub struct InvalidBakedAsset {
+    pub reason: String,
+}
+
+impl std::fmt::Display for InvalidBakedAsset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Invalid baked asset ({}); it needs to be baked again",
+            self.reason
+        )
+    }
+}
+
+impl std::error::Error for InvalidBakedAsset {}
+
+fn invalid_baked_asset(reason: impl Into<String>) -> InvalidBakedAsset {
+    InvalidBakedAsset {
+        reason: reason.into(),
+    }
+}
+
+/// FNV-1a over 64-bit words. Meant to catch corruption rather than tampering; unlike
+/// `DefaultHasher`, it doesn't change between toolchains.
+pub fn baked_asset_content_hash(bytes: &[u8]) -> u64 {
+    const PRIME: u64 = 0x100000001b3;
+    let mut hash: u64 = 0xcbf29ce484222325;
+
+    let mut words = bytes.chunks_exact(8);
+    for word in &mut words {
+        hash = (hash ^ u64::from_ne_bytes(word.try_into().unwrap())).wrapping_mul(PRIME);
+    }
+
+    for &byte in words.remainder() {
+        hash = (hash ^ byte as u64).wrapping_mul(PRIME);
+    }
+
+    hash
+}
+
+impl BakedAssetHeader {
+    pub const SIZE: usize = size_of::<Self>();
+
+    fn new(asset_type: [u8; 16], content: &[u8]) -> Self {
+        Self {
+            magic: BAKED_ASSET_MAGIC,
+            format_version: BAKED_ASSET_FORMAT_VERSION,
+            endianness_marker: BAKED_ASSET_ENDIANNESS_MARKER,
+            asset_type,
+            content_bytes: content.len() as u64,
+            content_hash: baked_asset_content_hash(content),
+        }
+    }
+
+    /// Parses the header at the start of `bytes`, and checks that it was written by this
+    /// version of the code, for a file of `file_bytes` in total. The contents are not checked.
+    pub fn read(bytes: &[u8], file_bytes: u64) -> Result<Self, InvalidBakedAsset> {
+        if bytes.len() < Self::SIZE {
+            return Err(invalid_baked_asset("too short for a header"));
+        }
+
+        // The header is not necessarily aligned.
+        let header: Self = unsafe { std::ptr::read_unaligned(bytes.as_ptr() as *const Self) };
+
+        if header.magic != BAKED_ASSET_MAGIC {
+            return Err(invalid_baked_asset("not a baked asset"));
+        }
+
+        if header.endianness_marker != BAKED_ASSET_ENDIANNESS_MARKER {
+            return Err(invalid_baked_asset("baked with a different endianness"));
+        }
+
+        if header.format_version != BAKED_ASSET_FORMAT_VERSION {
+            return Err(invalid_baked_asset(format!(
+                "format version {}, but {} is current",
+                header.format_version, BAKED_ASSET_FORMAT_VERSION
+            )));
+        }
+
+        if Some(file_bytes) != header.content_bytes.checked_add(Self::SIZE as u64) {
+            return Err(invalid_baked_asset(format!(
+                "{} bytes, but the header says {}",
+                file_bytes,
+                header.content_bytes.saturating_add(Self::SIZE as u64)
+            )));
+        }
+
+        Ok(header)
+    }
+
+    /// Reads and checks just the header of the baked asset file at `path`.
+    pub fn read_from_file(path: &Path) -> anyhow::Result<Self> {
+        use std::io::Read as _;
+
+        let mut file = std::fs::File::open(path).with_context(|| format!("Opening {:?}", path))?;
+        let file_bytes = file.metadata()?.len();
+
+        let mut bytes = [0u8; Self::SIZE];
+        let read = file.read(&mut bytes)?;
+
+        Self::read(&bytes[..read], file_bytes).with_context(|| format!("Reading {:?}", path))
+    }
+}
+
+/// Validates a whole baked asset file, and returns its contents. No references into the data
+/// are created before all the offsets, sizes and values in it have been checked.
+pub fn validate_baked_asset<T: FlatAsset>(bytes: &[u8]) -> Result<&T, InvalidBakedAsset> {
+    let header = BakedAssetHeader::read(bytes, bytes.len() as u64)?;
+
+    if header.asset_type != T::ASSET_TYPE {
+        return Err(invalid_baked_asset(format!(
+            "expected a {:?}, found a {:?}",
+            String::from_utf8_lossy(&T::ASSET_TYPE).trim_end_matches('\0'),
+            String::from_utf8_lossy(&header.asset_type).trim_end_matches('\0')
+        )));
+    }
+
+    let content = &bytes[BakedAssetHeader::SIZE..];
+    if baked_asset_content_hash(content) != header.content_hash {
+        return Err(invalid_baked_asset("content hash mismatch"));
+    }
+
+    T::validate_flat(content, 0).map_err(|err| invalid_baked_asset(format!("{:#}", err)))?;
+
+    // `Flat` structs are packed, so any address will do.
+    let asset = unsafe { &*(content.as_ptr() as *const T) };
+    asset
+        .validate_contents()
+        .map_err(|err| invalid_baked_asset(format!("{:#}", err)))?;
+
+    Ok(asset)
+}
+
+/// A type which flattened assets are made of.
+///
+/// # Safety
+/// Any bytes which pass `validate_flat` must form a valid value, and everything it refers to.
+pub unsafe trait FlatValue: Sized {
+    /// Whether any bytes form a valid value, so that `validate_flat` needs no calling.
+    const IS_PLAIN: bool;
+
+    /// Checks the value at `pos` within `bytes`, which the caller has already bounds-checked.
+    fn validate_flat(bytes: &[u8], pos: usize) -> anyhow::Result<()>;
+}
+
+/// The `Flat` type of an asset defined with `def_asset!`.
+pub trait FlatAsset: FlatValue {
+    /// Stored in the header, so that one kind of asset is never read as another.
+    const ASSET_TYPE: [u8; 16];
+
+    /// Checks the indices by which parts of the asset refer to each other,
+    /// once `validate_flat` has vouched for its layout.
+    fn validate_contents(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+const fn asset_type_tag(name: &str) -> [u8; 16] {
+    let name = name.as_bytes();
+    let mut res = [0u8; 16];
+    let mut i = 0;
+    while i < name.len() && i < res.len() {
+        res[i] = name[i];
+        i += 1;
+    }
+    res
+}
+
+fn read_flat_u64(bytes: &[u8], pos: usize) -> anyhow::Result<u64> {
+    let word = pos
+        .checked_add(8)
+        .and_then(|end| bytes.get(pos..end))
+        .context("Out of bounds")?;
+    Ok(u64::from_ne_bytes(word.try_into().unwrap()))
+}
+
+fn check_flat_bounds<T>(bytes: &[u8], pos: usize) -> anyhow::Result<()> {
+    anyhow::ensure!(
+        pos.checked_add(size_of::<T>())
+            .is_some_and(|end| end <= bytes.len()),
+        "Out of bounds"
+    );
+    Ok(())
+}
+
+macro_rules! impl_plain_flat_value {
+    ($($type:ty),+ $(,)?) => {
+        $(
+            unsafe impl FlatValue for $type {
+                const IS_PLAIN: bool = true;
+
+                fn validate_flat(_bytes: &[u8], _pos: usize) -> anyhow::Result<()> {
+                    Ok(())
+                }
+            }
+        )+
+    };
+}
+
+impl_plain_flat_value!(
+    u8,
+    u32,
+    f32,
+    [u16; 4],
+    [u32; 3],
+    [f32; 2],
+    [f32; 3],
+    [f32; 4],
+    kajiya_backend::ash::vk::Format,
+    PackedVertex,
+    MeshMaterial,
+    MeshLod,
+    SkinJoint,
+);
+
+unsafe impl FlatValue for AnimationChannel {
+    const IS_PLAIN: bool = false;
+
+    fn validate_flat(bytes: &[u8], pos: usize) -> anyhow::Result<()> {
+        let read_u32 = |offset: usize| {
+            let start = pos + offset;
+            u32::from_ne_bytes(bytes[start..start + 4].try_into().unwrap())
+        };
+
+        let path = read_u32(std::mem::offset_of!(AnimationChannel, path));
+        let interpolation = read_u32(std::mem::offset_of!(AnimationChannel, interpolation));
+
+        anyhow::ensure!(
+            path <= AnimationPath::Scale as u32,
+            "Invalid animation path {}",
+            path
+        );
+        anyhow::ensure!(
+            interpolation <= AnimationInterpolation::CubicSpline as u32,
+            "Invalid animation interpolation {}",
+            interpolation
+        );
+
+        Ok(())
+    }
+}
+
+unsafe impl<T> FlatValue for AssetRef<T> {
+    const IS_PLAIN: bool = true;
+
+    fn validate_flat(_bytes: &[u8], _pos: usize) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+unsafe impl<T: FlatValue> FlatValue for FlatVec<T> {
+    const IS_PLAIN: bool = false;
+
+    fn validate_flat(bytes: &[u8], pos: usize) -> anyhow::Result<()> {
+        let len = read_flat_u64(bytes, pos)?;
+        let offset = read_flat_u64(bytes, pos + 8)?;
+
+        // The offset is relative to the `offset` field itself.
+        let data_start = (pos as u64 + 8)
+            .checked_add(offset)
+            .context("Vector offset overflow")?;
+        let data_end = len
+            .checked_mul(size_of::<T>() as u64)
+            .and_then(|size| size.checked_add(data_start))
+            .context("Vector size overflow")?;
+
+        anyhow::ensure!(
+            data_start % align_of::<T>() as u64 == 0,
+            "Vector data at {} is misaligned",
+            data_start
+        );
+        anyhow::ensure!(
+            data_end <= bytes.len() as u64,
+            "Vector of {} items at {} runs past the end of the data",
+            len,
+            data_start
+        );
+
+        if !T::IS_PLAIN {
+            for i in 0..len as usize {
+                T::validate_flat(bytes, data_start as usize + i * size_of::<T>())
+                    .with_context(|| format!("Item {}", i))?;
+            }
         }
+
+        Ok(())
     }
 }
 
@@ -704,6 +2747,7 @@ macro_rules! def_asset {
                 $name:ident { $($type:tt)+ }
             )+
         }
+        $(validate_contents: $validate_contents:path)?
     ) => {
         #[allow(non_snake_case)]
         pub mod $struct_name {
@@ -735,9 +2779,39 @@ macro_rules! def_asset {
                         def_asset!(@flatten &mut output; &self.$name; $($type)+ );
                     )*
 
-                    output.finish(writer)
+                    output.finish(<Flat as FlatAsset>::ASSET_TYPE, writer)
+                }
+            }
+
+            unsafe impl FlatValue for Flat {
+                const IS_PLAIN: bool = false;
+
+                fn validate_flat(bytes: &[u8], pos: usize) -> anyhow::Result<()> {
+                    check_flat_bounds::<Self>(bytes, pos)?;
+
+                    $(
+                        anyhow::Context::context(
+                            <def_asset!(@flat_ty $($type)+ ) as FlatValue>::validate_flat(
+                                bytes,
+                                pos + std::mem::offset_of!(Flat, $name),
+                            ),
+                            stringify!($name),
+                        )?;
+                    )*
+
+                    Ok(())
                 }
             }
+
+            impl FlatAsset for Flat {
+                const ASSET_TYPE: [u8; 16] = asset_type_tag(stringify!($struct_name));
+
+                $(
+                    fn validate_contents(&self) -> anyhow::Result<()> {
+                        $validate_contents(self)
+                    }
+                )?
+            }
         }
     };
 }
@@ -783,6 +2857,11 @@ impl<T> Ord for AssetRef<T> {
     }
 }
 
+/// Version of the baked `.mesh` and `.image` layout. Must be bumped whenever
+/// `PackedTriMesh`, `GpuImage`, or any of the types they contain change,
+/// so that stale bakes in the cache are redone.
+pub const BAKED_ASSET_FORMAT_VERSION: u32 = 15;
+
 // TODO: use `rkyv` instead
 def_asset! {
     GpuImage {
@@ -798,12 +2877,139 @@ def_asset! {
     PackedTriMesh {
         verts { Vec(PackedVertex) }
         uvs { Vec([f32; 2]) }
+        uvs1 { Vec([f32; 2]) }
         tangents { Vec([f32; 4]) }
         colors { Vec([f32; 4]) }
         indices { Vec(u32) }
         material_ids { Vec(u32) }
         materials { Vec(MeshMaterial) }
         maps { Vec(Asset(GpuImage)) }
+        joints { Vec([u16; 4]) }
+        weights { Vec([f32; 4]) }
+        skin_joints { Vec(SkinJoint) }
+        animation_clip_names { Vec(Vec(u8)) }
+        animation_channels { Vec(AnimationChannel) }
+        animation_key_times { Vec(f32) }
+        animation_key_values { Vec([f32; 4]) }
+        morph_position_deltas { Vec([f32; 3]) }
+        morph_normal_deltas { Vec([f32; 3]) }
+        morph_default_weights { Vec(f32) }
+        lods { Vec(MeshLod) }
+        lod_indices { Vec(u32) }
+    }
+    validate_contents: validate_packed_tri_mesh
+}
+
+/// Checks everything the renderer indexes with, so that a damaged bake fails to load,
+/// rather than panicking or reading out of bounds on the GPU.
+fn validate_packed_tri_mesh(mesh: &PackedTriMesh::Flat) -> anyhow::Result<()> {
+    let vertex_count = mesh.verts.len();
+    let check_vertex_indices = |name: &str, indices: &[u32]| {
+        if let Some(idx) = indices.iter().find(|&&idx| idx as usize >= vertex_count) {
+            anyhow::bail!(
+                "{} refer to vertex {}, but there are only {}",
+                name,
+                idx,
+                vertex_count
+            );
+        }
+        Ok(())
+    };
+
+    check_vertex_indices("Indices", mesh.indices.as_slice())?;
+    check_vertex_indices("LOD indices", mesh.lod_indices.as_slice())?;
+
+    for (i, lod) in mesh.lods.as_slice().iter().enumerate() {
+        anyhow::ensure!(
+            lod.first_index as u64 + lod.index_count as u64 <= mesh.lod_indices.len() as u64,
+            "LOD {} runs past the end of the LOD indices",
+            i + 1
+        );
+    }
+
+    let material_count = mesh.materials.len();
+    if let Some(id) = mesh
+        .material_ids
+        .as_slice()
+        .iter()
+        .find(|&&id| id as usize >= material_count)
+    {
+        anyhow::bail!(
+            "Material {} is used, but there are only {}",
+            id,
+            material_count
+        );
+    }
+
+    for (i, material) in mesh.materials.as_slice().iter().enumerate() {
+        anyhow::ensure!(
+            material
+                .maps
+                .iter()
+                .all(|&map| (map as usize) < mesh.maps.len()),
+            "Material {} refers to a missing map",
+            i
+        );
+    }
+
+    for (i, joint) in mesh.skin_joints.as_slice().iter().enumerate() {
+        anyhow::ensure!(
+            joint.parent == SkinJoint::NO_PARENT || (joint.parent as usize) < i,
+            "Joint {} has parent {}, which doesn't precede it",
+            i,
+            joint.parent
+        );
+    }
+
+    for (i, channel) in mesh.animation_channels.as_slice().iter().enumerate() {
+        let values_per_key = if channel.interpolation == AnimationInterpolation::CubicSpline {
+            3
+        } else {
+            1
+        };
+
+        anyhow::ensure!(
+            (channel.clip as usize) < mesh.animation_clip_names.len()
+                && (channel.joint as usize) < mesh.skin_joints.len(),
+            "Animation channel {} refers to a missing clip or joint",
+            i
+        );
+        anyhow::ensure!(
+            channel.key_count > 0
+                && channel.first_key as u64 + channel.key_count as u64
+                    <= mesh.animation_key_times.len() as u64
+                && channel.first_value as u64 + channel.key_count as u64 * values_per_key
+                    <= mesh.animation_key_values.len() as u64,
+            "Animation channel {} runs past the end of its keys",
+            i
+        );
+    }
+
+    Ok(())
+}
+
+impl PackedTriMesh::Flat {
+    /// Level 0 is the full-resolution mesh, followed by each of the `lods`.
+    pub fn lod_count(&self) -> usize {
+        1 + self.lods.len()
+    }
+
+    pub fn lod_indices(&self, lod: usize) -> &[u32] {
+        if lod == 0 {
+            self.indices.as_slice()
+        } else {
+            let lod = self.lods[lod - 1];
+            &self.lod_indices.as_slice()[lod.first_index as usize..][..lod.index_count as usize]
+        }
+    }
+
+    /// See `MeshLod::error`
+    pub fn lod_error(&self, lod: usize) -> f32 {
+        if lod == 0 {
+            0.0
+        } else {
+            self.lods[lod - 1].error
+        }
     }
 }
 
@@ -851,22 +3057,144 @@ pub fn pack_triangle_mesh(mesh: &TriangleMesh) -> PackedTriangleMesh {
                         channel_swizzle: None,
                     },
                 ),
+                MeshMaterialMap::SpecGloss {
+                    diffuse,
+                    specular_glossiness,
+                    factors,
+                    output,
+                    params,
+                } => {
+                    let load = |source: &Option<ImageSource>| {
+                        source
+                            .as_ref()
+                            .map(|source| super::image::LoadImage::new(source).unwrap().into_lazy())
+                    };
+
+                    (
+                        super::image::ConvertSpecGlossImage {
+                            diffuse: load(diffuse),
+                            specular_glossiness: load(specular_glossiness),
+                            factors: *factors,
+                            output: *output,
+                        }
+                        .into_lazy(),
+                        *params,
+                    )
+                }
             };
 
             crate::image::CreateGpuImage { image, params }.into_lazy()
         })
         .collect();
 
+    // Joints and weights are of no use without the skeleton.
+    let skin = mesh.skin.clone().unwrap_or_default();
+    let (joints, weights) = if mesh.skin.is_some() {
+        (mesh.joints.clone(), mesh.weights.clone())
+    } else {
+        Default::default()
+    };
+
+    let (lods, lod_indices) = build_mesh_lods(&mesh.positions, &mesh.indices);
+
     PackedTriangleMesh {
         verts,
         uvs: mesh.uvs.clone(),
+        uvs1: mesh.uvs1.clone(),
         tangents: mesh.tangents.clone(),
         colors: mesh.colors.clone(),
         indices: mesh.indices.clone(),
         material_ids: mesh.material_ids.clone(),
         materials: mesh.materials.clone(),
         maps,
+        joints,
+        weights,
+        skin_joints: skin.joints,
+        animation_clip_names: skin
+            .animation_clip_names
+            .into_iter()
+            .map(String::into_bytes)
+            .collect(),
+        animation_channels: skin.animation_channels,
+        animation_key_times: skin.animation_key_times,
+        animation_key_values: skin.animation_key_values,
+        morph_position_deltas: mesh.morph_position_deltas.clone(),
+        morph_normal_deltas: mesh.morph_normal_deltas.clone(),
+        morph_default_weights: mesh.morph_default_weights.clone(),
+        lods,
+        lod_indices,
+    }
+}
+
+/// Upper bound on the number of simplified versions generated for each mesh.
+const MAX_MESH_LODS: usize = 7;
+
+/// Levels of detail with fewer triangles than this aren't simplified any further.
+const MIN_MESH_LOD_TRIANGLES: usize = 64;
+
+/// How far each simplification step may stray from its source, relative to the mesh extent.
+const MAX_MESH_LOD_STEP_ERROR: f32 = 0.05;
+
+/// Builds a chain of simplified index buffers, each with about half the triangles of the
+/// previous one. Stops early once the simplifier can't make enough progress.
+fn build_mesh_lods(positions: &[[f32; 3]], indices: &[u32]) -> (Vec<MeshLod>, Vec<u32>) {
+    let mut lods = Vec::new();
+    let mut lod_indices = Vec::new();
+
+    if positions.is_empty() {
+        return (lods, lod_indices);
+    }
+
+    let vertices = meshopt::VertexDataAdapter::new(
+        meshopt::typed_to_bytes(positions),
+        std::mem::size_of::<[f32; 3]>(),
+        0,
+    )
+    .expect("VertexDataAdapter");
+
+    // The simplifier reports errors relative to the extent of the mesh.
+    let (min, max) = positions.iter().fold(
+        (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+        |(min, max), pos| (min.min(Vec3::from(*pos)), max.max(Vec3::from(*pos))),
+    );
+    let extent = (max - min).max_element();
+
+    let mut source = indices.to_vec();
+    let mut error = 0.0;
+
+    while lods.len() < MAX_MESH_LODS && source.len() / 3 > MIN_MESH_LOD_TRIANGLES {
+        let target_index_count = source.len() / 6 * 3;
+
+        let mut step_error = 0.0f32;
+        let simplified = meshopt::simplify(
+            &source,
+            &vertices,
+            target_index_count,
+            MAX_MESH_LOD_STEP_ERROR,
+            // Vertices are split along material and UV seams; keep those closed.
+            meshopt::SimplifyOptions::LockBorder,
+            Some(&mut step_error),
+        );
+
+        // Not worth the memory if a quarter of the triangles can't be removed.
+        if simplified.is_empty() || simplified.len() > source.len() * 3 / 4 {
+            break;
+        }
+
+        // Simplifying each level from the previous one accumulates the errors.
+        error += step_error * extent;
+
+        lods.push(MeshLod {
+            first_index: lod_indices.len() as u32,
+            index_count: simplified.len() as u32,
+            error,
+        });
+        lod_indices.extend_from_slice(&simplified);
+
+        source = simplified;
     }
+
+    (lods, lod_indices)
 }
 
 #[derive(Copy, Clone)]
@@ -918,3 +3246,311 @@ impl<'a> mikktspace::Geometry for TangentCalcContext<'a> {
         self.tangents[self.indices[face * 3 + vert] as usize] = tangent;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn baked_image() -> Vec<u8> {
+        let mut bytes = Vec::new();
+        GpuImage::Proto {
+            format: kajiya_backend::ash::vk::Format::R8G8B8A8_UNORM,
+            extent: [1, 1, 1],
+            mips: vec![vec![1, 2, 3, 4]],
+        }
+        .flatten_into(&mut bytes);
+        bytes
+    }
+
+    fn write_u64(bytes: &mut [u8], pos: usize, value: u64) {
+        bytes[pos..pos + 8].copy_from_slice(&value.to_ne_bytes());
+    }
+
+    /// Updates the header after the contents have been tampered with.
+    fn rehash(bytes: &mut [u8]) {
+        let hash = baked_asset_content_hash(&bytes[BakedAssetHeader::SIZE..]);
+        write_u64(
+            bytes,
+            std::mem::offset_of!(BakedAssetHeader, content_hash),
+            hash,
+        );
+    }
+
+    fn invalid_reason(bytes: &[u8]) -> String {
+        match validate_baked_asset::<GpuImage::Flat>(bytes) {
+            Ok(_) => panic!("a damaged asset passed validation"),
+            Err(err) => err.reason,
+        }
+    }
+
+    #[test]
+    fn validate_accepts_a_fresh_bake() {
+        let bytes = baked_image();
+        let image = validate_baked_asset::<GpuImage::Flat>(&bytes).unwrap();
+
+        assert_eq!({ image.extent }, [1, 1, 1]);
+        assert_eq!(image.mips.len(), 1);
+        assert_eq!(image.mips[0].as_slice(), [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn validate_rejects_truncated_files() {
+        let bytes = baked_image();
+
+        invalid_reason(&bytes[..BakedAssetHeader::SIZE - 1]);
+        invalid_reason(&bytes[..bytes.len() - 1]);
+
+        let mut extended = bytes.clone();
+        extended.push(0);
+        invalid_reason(&extended);
+    }
+
+    #[test]
+    fn validate_rejects_foreign_headers() {
+        let mut bytes = baked_image();
+        bytes[0] ^= 1;
+        assert_eq!(invalid_reason(&bytes), "not a baked asset");
+
+        let mut bytes = baked_image();
+        let version_pos = std::mem::offset_of!(BakedAssetHeader, format_version);
+        bytes[version_pos..version_pos + 4]
+            .copy_from_slice(&(BAKED_ASSET_FORMAT_VERSION - 1).to_ne_bytes());
+        assert!(invalid_reason(&bytes).starts_with("format version"));
+
+        let bytes = baked_image();
+        assert!(validate_baked_asset::<PackedTriMesh::Flat>(&bytes).is_err());
+    }
+
+    #[test]
+    fn validate_rejects_corrupted_contents() {
+        let mut bytes = baked_image();
+        *bytes.last_mut().unwrap() ^= 1;
+        assert_eq!(invalid_reason(&bytes), "content hash mismatch");
+    }
+
+    #[test]
+    fn validate_rejects_vectors_out_of_range() {
+        let mips_pos = BakedAssetHeader::SIZE + std::mem::offset_of!(GpuImage::Flat, mips);
+
+        // Length
+        let mut bytes = baked_image();
+        write_u64(&mut bytes, mips_pos, 1 << 40);
+        rehash(&mut bytes);
+        invalid_reason(&bytes);
+
+        let mut bytes = baked_image();
+        write_u64(&mut bytes, mips_pos, u64::MAX);
+        rehash(&mut bytes);
+        invalid_reason(&bytes);
+
+        // Offset
+        let mut bytes = baked_image();
+        let past_the_end = bytes.len() as u64;
+        write_u64(&mut bytes, mips_pos + 8, past_the_end);
+        rehash(&mut bytes);
+        invalid_reason(&bytes);
+
+        let mut bytes = baked_image();
+        write_u64(&mut bytes, mips_pos + 8, u64::MAX - 4);
+        rehash(&mut bytes);
+        invalid_reason(&bytes);
+    }
+
+    /// A single triangle without materials, with a joint and a clip animating it.
+    fn packed_triangle() -> PackedTriangleMesh {
+        let vertex = PackedVertex {
+            pos: [0.0; 3],
+            normal: 0,
+        };
+        let joint = SkinJoint {
+            parent: SkinJoint::NO_PARENT,
+            parent_offset: Mat4::IDENTITY.to_cols_array(),
+            translation: [0.0; 3],
+            rotation: Quat::IDENTITY.to_array(),
+            scale: [1.0; 3],
+            inverse_bind_matrix: Mat4::IDENTITY.to_cols_array(),
+        };
+
+        PackedTriMesh::Proto {
+            verts: vec![vertex; 3],
+            uvs: Vec::new(),
+            uvs1: Vec::new(),
+            tangents: Vec::new(),
+            colors: Vec::new(),
+            indices: vec![0, 1, 2],
+            material_ids: Vec::new(),
+            materials: Vec::new(),
+            maps: Vec::new(),
+            joints: Vec::new(),
+            weights: Vec::new(),
+            skin_joints: vec![joint],
+            animation_clip_names: vec![b"clip".to_vec()],
+            animation_channels: vec![AnimationChannel {
+                clip: 0,
+                joint: 0,
+                path: AnimationPath::Translation,
+                interpolation: AnimationInterpolation::CubicSpline,
+                first_key: 0,
+                key_count: 1,
+                first_value: 0,
+            }],
+            animation_key_times: vec![0.0],
+            animation_key_values: vec![[0.0; 4]; 3],
+            morph_position_deltas: Vec::new(),
+            morph_normal_deltas: Vec::new(),
+            morph_default_weights: Vec::new(),
+            lods: vec![MeshLod {
+                first_index: 0,
+                index_count: 3,
+                error: 0.0,
+            }],
+            lod_indices: vec![0, 1, 2],
+        }
+    }
+
+    fn invalid_mesh_reason(mesh: PackedTriangleMesh) -> String {
+        let mut bytes = Vec::new();
+        mesh.flatten_into(&mut bytes);
+        match validate_baked_asset::<PackedTriMesh::Flat>(&bytes) {
+            Ok(_) => panic!("a damaged mesh passed validation"),
+            Err(err) => err.reason,
+        }
+    }
+
+    #[test]
+    fn validate_rejects_mesh_indices_out_of_range() {
+        let mut bytes = Vec::new();
+        packed_triangle().flatten_into(&mut bytes);
+        let mesh = validate_baked_asset::<PackedTriMesh::Flat>(&bytes).unwrap();
+        assert_eq!(mesh.lod_indices(1), [0, 1, 2]);
+
+        let mut mesh = packed_triangle();
+        mesh.indices[2] = 3;
+        assert!(invalid_mesh_reason(mesh).starts_with("Indices refer to vertex 3"));
+
+        let mut mesh = packed_triangle();
+        mesh.lod_indices[0] = 3;
+        invalid_mesh_reason(mesh);
+
+        let mut mesh = packed_triangle();
+        mesh.lods[0].first_index = 1;
+        invalid_mesh_reason(mesh);
+
+        let mut mesh = packed_triangle();
+        mesh.lods[0].index_count = u32::MAX;
+        invalid_mesh_reason(mesh);
+
+        let mut mesh = packed_triangle();
+        mesh.material_ids = vec![0; 3];
+        invalid_mesh_reason(mesh);
+    }
+
+    #[test]
+    fn validate_rejects_skins_out_of_range() {
+        let mut mesh = packed_triangle();
+        mesh.skin_joints[0].parent = 0;
+        invalid_mesh_reason(mesh);
+
+        let mut mesh = packed_triangle();
+        mesh.animation_channels[0].joint = 1;
+        invalid_mesh_reason(mesh);
+
+        let mut mesh = packed_triangle();
+        mesh.animation_channels[0].clip = 1;
+        invalid_mesh_reason(mesh);
+
+        let mut mesh = packed_triangle();
+        mesh.animation_channels[0].key_count = 0;
+        invalid_mesh_reason(mesh);
+
+        let mut mesh = packed_triangle();
+        mesh.animation_channels[0].first_key = 1;
+        invalid_mesh_reason(mesh);
+
+        // Cubic splines have three values per key.
+        let mut mesh = packed_triangle();
+        mesh.animation_channels[0].first_value = 1;
+        invalid_mesh_reason(mesh);
+    }
+
+    /// A bumpy square of `size` by `size` quads.
+    fn grid_mesh(size: u32) -> (Vec<[f32; 3]>, Vec<u32>) {
+        let mut positions = Vec::new();
+        for y in 0..=size {
+            for x in 0..=size {
+                let (fx, fy) = (x as f32, y as f32);
+                positions.push([fx, fy, (fx * 0.7).sin() * (fy * 0.3).cos()]);
+            }
+        }
+
+        let mut indices = Vec::new();
+        for y in 0..size {
+            for x in 0..size {
+                let i = y * (size + 1) + x;
+                indices.extend_from_slice(&[i, i + 1, i + size + 2, i, i + size + 2, i + size + 1]);
+            }
+        }
+
+        (positions, indices)
+    }
+
+    #[test]
+    fn build_mesh_lods_simplifies_progressively() {
+        let (positions, indices) = grid_mesh(32);
+        let (lods, lod_indices) = build_mesh_lods(&positions, &indices);
+
+        assert!(!lods.is_empty());
+        assert!(lods.len() <= MAX_MESH_LODS);
+
+        let mut prev_index_count = indices.len() as u32;
+        let mut prev_error = 0.0;
+        let mut next_first_index = 0;
+
+        for lod in &lods {
+            assert_eq!(lod.first_index, next_first_index);
+            assert_eq!(lod.index_count % 3, 0);
+            assert!(lod.index_count < prev_index_count);
+            assert!(lod.error >= prev_error);
+
+            let lod_range = lod.first_index as usize..(lod.first_index + lod.index_count) as usize;
+            assert!(
+                lod_indices[lod_range]
+                    .iter()
+                    .all(|&idx| (idx as usize) < positions.len())
+            );
+
+            prev_index_count = lod.index_count;
+            prev_error = lod.error;
+            next_first_index += lod.index_count;
+        }
+
+        assert_eq!(lod_indices.len(), next_first_index as usize);
+    }
+
+    #[test]
+    fn build_mesh_lods_skips_small_meshes() {
+        let (positions, indices) = grid_mesh(4);
+        let (lods, lod_indices) = build_mesh_lods(&positions, &indices);
+        assert!(lods.is_empty());
+        assert!(lod_indices.is_empty());
+
+        let (lods, _) = build_mesh_lods(&[], &[]);
+        assert!(lods.is_empty());
+    }
+
+    #[test]
+    fn append_uvs1_only_pads_when_a_primitive_has_a_second_set() {
+        fn append(mesh: &mut TriangleMesh, uvs: Vec<[f32; 2]>, uvs1: Option<Vec<[f32; 2]>>) {
+            append_uvs1(mesh, &uvs, uvs1);
+            mesh.uvs.extend(uvs);
+        }
+
+        let mut mesh = TriangleMesh::default();
+        append(&mut mesh, vec![[0.0, 0.0]], None);
+        assert!(mesh.uvs1.is_empty());
+
+        append(&mut mesh, vec![[1.0, 1.0]], Some(vec![[2.0, 2.0]]));
+        append(&mut mesh, vec![[3.0, 3.0]], None);
+        assert_eq!(mesh.uvs1, [[0.0, 0.0], [2.0, 2.0], [3.0, 3.0]]);
+    }
+}
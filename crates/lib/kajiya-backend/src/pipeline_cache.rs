@@ -53,7 +53,8 @@ impl LazyWorker for CompilePipelineShaders {
                     ShaderPipelineStage::Pixel => "ps".to_owned(),
                     ShaderPipelineStage::RayGen
                     | ShaderPipelineStage::RayMiss
-                    | ShaderPipelineStage::RayClosestHit => "lib".to_owned(),
+                    | ShaderPipelineStage::RayClosestHit
+                    | ShaderPipelineStage::RayAnyHit => "lib".to_owned(),
                 },
             }
             .into_lazy()
@@ -96,7 +96,8 @@ impl LazyWorker for CompileRayTracingShader {
         match ext.as_str() {
             "glsl" => unimplemented!(),
             "hlsl" => {
-                let target_profile = "lib_6_4";
+                // 6.5 for `GeometryIndex()`
+                let target_profile = "lib_6_5";
                 let spirv = compile_generic_shader_hlsl_impl(&name, &source, target_profile)?;
 
                 Ok(RayTracingShader { name, spirv })
@@ -352,6 +352,9 @@ impl Device {
 
                 assert_ne!(shader_float16_int8.shader_int8, 0);
 
+                // Used by the allocator and bindless buffers; core since Vulkan 1.2
+                assert_ne!(get_buffer_device_address_features.buffer_device_address, 0);
+
                 if ray_tracing_enabled {
                     assert_ne!(descriptor_indexing.shader_uniform_buffer_array_non_uniform_indexing, 0);
                     assert_ne!(descriptor_indexing.shader_storage_buffer_array_non_uniform_indexing, 0);
@@ -364,7 +367,6 @@ impl Device {
                     assert_ne!(ray_tracing_pipeline_features.ray_tracing_pipeline, 0);
                     assert_ne!(ray_tracing_pipeline_features.ray_tracing_pipeline_trace_rays_indirect, 0);
 
-                    assert_ne!(get_buffer_device_address_features.buffer_device_address, 0);
                 }
             }
 
@@ -466,6 +468,7 @@ impl Device {
         ];
         let address_modes = [
             vk::SamplerAddressMode::REPEAT,
+            vk::SamplerAddressMode::MIRRORED_REPEAT,
             vk::SamplerAddressMode::CLAMP_TO_EDGE,
         ];
 
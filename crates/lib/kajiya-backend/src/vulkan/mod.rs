@@ -32,8 +32,12 @@ fn select_surface_format(formats: Vec<vk::SurfaceFormatKHR>) -> Option<vk::Surfa
 
 pub struct RenderBackend {
     pub device: Arc<device::Device>,
-    pub surface: Arc<surface::Surface>,
-    pub swapchain: swapchain::Swapchain,
+
+    /// `None` for headless backends created with `RenderBackend::new_headless`.
+    pub surface: Option<Arc<surface::Surface>>,
+
+    /// `None` for headless backends created with `RenderBackend::new_headless`.
+    pub swapchain: Option<swapchain::Swapchain>,
 }
 
 #[derive(Clone, Copy)]
@@ -44,6 +48,53 @@ pub struct RenderBackendConfig {
     pub device_index: Option<usize>,
 }
 
+#[derive(Clone, Copy, Default)]
+pub struct HeadlessRenderBackendConfig {
+    pub graphics_debugging: bool,
+    pub device_index: Option<usize>,
+}
+
+fn select_physical_device(
+    physical_devices: Vec<physical_device::PhysicalDevice>,
+    device_index: Option<usize>,
+) -> anyhow::Result<Arc<physical_device::PhysicalDevice>> {
+    info!(
+        "Available physical devices: {:#?}",
+        physical_devices
+            .iter()
+            .map(|dev| unsafe {
+                ::std::ffi::CStr::from_ptr(
+                    dev.properties.device_name.as_ptr() as *const std::os::raw::c_char
+                )
+            })
+            .collect::<Vec<_>>()
+    );
+
+    let physical_device = if let Some(device_index) = device_index {
+        physical_devices.into_iter().nth(device_index)
+    } else {
+        physical_devices
+            .into_iter()
+            // If there are multiple devices with the same score, `max_by_key` would choose the last,
+            // and we want to preserve the order of devices from `enumerate_physical_devices`.
+            .rev()
+            .max_by_key(|device| match device.properties.device_type {
+                vk::PhysicalDeviceType::INTEGRATED_GPU => 200,
+                vk::PhysicalDeviceType::DISCRETE_GPU => 1000,
+                vk::PhysicalDeviceType::VIRTUAL_GPU => 1,
+                _ => 0,
+            })
+    };
+
+    let physical_device = Arc::new(
+        physical_device.ok_or_else(|| anyhow::anyhow!("No suitable physical device found"))?,
+    );
+
+    info!("Selected physical device: {:#?}", *physical_device);
+
+    Ok(physical_device)
+}
+
 impl RenderBackend {
     pub fn new<T>(window: &T, config: RenderBackendConfig) -> anyhow::Result<Self>
     where
@@ -65,36 +116,7 @@ impl RenderBackend {
         let physical_devices =
             enumerate_physical_devices(&instance)?.with_presentation_support(&surface);
 
-        info!(
-            "Available physical devices: {:#?}",
-            physical_devices
-                .iter()
-                .map(|dev| unsafe {
-                    ::std::ffi::CStr::from_ptr(
-                        dev.properties.device_name.as_ptr() as *const std::os::raw::c_char
-                    )
-                })
-                .collect::<Vec<_>>()
-        );
-
-        let physical_device = Arc::new(if let Some(device_index) = config.device_index {
-            physical_devices.into_iter().nth(device_index).unwrap()
-        } else {
-            physical_devices
-                .into_iter()
-                // If there are multiple devices with the same score, `max_by_key` would choose the last,
-                // and we want to preserve the order of devices from `enumerate_physical_devices`.
-                .rev()
-                .max_by_key(|device| match device.properties.device_type {
-                    vk::PhysicalDeviceType::INTEGRATED_GPU => 200,
-                    vk::PhysicalDeviceType::DISCRETE_GPU => 1000,
-                    vk::PhysicalDeviceType::VIRTUAL_GPU => 1,
-                    _ => 0,
-                })
-                .unwrap()
-        });
-
-        info!("Selected physical device: {:#?}", *physical_device);
+        let physical_device = select_physical_device(physical_devices, config.device_index)?;
 
         let device = device::Device::create(&physical_device)?;
         let surface_formats = swapchain::Swapchain::enumerate_surface_formats(&device, &surface)?;
@@ -116,8 +138,33 @@ impl RenderBackend {
 
         Ok(Self {
             device,
-            surface,
-            swapchain,
+            surface: Some(surface),
+            swapchain: Some(swapchain),
+        })
+    }
+
+    /// Creates a backend without a window, surface or swapchain. Frames are rendered
+    /// into caller-owned images via `Renderer::draw_frame_headless`.
+    ///
+    /// As with windowed backends, the ray tracing extensions are only used if the device
+    /// supports them; without them, rendering falls back to the raster-only path. This
+    /// makes software implementations such as lavapipe usable, e.g. on CI. They still need
+    /// Vulkan 1.2 with descriptor indexing, scalar block layout, imageless framebuffers,
+    /// 8-bit shader integers, and buffer device addresses.
+    pub fn new_headless(config: HeadlessRenderBackendConfig) -> anyhow::Result<Self> {
+        let instance = instance::Instance::builder()
+            .graphics_debugging(config.graphics_debugging)
+            .build()?;
+
+        let physical_devices = physical_device::enumerate_physical_devices(&instance)?;
+        let physical_device = select_physical_device(physical_devices, config.device_index)?;
+
+        let device = device::Device::create(&physical_device)?;
+
+        Ok(Self {
+            device,
+            surface: None,
+            swapchain: None,
         })
     }
 
@@ -36,6 +36,9 @@ pub struct RayTracingGeometryDesc {
     pub vertex_format: vk::Format,
     pub vertex_stride: usize,
     pub parts: Vec<RayTracingGeometryPart>,
+
+    /// If `false`, any-hit shaders will be invoked for the geometry.
+    pub opaque: bool,
 }
 
 #[derive(Clone)]
@@ -43,6 +46,12 @@ pub struct RayTracingInstanceDesc {
     pub blas: Arc<RayTracingAcceleration>,
     pub transformation: Affine3A,
     pub mesh_index: u32,
+
+    /// Rays only hit the instance if this shares a bit with their instance inclusion mask.
+    pub mask: u8,
+
+    /// Makes back faces hittable even by rays with `RAY_FLAG_CULL_BACK_FACING_TRIANGLES`.
+    pub cull_disable: bool,
 }
 
 #[derive(Clone)]
@@ -54,6 +63,9 @@ pub struct RayTracingTopAccelerationDesc {
 #[derive(Clone, Debug)]
 pub struct RayTracingBottomAccelerationDesc {
     pub geometries: Vec<RayTracingGeometryDesc>,
+
+    /// Allows `refit_ray_tracing_bottom_acceleration` after the vertices move.
+    pub allow_update: bool,
 }
 
 #[derive(Clone, Debug)]
@@ -66,6 +78,9 @@ pub struct RayTracingShaderTableDesc {
 pub struct RayTracingAcceleration {
     pub raw: vk::AccelerationStructureKHR,
     backing_buffer: super::buffer::Buffer,
+
+    /// Scratch memory for refits; only allocated if updates are allowed.
+    update_scratch_buffer: Option<super::buffer::Buffer>,
 }
 
 #[derive(Clone)]
@@ -99,49 +114,12 @@ impl Device {
     ) -> Result<RayTracingAcceleration, BackendError> {
         //log::trace!("Creating ray tracing bottom acceleration: {:?}", desc);
 
-        let geometries: Result<Vec<ash::vk::AccelerationStructureGeometryKHR>, BackendError> = desc
-            .geometries
-            .iter()
-            .map(
-                |desc| -> Result<ash::vk::AccelerationStructureGeometryKHR, BackendError> {
-                    let part: RayTracingGeometryPart = desc.parts[0];
-
-                    let geometry = ash::vk::AccelerationStructureGeometryKHR::default()
-                        .geometry_type(ash::vk::GeometryTypeKHR::TRIANGLES)
-                        .geometry(ash::vk::AccelerationStructureGeometryDataKHR {
-                            triangles:
-                                ash::vk::AccelerationStructureGeometryTrianglesDataKHR::default()
-                                    .vertex_data(ash::vk::DeviceOrHostAddressConstKHR {
-                                        device_address: desc.vertex_buffer,
-                                    })
-                                    .vertex_stride(desc.vertex_stride as _)
-                                    .max_vertex(part.max_vertex)
-                                    .vertex_format(desc.vertex_format)
-                                    .index_data(ash::vk::DeviceOrHostAddressConstKHR {
-                                        device_address: desc.index_buffer,
-                                    })
-                                    .index_type(ash::vk::IndexType::UINT32), // TODO
-                        })
-                        .flags(ash::vk::GeometryFlagsKHR::OPAQUE);
-
-                    Ok(geometry)
-                },
-            )
-            .collect();
-        let geometries = geometries?;
-
-        let build_range_infos: Vec<ash::vk::AccelerationStructureBuildRangeInfoKHR> = desc
-            .geometries
-            .iter()
-            .map(|desc| {
-                ash::vk::AccelerationStructureBuildRangeInfoKHR::default()
-                    .primitive_count(desc.parts[0].index_count as u32 / 3)
-            })
-            .collect();
+        let geometries = bottom_acceleration_geometries(desc);
+        let build_range_infos = bottom_acceleration_build_range_infos(desc);
 
         let geometry_info = ash::vk::AccelerationStructureBuildGeometryInfoKHR::default()
             .ty(ash::vk::AccelerationStructureTypeKHR::BOTTOM_LEVEL)
-            .flags(ash::vk::BuildAccelerationStructureFlagsKHR::PREFER_FAST_TRACE)
+            .flags(bottom_acceleration_build_flags(desc))
             .geometries(geometries.as_slice())
             .mode(vk::BuildAccelerationStructureModeKHR::BUILD);
 
@@ -203,11 +181,11 @@ impl Device {
                 GeometryInstance::new(
                     transform,
                     desc.mesh_index, /* instance id */
-                    0xff,
+                    desc.mask,
                     0,
-                    /*ash::vk::GeometryInstanceFlagsKHR::TRIANGLE_FACING_CULL_DISABLE
-                    | */
-                    ash::vk::GeometryInstanceFlagsKHR::FORCE_OPAQUE,
+                    // Opacity is determined per geometry; see `RayTracingGeometryDesc::opaque`.
+                    // Facing is determined in object space, so it's not flipped by mirroring.
+                    instance_flags(desc),
                     blas_address,
                 )
             })
@@ -297,6 +275,27 @@ impl Device {
         let backing_buffer_size: usize =
             preallocate_bytes.max(size_info.acceleration_structure_size as usize);
 
+        let update_scratch_buffer = if geometry_info
+            .flags
+            .contains(vk::BuildAccelerationStructureFlagsKHR::ALLOW_UPDATE)
+        {
+            Some(
+                self.create_buffer(
+                    super::buffer::BufferDesc::new_gpu_only(
+                        size_info.update_scratch_size.max(1) as usize,
+                        vk::BufferUsageFlags::STORAGE_BUFFER
+                            | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+                    )
+                    // TODO: query minAccelerationStructureScratchOffsetAlignment
+                    .alignment(256),
+                    "Acceleration structure update scratch buffer",
+                    None,
+                )?,
+            )
+        } else {
+            None
+        };
+
         let accel_buffer = self.create_buffer(
             super::buffer::BufferDesc::new_gpu_only(
                 backing_buffer_size,
@@ -386,6 +385,7 @@ impl Device {
                 Ok(RayTracingAcceleration {
                     raw: accel_raw,
                     backing_buffer: accel_buffer,
+                    update_scratch_buffer,
                 })
             }
         };
@@ -433,11 +433,11 @@ impl Device {
             GeometryInstance::new(
                 transform,
                 desc.mesh_index, /* instance id */
-                0xff,
+                desc.mask,
                 0,
-                /*ash::vk::GeometryInstanceFlagsKHR::TRIANGLE_FACING_CULL_DISABLE
-                | */
-                ash::vk::GeometryInstanceFlagsKHR::FORCE_OPAQUE,
+                // Opacity is determined per geometry; see `RayTracingGeometryDesc::opaque`.
+                // Facing is determined in object space, so it's not flipped by mirroring.
+                instance_flags(desc),
                 blas_address,
             )
         }));
@@ -555,6 +555,61 @@ impl Device {
         }
     }
 
+    /// Updates `blas` in place for new vertex positions. The topology described by `desc`
+    /// must match the one it was created with, and `allow_update` must have been set.
+    pub fn refit_ray_tracing_bottom_acceleration(
+        &self,
+        cb: vk::CommandBuffer,
+        desc: &RayTracingBottomAccelerationDesc,
+        blas: &RayTracingAcceleration,
+    ) {
+        let scratch_buffer = blas
+            .update_scratch_buffer
+            .as_ref()
+            .expect("the acceleration structure was not created with `allow_update`");
+
+        let geometries = bottom_acceleration_geometries(desc);
+        let build_range_infos = bottom_acceleration_build_range_infos(desc);
+
+        let geometry_info = ash::vk::AccelerationStructureBuildGeometryInfoKHR::default()
+            .ty(ash::vk::AccelerationStructureTypeKHR::BOTTOM_LEVEL)
+            .flags(bottom_acceleration_build_flags(desc))
+            .geometries(geometries.as_slice())
+            .mode(vk::BuildAccelerationStructureModeKHR::UPDATE)
+            .src_acceleration_structure(blas.raw)
+            .dst_acceleration_structure(blas.raw)
+            .scratch_data(ash::vk::DeviceOrHostAddressKHR {
+                device_address: scratch_buffer.device_address(self),
+            });
+
+        unsafe {
+            self.acceleration_structure_ext
+                .cmd_build_acceleration_structures(
+                    cb,
+                    std::slice::from_ref(&geometry_info),
+                    std::slice::from_ref(&build_range_infos.as_slice()),
+                );
+
+            self.raw.cmd_pipeline_barrier(
+                cb,
+                ash::vk::PipelineStageFlags::ACCELERATION_STRUCTURE_BUILD_KHR,
+                ash::vk::PipelineStageFlags::ACCELERATION_STRUCTURE_BUILD_KHR,
+                ash::vk::DependencyFlags::empty(),
+                &[ash::vk::MemoryBarrier::default()
+                    .src_access_mask(
+                        ash::vk::AccessFlags::ACCELERATION_STRUCTURE_READ_KHR
+                            | ash::vk::AccessFlags::ACCELERATION_STRUCTURE_WRITE_KHR,
+                    )
+                    .dst_access_mask(
+                        ash::vk::AccessFlags::ACCELERATION_STRUCTURE_READ_KHR
+                            | ash::vk::AccessFlags::ACCELERATION_STRUCTURE_WRITE_KHR,
+                    )],
+                &[],
+                &[],
+            );
+        }
+    }
+
     fn create_ray_tracing_shader_table(
         &self,
         desc: &RayTracingShaderTableDesc,
@@ -662,6 +717,69 @@ impl Device {
     }
 }
 
+fn bottom_acceleration_geometries(
+    desc: &RayTracingBottomAccelerationDesc,
+) -> Vec<ash::vk::AccelerationStructureGeometryKHR<'static>> {
+    desc.geometries
+        .iter()
+        .map(|desc| {
+            let part: RayTracingGeometryPart = desc.parts[0];
+
+            ash::vk::AccelerationStructureGeometryKHR::default()
+                .geometry_type(ash::vk::GeometryTypeKHR::TRIANGLES)
+                .geometry(ash::vk::AccelerationStructureGeometryDataKHR {
+                    triangles: ash::vk::AccelerationStructureGeometryTrianglesDataKHR::default()
+                        .vertex_data(ash::vk::DeviceOrHostAddressConstKHR {
+                            device_address: desc.vertex_buffer,
+                        })
+                        .vertex_stride(desc.vertex_stride as _)
+                        .max_vertex(part.max_vertex)
+                        .vertex_format(desc.vertex_format)
+                        .index_data(ash::vk::DeviceOrHostAddressConstKHR {
+                            device_address: desc.index_buffer,
+                        })
+                        .index_type(ash::vk::IndexType::UINT32), // TODO
+                })
+                .flags(if desc.opaque {
+                    ash::vk::GeometryFlagsKHR::OPAQUE
+                } else {
+                    ash::vk::GeometryFlagsKHR::empty()
+                })
+        })
+        .collect()
+}
+
+fn instance_flags(desc: &RayTracingInstanceDesc) -> ash::vk::GeometryInstanceFlagsKHR {
+    if desc.cull_disable {
+        ash::vk::GeometryInstanceFlagsKHR::TRIANGLE_FACING_CULL_DISABLE
+    } else {
+        ash::vk::GeometryInstanceFlagsKHR::empty()
+    }
+}
+
+fn bottom_acceleration_build_range_infos(
+    desc: &RayTracingBottomAccelerationDesc,
+) -> Vec<ash::vk::AccelerationStructureBuildRangeInfoKHR> {
+    desc.geometries
+        .iter()
+        .map(|desc| {
+            ash::vk::AccelerationStructureBuildRangeInfoKHR::default()
+                .primitive_count(desc.parts[0].index_count as u32 / 3)
+        })
+        .collect()
+}
+
+fn bottom_acceleration_build_flags(
+    desc: &RayTracingBottomAccelerationDesc,
+) -> vk::BuildAccelerationStructureFlagsKHR {
+    if desc.allow_update {
+        vk::BuildAccelerationStructureFlagsKHR::PREFER_FAST_TRACE
+            | vk::BuildAccelerationStructureFlagsKHR::ALLOW_UPDATE
+    } else {
+        vk::BuildAccelerationStructureFlagsKHR::PREFER_FAST_TRACE
+    }
+}
+
 pub struct RayTracingPipeline {
     pub common: ShaderPipelineCommon,
     pub sbt: RayTracingShaderTable,
@@ -831,6 +949,7 @@ pub fn create_ray_tracing_pipeline(
                     assert!(
                         prev_stage == Some(ShaderPipelineStage::RayMiss)
                             || prev_stage == Some(ShaderPipelineStage::RayClosestHit)
+                            || prev_stage == Some(ShaderPipelineStage::RayAnyHit)
                     );
                     hit_entry_count += 1;
 
@@ -858,6 +977,45 @@ pub fn create_ray_tracing_pipeline(
                     shader_stages.push(stage);
                     shader_groups.push(group);
                 }
+                ShaderPipelineStage::RayAnyHit => {
+                    assert!(
+                        prev_stage == Some(ShaderPipelineStage::RayMiss)
+                            || prev_stage == Some(ShaderPipelineStage::RayClosestHit)
+                            || prev_stage == Some(ShaderPipelineStage::RayAnyHit)
+                    );
+
+                    let (module, entry_point) = create_shader_module(desc);
+
+                    // This is safe because we have stopped the vec from resizing
+                    // and moving the references. That means each cell/element is
+                    // effectively it's own variable/container and mutability should
+                    // be considered per element.
+                    *entry_points[idx].as_mut_unchecked() =
+                        std::ffi::CString::new(entry_point).unwrap();
+
+                    let stage = ash::vk::PipelineShaderStageCreateInfo::default()
+                        .stage(ash::vk::ShaderStageFlags::ANY_HIT_KHR)
+                        .module(module)
+                        .name(entry_points[idx].as_ref_unchecked());
+
+                    if prev_stage == Some(ShaderPipelineStage::RayClosestHit) {
+                        // Joins the hit group of the preceding closest hit shader
+                        shader_groups.last_mut().unwrap().any_hit_shader = group_idx as _;
+                    } else {
+                        hit_entry_count += 1;
+
+                        let group = ash::vk::RayTracingShaderGroupCreateInfoKHR::default()
+                            .ty(ash::vk::RayTracingShaderGroupTypeKHR::TRIANGLES_HIT_GROUP)
+                            .general_shader(ash::vk::SHADER_UNUSED_KHR)
+                            .closest_hit_shader(ash::vk::SHADER_UNUSED_KHR)
+                            .any_hit_shader(group_idx as _)
+                            .intersection_shader(ash::vk::SHADER_UNUSED_KHR);
+
+                        shader_groups.push(group);
+                    }
+
+                    shader_stages.push(stage);
+                }
                 _ => unimplemented!(),
             }
 
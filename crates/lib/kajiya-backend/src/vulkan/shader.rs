@@ -475,6 +475,9 @@ pub enum ShaderPipelineStage {
     RayGen,
     RayMiss,
     RayClosestHit,
+    /// Attached to the hit group of the `RayClosestHit` shader right before it.
+    /// Otherwise, starts a hit group without a closest hit shader.
+    RayAnyHit,
 }
 
 #[derive(Builder, Hash, PartialEq, Eq, Clone, Debug)]
@@ -518,6 +521,8 @@ pub struct RasterPipelineDesc {
     pub render_pass: Arc<RenderPass>,
     #[builder(default)]
     pub face_cull: bool,
+    #[builder(default = "vk::FrontFace::COUNTER_CLOCKWISE")]
+    pub front_face: vk::FrontFace,
     #[builder(default = "true")]
     pub depth_write: bool,
     #[builder(default)]
@@ -898,7 +903,7 @@ pub fn create_raster_pipeline(
             .scissor_count(1);
 
         let rasterization_info = vk::PipelineRasterizationStateCreateInfo {
-            front_face: vk::FrontFace::COUNTER_CLOCKWISE,
+            front_face: desc.front_face,
             line_width: 1.0,
             polygon_mode: vk::PolygonMode::FILL,
             cull_mode: if desc.face_cull {
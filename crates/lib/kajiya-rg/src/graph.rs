@@ -868,11 +868,14 @@ impl<'exec_params, 'constants> ExecutingRenderGraph<'exec_params, 'constants> {
         self.passes = passes.into();
     }
 
+    /// Records the passes which write to the swapchain. `presentation_image` is substituted
+    /// for the swapchain resource, and can be either a swapchain image, or a plain image
+    /// owned by the caller when rendering headless.
     #[must_use]
     pub fn record_presentation_cb(
         mut self,
         cb: &CommandBuffer,
-        swapchain_image: Arc<Image>,
+        presentation_image: Arc<Image>,
     ) -> RetiredRenderGraph {
         let params = &self.resource_registry.execution_params;
 
@@ -899,7 +902,7 @@ impl<'exec_params, 'constants> ExecutingRenderGraph<'exec_params, 'constants> {
             if let AnyRenderResource::Pending(pending) = &mut res.resource {
                 match pending.resource {
                     GraphResourceInfo::Imported(GraphResourceImportInfo::SwapchainImage) => {
-                        res.resource = AnyRenderResource::ImportedImage(swapchain_image.clone());
+                        res.resource = AnyRenderResource::ImportedImage(presentation_image.clone());
                     }
                     _ => panic!("Only swapchain can be currently pending"),
                 }
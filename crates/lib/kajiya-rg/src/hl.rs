@@ -160,12 +160,43 @@ impl<'rg> SimpleRenderPass<'rg, RgComputePipelineHandle> {
     }
 }
 
+/// The shaders of one hit group of a ray tracing pipeline.
+#[derive(Clone)]
+pub struct RtHitGroup {
+    pub closest_hit: Option<ShaderSource>,
+
+    /// Only invoked for non-opaque geometry, e.g. to alpha test it.
+    pub any_hit: Option<ShaderSource>,
+}
+
+impl RtHitGroup {
+    pub fn new(closest_hit: ShaderSource) -> Self {
+        Self {
+            closest_hit: Some(closest_hit),
+            any_hit: None,
+        }
+    }
+
+    /// For rays which skip closest hit shaders, such as shadow rays.
+    pub fn any_hit_only(any_hit: ShaderSource) -> Self {
+        Self {
+            closest_hit: None,
+            any_hit: Some(any_hit),
+        }
+    }
+
+    pub fn any_hit(mut self, any_hit: ShaderSource) -> Self {
+        self.any_hit = Some(any_hit);
+        self
+    }
+}
+
 impl<'rg> SimpleRenderPass<'rg, RgRtPipelineHandle> {
     pub fn new_rt(
         mut pass: PassBuilder<'rg>,
         rgen: ShaderSource,
         miss: impl IntoIterator<Item = ShaderSource>,
-        hit: impl IntoIterator<Item = ShaderSource>,
+        hit: impl IntoIterator<Item = RtHitGroup>,
     ) -> Self {
         let miss = miss.into_iter();
         let hit = hit.into_iter();
@@ -187,13 +218,30 @@ impl<'rg> SimpleRenderPass<'rg, RgRtPipelineHandle> {
             );
         }
 
-        for source in hit {
-            shaders.push(
-                PipelineShaderDesc::builder(ShaderPipelineStage::RayClosestHit)
-                    .source(source)
-                    .build()
-                    .unwrap(),
-            );
+        for group in hit {
+            if let Some(source) = group.closest_hit {
+                shaders.push(
+                    PipelineShaderDesc::builder(ShaderPipelineStage::RayClosestHit)
+                        .source(source)
+                        .build()
+                        .unwrap(),
+                );
+            } else {
+                // The any hit shader would join the hit group of the closest hit shader before it.
+                assert!(
+                    shaders.last().unwrap().stage != ShaderPipelineStage::RayClosestHit,
+                    "A hit group without a closest hit shader can't follow one without an any hit shader"
+                );
+            }
+
+            if let Some(source) = group.any_hit {
+                shaders.push(
+                    PipelineShaderDesc::builder(ShaderPipelineStage::RayAnyHit)
+                        .source(source)
+                        .build()
+                        .unwrap(),
+                );
+            }
         }
 
         let pipeline = pass.register_ray_tracing_pipeline(
@@ -11,7 +11,7 @@ use kajiya_backend::{
     rspirv_reflect,
     transient_resource_cache::TransientResourceCache,
     vk_sync,
-    vulkan::{self, RenderBackend, swapchain::Swapchain},
+    vulkan::{self, RenderBackend, image::Image, swapchain::Swapchain},
 };
 #[allow(unused_imports)]
 use log::{debug, error, info, trace, warn};
@@ -77,6 +77,12 @@ lazy_static::lazy_static! {
     .collect();
 }
 
+/// Where the render graph's swapchain image resolves to when drawing a frame.
+enum PresentationTarget<'a> {
+    Swapchain(&'a mut Swapchain),
+    Image(Arc<Image>),
+}
+
 pub struct FrameConstantsLayout {
     pub globals_offset: u32,
     pub instance_dynamic_parameters_offset: u32,
@@ -120,6 +126,37 @@ impl Renderer {
         swapchain: &mut Swapchain,
     ) where
         PrepareFrameConstantsFn: FnOnce(&mut DynamicConstants) -> FrameConstantsLayout,
+    {
+        self.draw_frame_impl(
+            prepare_frame_constants,
+            PresentationTarget::Swapchain(swapchain),
+        )
+    }
+
+    /// Like `draw_frame`, but the render graph's swapchain image is substituted with
+    /// `output_image`, which must have been created with `STORAGE` usage.
+    ///
+    /// Once the frame is submitted, `output_image` is left in `TransferRead` access,
+    /// ready to be copied out by subsequent commands on the universal queue.
+    pub fn draw_frame_headless<PrepareFrameConstantsFn>(
+        &mut self,
+        prepare_frame_constants: PrepareFrameConstantsFn,
+        output_image: &Arc<Image>,
+    ) where
+        PrepareFrameConstantsFn: FnOnce(&mut DynamicConstants) -> FrameConstantsLayout,
+    {
+        self.draw_frame_impl(
+            prepare_frame_constants,
+            PresentationTarget::Image(output_image.clone()),
+        )
+    }
+
+    fn draw_frame_impl<PrepareFrameConstantsFn>(
+        &mut self,
+        prepare_frame_constants: PrepareFrameConstantsFn,
+        target: PresentationTarget,
+    ) where
+        PrepareFrameConstantsFn: FnOnce(&mut DynamicConstants) -> FrameConstantsLayout,
     {
         let rg = if let Some(rg) = self.compiled_rg.take() {
             rg
@@ -217,10 +254,28 @@ impl Renderer {
         // Now that we've done the main submission and the GPU is busy, acquire the presentation image.
         // This can block, so we're doing it as late as possible.
 
-        let swapchain_image = swapchain
-            .acquire_next_image()
-            .ok()
-            .expect("swapchain image");
+        let (swapchain, swapchain_image, presentation_image) = match target {
+            PresentationTarget::Swapchain(swapchain) => {
+                let swapchain_image = swapchain
+                    .acquire_next_image()
+                    .ok()
+                    .expect("swapchain image");
+                let image = swapchain_image.image.clone();
+
+                (Some(swapchain), Some(swapchain_image), image)
+            }
+            PresentationTarget::Image(image) => (None, None, image),
+        };
+
+        // Headless output images have no prior contents we care about, and are left readable.
+        let (initial_access, final_access) = if swapchain.is_some() {
+            (vk_sync::AccessType::Present, vk_sync::AccessType::Present)
+        } else {
+            (
+                vk_sync::AccessType::Nothing,
+                vk_sync::AccessType::TransferRead,
+            )
+        };
 
         // Execute the rest of the render graph, and submit the presentation command buffer.
         let retired_rg = {
@@ -228,13 +283,13 @@ impl Renderer {
 
             let presentation_cb = &current_frame.presentation_command_buffer;
 
-            // Transition the swapchain to CS write
+            // Transition the presentation image to CS write
             vulkan::barrier::record_image_barrier(
                 device,
                 presentation_cb.raw,
                 vulkan::barrier::ImageBarrier::new(
-                    swapchain_image.image.raw,
-                    vk_sync::AccessType::Present,
+                    presentation_image.raw,
+                    initial_access,
                     vk_sync::AccessType::ComputeShaderWrite,
                     vk::ImageAspectFlags::COLOR,
                 )
@@ -242,16 +297,16 @@ impl Renderer {
             );
 
             let retired_rg =
-                executing_rg.record_presentation_cb(presentation_cb, swapchain_image.image.clone());
+                executing_rg.record_presentation_cb(presentation_cb, presentation_image.clone());
 
-            // Transition the swapchain to present
+            // Transition the presentation image to present, or to a readable state when headless
             vulkan::barrier::record_image_barrier(
                 device,
                 presentation_cb.raw,
                 vulkan::barrier::ImageBarrier::new(
-                    swapchain_image.image.raw,
+                    presentation_image.raw,
                     vk_sync::AccessType::ComputeShaderWrite,
-                    vk_sync::AccessType::Present,
+                    final_access,
                     vk::ImageAspectFlags::COLOR,
                 ),
             );
@@ -264,13 +319,19 @@ impl Renderer {
             unsafe {
                 raw_device.end_command_buffer(presentation_cb.raw).unwrap();
 
-                let submit_info = [vk::SubmitInfo::default()
-                    .wait_semaphores(std::slice::from_ref(&swapchain_image.acquire_semaphore))
-                    .signal_semaphores(std::slice::from_ref(
-                        &swapchain_image.rendering_finished_semaphore,
-                    ))
-                    .wait_dst_stage_mask(&[vk::PipelineStageFlags::COMPUTE_SHADER])
-                    .command_buffers(std::slice::from_ref(&presentation_cb.raw))];
+                let wait_dst_stage_mask = [vk::PipelineStageFlags::COMPUTE_SHADER];
+                let mut submit_info = vk::SubmitInfo::default()
+                    .command_buffers(std::slice::from_ref(&presentation_cb.raw));
+
+                if let Some(swapchain_image) = &swapchain_image {
+                    submit_info = submit_info
+                        .wait_semaphores(std::slice::from_ref(&swapchain_image.acquire_semaphore))
+                        .signal_semaphores(std::slice::from_ref(
+                            &swapchain_image.rendering_finished_semaphore,
+                        ))
+                        .wait_dst_stage_mask(&wait_dst_stage_mask);
+                }
+
                 raw_device
                     .reset_fences(std::slice::from_ref(&presentation_cb.submit_done_fence))
                     .expect("reset_fences");
@@ -279,14 +340,16 @@ impl Renderer {
                 raw_device
                     .queue_submit(
                         self.device.universal_queue.raw,
-                        &submit_info,
+                        std::slice::from_ref(&submit_info),
                         presentation_cb.submit_done_fence,
                     )
                     .map_err(|err| device.report_error(err.into()))
                     .expect("presentation queue_submit failed");
             }
 
-            swapchain.present_image(swapchain_image);
+            if let (Some(swapchain), Some(swapchain_image)) = (swapchain, swapchain_image) {
+                swapchain.present_image(swapchain_image);
+            }
 
             retired_rg
         };
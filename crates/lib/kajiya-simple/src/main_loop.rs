@@ -495,7 +495,10 @@ impl ApplicationHandler for SimpleApp<'_> {
                             dt_filtered,
                         )
                     },
-                    &mut render_backend.swapchain,
+                    render_backend
+                        .swapchain
+                        .as_mut()
+                        .expect("windowed backend must have a swapchain"),
                 );
                 world_renderer.retire_frame();
                 self.last_error_text = None;
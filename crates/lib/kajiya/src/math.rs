@@ -1,4 +1,4 @@
-pub use glam::{Mat2, Mat3, Mat4, Quat, Vec2, Vec3, Vec4};
+pub use glam::{Affine3A, Mat2, Mat3, Mat4, Quat, Vec2, Vec3, Vec4};
 
 #[allow(dead_code)]
 pub fn build_orthonormal_basis(n: Vec3) -> Mat3 {
@@ -1,23 +1,58 @@
 use std::{collections::HashMap, fs::File, path::PathBuf};
 
 use anyhow::Context;
+use kajiya_asset::mesh::{FlatAsset, validate_baked_asset};
 use parking_lot::Mutex;
 
+struct MappedAsset {
+    mmap: memmap2::Mmap,
+
+    /// The `FlatAsset::ASSET_TYPE` the data was validated as, and the offset of the asset in the map.
+    validated_as: [u8; 16],
+    asset_offset: usize,
+}
+
 lazy_static::lazy_static! {
-    static ref ASSET_MMAPS: Mutex<HashMap<PathBuf, memmap2::Mmap>> = Mutex::new(HashMap::new());
+    static ref ASSET_MMAPS: Mutex<HashMap<PathBuf, MappedAsset>> = Mutex::new(HashMap::new());
 }
 
-pub fn mmapped_asset<T, P: Into<std::path::PathBuf>>(path: P) -> anyhow::Result<&'static T> {
+/// Maps a baked asset into memory, checking that it's intact and of the expected type
+/// the first time it's seen. Failed checks are reported as `InvalidBakedAsset`.
+pub fn mmapped_asset<T: FlatAsset, P: Into<std::path::PathBuf>>(
+    path: P,
+) -> anyhow::Result<&'static T> {
     let path = path.into();
     let path = kajiya_backend::canonical_path_from_vfs(&path)
         .with_context(|| format!("Can't mmap asset: file doesn't exist: {:?}", path))?;
 
     let mut mmaps = ASSET_MMAPS.lock();
-    let data: &[u8] = mmaps.entry(path.clone()).or_insert_with(|| {
-        let file =
-            File::open(&path).unwrap_or_else(|e| panic!("Could not mmap {:?}: {:?}", path, e));
-        unsafe { memmap2::MmapOptions::new().map(&file).unwrap() }
-    });
-    let asset: &T = unsafe { (data.as_ptr() as *const T).as_ref() }.unwrap();
+
+    if let Some(mapped) = mmaps.get(&path) {
+        // The maps are never removed, so the data lives for as long as the program.
+        let data: &'static [u8] = unsafe { std::mem::transmute::<&[u8], _>(&mapped.mmap[..]) };
+
+        if mapped.validated_as == T::ASSET_TYPE {
+            return Ok(unsafe { &*(data.as_ptr().add(mapped.asset_offset) as *const T) });
+        }
+
+        return validate_baked_asset::<T>(data).with_context(|| format!("Loading {:?}", path));
+    }
+
+    let file = File::open(&path).with_context(|| format!("Could not mmap {:?}", path))?;
+    let mmap = unsafe { memmap2::MmapOptions::new().map(&file) }
+        .with_context(|| format!("Could not mmap {:?}", path))?;
+
+    // The maps are never removed, so the data lives for as long as the program.
+    let data: &'static [u8] = unsafe { std::mem::transmute::<&[u8], _>(&mmap[..]) };
+    let asset = validate_baked_asset::<T>(data).with_context(|| format!("Loading {:?}", path))?;
+
+    mmaps.insert(
+        path,
+        MappedAsset {
+            mmap,
+            validated_as: T::ASSET_TYPE,
+            asset_offset: asset as *const T as usize - data.as_ptr() as usize,
+        },
+    );
     Ok(asset)
 }
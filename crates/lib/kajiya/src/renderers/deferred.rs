@@ -10,7 +10,8 @@ pub fn light_gbuffer(
     gbuffer_depth: &GbufferDepth,
     shadow_mask: &rg::Handle<Image>,
     rtr: &rg::Handle<Image>,
-    rtdgi: &rg::Handle<Image>,
+    rtdgi: Option<&rg::Handle<Image>>,
+    ssgi: &rg::Handle<Image>,
     ircache: &mut IrcacheRenderState,
     wrc: &WrcRenderState,
     temporal_output: &mut rg::Handle<Image>,
@@ -21,6 +22,17 @@ pub fn light_gbuffer(
     debug_shading_mode: usize,
     debug_show_wrc: bool,
 ) {
+    // Without ray tracing, bind a dummy in place of rtdgi; the shader falls back to SSAO-occluded sky.
+    let dummy_rtdgi;
+    let rtdgi_enabled = rtdgi.is_some();
+    let rtdgi = match rtdgi {
+        Some(rtdgi) => rtdgi,
+        None => {
+            dummy_rtdgi = rg.create(ImageDesc::new_2d(vk::Format::R8G8B8A8_UNORM, [1, 1]));
+            &dummy_rtdgi
+        }
+    };
+
     SimpleRenderPass::new_compute(rg.add_pass("light gbuffer"), "/shaders/light_gbuffer.hlsl")
         .read(&gbuffer_depth.gbuffer)
         .read_aspect(&gbuffer_depth.depth, vk::ImageAspectFlags::DEPTH)
@@ -33,10 +45,13 @@ pub fn light_gbuffer(
         .write(output)
         .read(sky_cube)
         .read(convolved_sky_cube)
+        .read(&gbuffer_depth.gbuffer_ext)
+        .read(ssgi)
         .constants((
             gbuffer_depth.gbuffer.desc().extent_inv_extent_2d(),
             debug_shading_mode as u32,
             debug_show_wrc as u32,
+            rtdgi_enabled as u32,
         ))
         .raw_descriptor_set(1, bindless_descriptor_set)
         .dispatch(gbuffer_depth.gbuffer.desc().extent);
@@ -0,0 +1,420 @@
+use std::sync::Arc;
+
+use glam::{Mat4, Quat, Vec3, Vec4};
+use kajiya_asset::mesh::{
+    AnimationChannel, AnimationInterpolation, AnimationPath, PackedTriMesh, SkinJoint,
+};
+use kajiya_backend::{
+    ash::vk,
+    vk_sync::AccessType,
+    vulkan::{buffer::Buffer, ray_tracing::*},
+};
+use kajiya_rg::{self as rg, SimpleRenderPass};
+
+#[derive(Clone, Debug)]
+pub struct AnimationClipInfo {
+    pub name: String,
+    /// In seconds; clips loop over this period.
+    pub duration: f32,
+}
+
+pub fn mesh_animation_clips(mesh: &PackedTriMesh::Flat) -> Vec<AnimationClipInfo> {
+    mesh.animation_clip_names
+        .as_slice()
+        .iter()
+        .enumerate()
+        .map(|(clip, name)| AnimationClipInfo {
+            name: String::from_utf8_lossy(name.as_slice()).into_owned(),
+            duration: clip_duration(mesh, clip as u32),
+        })
+        .collect()
+}
+
+fn clip_duration(mesh: &PackedTriMesh::Flat, clip: u32) -> f32 {
+    let key_times = mesh.animation_key_times.as_slice();
+
+    mesh.animation_channels
+        .as_slice()
+        .iter()
+        .filter(|channel| channel.clip == clip)
+        .map(|channel| key_times[(channel.first_key + channel.key_count - 1) as usize])
+        .fold(0.0, f32::max)
+}
+
+/// Samples `channel` at `time`, clamping to its first and last keys.
+fn sample_animation_channel(
+    mesh: &PackedTriMesh::Flat,
+    channel: &AnimationChannel,
+    time: f32,
+) -> Vec4 {
+    let key_times = &mesh.animation_key_times.as_slice()
+        [channel.first_key as usize..(channel.first_key + channel.key_count) as usize];
+    let values = &mesh.animation_key_values.as_slice()[channel.first_value as usize..];
+
+    let cubic = channel.interpolation == AnimationInterpolation::CubicSpline;
+    let value = |key: usize| -> Vec4 {
+        Vec4::from(if cubic {
+            values[key * 3 + 1]
+        } else {
+            values[key]
+        })
+    };
+
+    // Index of the first key after `time`
+    let next = key_times.partition_point(|&t| t <= time);
+    if next == 0 {
+        return value(0);
+    } else if next == key_times.len() {
+        return value(key_times.len() - 1);
+    }
+
+    let prev = next - 1;
+    let dt = key_times[next] - key_times[prev];
+    let t = ((time - key_times[prev]) / dt.max(1e-6)).clamp(0.0, 1.0);
+    let is_rotation = channel.path == AnimationPath::Rotation;
+
+    match channel.interpolation {
+        AnimationInterpolation::Step => value(prev),
+        AnimationInterpolation::Linear if is_rotation => {
+            let q0 = Quat::from_vec4(value(prev));
+            let q1 = Quat::from_vec4(value(next));
+            Vec4::from(q0.slerp(q1, t))
+        }
+        AnimationInterpolation::Linear => value(prev).lerp(value(next), t),
+        AnimationInterpolation::CubicSpline => {
+            let out_tangent = Vec4::from(values[prev * 3 + 2]) * dt;
+            let in_tangent = Vec4::from(values[next * 3]) * dt;
+
+            let t2 = t * t;
+            let t3 = t2 * t;
+
+            let res = value(prev) * (2.0 * t3 - 3.0 * t2 + 1.0)
+                + out_tangent * (t3 - 2.0 * t2 + t)
+                + value(next) * (-2.0 * t3 + 3.0 * t2)
+                + in_tangent * (t3 - t2);
+
+            if is_rotation { res.normalize() } else { res }
+        }
+    }
+}
+
+/// Skinning matrices of the joints of `mesh` with `clip` at `time` seconds,
+/// or in the rest pose without a clip.
+pub fn evaluate_skin_pose(mesh: &PackedTriMesh::Flat, clip: Option<u32>, time: f32) -> Vec<Mat4> {
+    let joints: &[SkinJoint] = mesh.skin_joints.as_slice();
+
+    let mut local_poses: Vec<(Vec3, Quat, Vec3)> = joints
+        .iter()
+        .map(|joint| {
+            (
+                Vec3::from(joint.translation),
+                Quat::from_array(joint.rotation),
+                Vec3::from(joint.scale),
+            )
+        })
+        .collect();
+
+    if let Some(clip) = clip {
+        let duration = clip_duration(mesh, clip);
+        let time = if duration > 0.0 {
+            time.rem_euclid(duration)
+        } else {
+            0.0
+        };
+
+        for channel in mesh.animation_channels.as_slice() {
+            if channel.clip != clip {
+                continue;
+            }
+
+            let value = sample_animation_channel(mesh, channel, time);
+            let pose = &mut local_poses[channel.joint as usize];
+
+            match channel.path {
+                AnimationPath::Translation => pose.0 = value.truncate(),
+                AnimationPath::Rotation => pose.1 = Quat::from_vec4(value).normalize(),
+                AnimationPath::Scale => pose.2 = value.truncate(),
+            }
+        }
+    }
+
+    let mut joint_transforms: Vec<Mat4> = Vec::with_capacity(joints.len());
+    for (joint, (translation, rotation, scale)) in joints.iter().zip(local_poses) {
+        let parent = if joint.parent == SkinJoint::NO_PARENT {
+            Mat4::IDENTITY
+        } else {
+            joint_transforms[joint.parent as usize]
+        };
+
+        joint_transforms.push(
+            parent
+                * Mat4::from_cols_array(&joint.parent_offset)
+                * Mat4::from_scale_rotation_translation(scale, rotation, translation),
+        );
+    }
+
+    joint_transforms
+        .into_iter()
+        .zip(joints)
+        .map(|(xform, joint)| xform * Mat4::from_cols_array(&joint.inverse_bind_matrix))
+        .collect()
+}
+
+/// Offsets into the vertex buffer; must match `deform_mesh.hlsl`.
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct DeformConstants {
+    pub vertex_count: u32,
+    pub source_core_offset: u32,
+    pub source_tangent_offset: u32,
+    /// Zero if not skinned
+    pub joints_offset: u32,
+    pub weights_offset: u32,
+    pub morph_target_count: u32,
+    pub morph_position_deltas_offset: u32,
+    pub morph_normal_deltas_offset: u32,
+    pub output_core_offset: u32,
+    pub output_prev_pos_offset: u32,
+    pub output_tangent_offset: u32,
+}
+
+pub struct DeformedInstance {
+    pub constants: DeformConstants,
+    /// Empty if not skinned
+    pub skin_matrices: Vec<Mat4>,
+    /// One per morph target
+    pub morph_weights: Vec<f32>,
+
+    /// The acceleration structures over the output vertices, if ray tracing.
+    pub blases: Vec<(
+        Arc<RayTracingAcceleration>,
+        RayTracingBottomAccelerationDesc,
+    )>,
+}
+
+/// Writes the morphed and skinned vertices of every instance, then refits their
+/// acceleration structures.
+pub fn deform_meshes(
+    rg: &mut rg::RenderGraph,
+    vertex_buffer: Arc<Buffer>,
+    bindless_descriptor_set: vk::DescriptorSet,
+    instances: Vec<DeformedInstance>,
+) {
+    if instances.is_empty() {
+        return;
+    }
+
+    let mut vertex_buffer = rg.import(vertex_buffer, AccessType::AnyShaderReadOther);
+    let mut blas_refits = Vec::new();
+
+    for instance in instances {
+        // Row-major 3x4, as in `InstanceTransform`
+        let skin_matrices: Vec<[f32; 12]> = instance
+            .skin_matrices
+            .iter()
+            .map(|xform| xform.transpose().to_cols_array()[..12].try_into().unwrap())
+            .collect();
+
+        SimpleRenderPass::new_compute(rg.add_pass("deform mesh"), "/shaders/deform_mesh.hlsl")
+            .write(&mut vertex_buffer)
+            .dynamic_storage_buffer_vec(skin_matrices)
+            .dynamic_storage_buffer_vec(instance.morph_weights)
+            .constants(instance.constants)
+            .raw_descriptor_set(1, bindless_descriptor_set)
+            .dispatch([instance.constants.vertex_count, 1, 1]);
+
+        blas_refits.extend(instance.blases);
+    }
+
+    // Also needed without ray tracing: the read makes the deformed vertices visible
+    // to all subsequent passes, which access them through the bindless descriptor set.
+    let mut pass = rg.add_pass("refit deformed blas");
+    pass.read(&vertex_buffer, AccessType::AnyShaderReadOther);
+
+    pass.render(move |api| {
+        for (blas, desc) in &blas_refits {
+            api.device()
+                .refit_ray_tracing_bottom_acceleration(api.cb.raw, desc, blas);
+        }
+
+        Ok(())
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kajiya_asset::mesh::{MeshSkin, validate_baked_asset};
+
+    fn joint(parent: u32, translation: [f32; 3]) -> SkinJoint {
+        SkinJoint {
+            parent,
+            parent_offset: Mat4::IDENTITY.to_cols_array(),
+            translation,
+            rotation: Quat::IDENTITY.to_array(),
+            scale: [1.0; 3],
+            inverse_bind_matrix: Mat4::IDENTITY.to_cols_array(),
+        }
+    }
+
+    fn channel(
+        joint: u32,
+        path: AnimationPath,
+        interpolation: AnimationInterpolation,
+        first_key: u32,
+        key_count: u32,
+    ) -> AnimationChannel {
+        AnimationChannel {
+            clip: 0,
+            joint,
+            path,
+            interpolation,
+            first_key,
+            key_count,
+            first_value: first_key,
+        }
+    }
+
+    fn bake_skin(skin: MeshSkin) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        PackedTriMesh::Proto {
+            verts: Vec::new(),
+            uvs: Vec::new(),
+            uvs1: Vec::new(),
+            tangents: Vec::new(),
+            colors: Vec::new(),
+            indices: Vec::new(),
+            material_ids: Vec::new(),
+            materials: Vec::new(),
+            maps: Vec::new(),
+            joints: Vec::new(),
+            weights: Vec::new(),
+            skin_joints: skin.joints,
+            animation_clip_names: skin
+                .animation_clip_names
+                .into_iter()
+                .map(String::into_bytes)
+                .collect(),
+            animation_channels: skin.animation_channels,
+            animation_key_times: skin.animation_key_times,
+            animation_key_values: skin.animation_key_values,
+            morph_position_deltas: Vec::new(),
+            morph_normal_deltas: Vec::new(),
+            morph_default_weights: Vec::new(),
+            lods: Vec::new(),
+            lod_indices: Vec::new(),
+        }
+        .flatten_into(&mut bytes);
+        bytes
+    }
+
+    /// A root joint with a child two units up, and a clip which moves the root along X,
+    /// turns the child about Z, and steps the child's scale.
+    fn animated_skin() -> Vec<u8> {
+        let quarter_turn = Quat::from_rotation_z(std::f32::consts::FRAC_PI_2).to_array();
+
+        bake_skin(MeshSkin {
+            joints: vec![
+                joint(SkinJoint::NO_PARENT, [1.0, 0.0, 0.0]),
+                joint(0, [0.0, 2.0, 0.0]),
+            ],
+            animation_clip_names: vec!["walk".to_owned()],
+            animation_channels: vec![
+                channel(
+                    0,
+                    AnimationPath::Translation,
+                    AnimationInterpolation::Linear,
+                    0,
+                    2,
+                ),
+                channel(
+                    1,
+                    AnimationPath::Rotation,
+                    AnimationInterpolation::Linear,
+                    2,
+                    2,
+                ),
+                channel(1, AnimationPath::Scale, AnimationInterpolation::Step, 4, 2),
+            ],
+            animation_key_times: vec![0.0, 2.0, 0.0, 2.0, 0.0, 1.0],
+            animation_key_values: vec![
+                [0.0, 0.0, 0.0, 0.0],
+                [4.0, 0.0, 0.0, 0.0],
+                Quat::IDENTITY.to_array(),
+                quarter_turn,
+                [1.0, 1.0, 1.0, 0.0],
+                [2.0, 2.0, 2.0, 0.0],
+            ],
+        })
+    }
+
+    fn assert_translation(xform: Mat4, expected: [f32; 3]) {
+        let translation = xform.w_axis.truncate();
+        assert!(
+            translation.abs_diff_eq(Vec3::from(expected), 1e-5),
+            "{:?} != {:?}",
+            translation,
+            expected
+        );
+    }
+
+    #[test]
+    fn rest_pose_chains_joints_and_applies_inverse_bind_matrices() {
+        let mut skin = MeshSkin {
+            joints: vec![
+                joint(SkinJoint::NO_PARENT, [1.0, 0.0, 0.0]),
+                joint(0, [0.0, 2.0, 0.0]),
+            ],
+            ..Default::default()
+        };
+
+        let bytes = bake_skin(skin.clone());
+        let mesh = validate_baked_asset::<PackedTriMesh::Flat>(&bytes).unwrap();
+        let pose = evaluate_skin_pose(mesh, None, 0.0);
+        assert_translation(pose[0], [1.0, 0.0, 0.0]);
+        assert_translation(pose[1], [1.0, 2.0, 0.0]);
+
+        // Binding at the rest pose leaves the vertices where they are.
+        skin.joints[0].inverse_bind_matrix =
+            Mat4::from_translation(Vec3::new(-1.0, 0.0, 0.0)).to_cols_array();
+        skin.joints[1].inverse_bind_matrix =
+            Mat4::from_translation(Vec3::new(-1.0, -2.0, 0.0)).to_cols_array();
+
+        let bytes = bake_skin(skin);
+        let mesh = validate_baked_asset::<PackedTriMesh::Flat>(&bytes).unwrap();
+        for xform in evaluate_skin_pose(mesh, None, 0.0) {
+            assert!(xform.abs_diff_eq(Mat4::IDENTITY, 1e-5));
+        }
+    }
+
+    #[test]
+    fn clips_interpolate_and_loop() {
+        let bytes = animated_skin();
+        let mesh = validate_baked_asset::<PackedTriMesh::Flat>(&bytes).unwrap();
+
+        let clips = mesh_animation_clips(mesh);
+        assert_eq!(clips.len(), 1);
+        assert_eq!(clips[0].name, "walk");
+        assert_eq!(clips[0].duration, 2.0);
+
+        // Halfway: the root has moved two units, the child has turned an eighth,
+        // and its scale has stepped to the last key.
+        let pose = evaluate_skin_pose(mesh, Some(0), 1.0);
+        assert_translation(pose[0], [2.0, 0.0, 0.0]);
+        assert_translation(pose[1], [2.0, 2.0, 0.0]);
+        let child_x = pose[1].transform_vector3(Vec3::X);
+        let expected_x = Quat::from_rotation_z(std::f32::consts::FRAC_PI_4) * Vec3::X * 2.0;
+        assert!(child_x.abs_diff_eq(expected_x, 1e-5), "{:?}", child_x);
+
+        // Just before the end of the step key, the scale hasn't changed yet.
+        let pose = evaluate_skin_pose(mesh, Some(0), 0.99);
+        assert!((pose[1].transform_vector3(Vec3::X).length() - 1.0).abs() < 1e-5);
+
+        // The clip loops.
+        let looped = evaluate_skin_pose(mesh, Some(0), 3.0);
+        let pose = evaluate_skin_pose(mesh, Some(0), 1.0);
+        for (a, b) in looped.iter().zip(&pose) {
+            assert!(a.abs_diff_eq(*b, 1e-5));
+        }
+    }
+}
@@ -401,7 +401,11 @@ impl IrcacheRenderState {
                 ShaderSource::hlsl("/shaders/rt/shadow.rmiss.hlsl"),
                 ShaderSource::hlsl("/shaders/rt/shadow.rmiss.hlsl"),
             ],
-            std::iter::empty(),
+            [
+                // Duplicated because `rt.hlsl` hardcodes the shadow hit group index to 1
+                rg::RtHitGroup::any_hit_only(ShaderSource::hlsl("/shaders/rt/shadow.rahit.hlsl")),
+                rg::RtHitGroup::any_hit_only(ShaderSource::hlsl("/shaders/rt/shadow.rahit.hlsl")),
+            ],
         )
         .read(&self.ircache_spatial_buf)
         .read(&self.ircache_life_buf)
@@ -420,7 +424,11 @@ impl IrcacheRenderState {
                 ShaderSource::hlsl("/shaders/rt/gbuffer.rmiss.hlsl"),
                 ShaderSource::hlsl("/shaders/rt/shadow.rmiss.hlsl"),
             ],
-            [ShaderSource::hlsl("/shaders/rt/gbuffer.rchit.hlsl")],
+            [
+                rg::RtHitGroup::new(ShaderSource::hlsl("/shaders/rt/gbuffer.rchit.hlsl"))
+                    .any_hit(ShaderSource::hlsl("/shaders/rt/gbuffer.rahit.hlsl")),
+                rg::RtHitGroup::any_hit_only(ShaderSource::hlsl("/shaders/rt/shadow.rahit.hlsl")),
+            ],
         )
         .read(&self.ircache_spatial_buf)
         .read(sky_cube)
@@ -453,7 +461,11 @@ impl IrcacheRenderState {
                 ShaderSource::hlsl("/shaders/rt/gbuffer.rmiss.hlsl"),
                 ShaderSource::hlsl("/shaders/rt/shadow.rmiss.hlsl"),
             ],
-            [ShaderSource::hlsl("/shaders/rt/gbuffer.rchit.hlsl")],
+            [
+                rg::RtHitGroup::new(ShaderSource::hlsl("/shaders/rt/gbuffer.rchit.hlsl"))
+                    .any_hit(ShaderSource::hlsl("/shaders/rt/gbuffer.rahit.hlsl")),
+                rg::RtHitGroup::any_hit_only(ShaderSource::hlsl("/shaders/rt/shadow.rahit.hlsl")),
+            ],
         )
         .read(&self.ircache_spatial_buf)
         .read(sky_cube)
@@ -53,7 +53,11 @@ impl LightingRenderer {
                 ShaderSource::hlsl("/shaders/rt/gbuffer.rmiss.hlsl"),
                 ShaderSource::hlsl("/shaders/rt/shadow.rmiss.hlsl"),
             ],
-            [ShaderSource::hlsl("/shaders/rt/gbuffer.rchit.hlsl")],
+            [
+                rg::RtHitGroup::new(ShaderSource::hlsl("/shaders/rt/gbuffer.rchit.hlsl"))
+                    .any_hit(ShaderSource::hlsl("/shaders/rt/gbuffer.rahit.hlsl")),
+                rg::RtHitGroup::any_hit_only(ShaderSource::hlsl("/shaders/rt/shadow.rahit.hlsl")),
+            ],
         )
         .read_aspect(&gbuffer_depth.depth, vk::ImageAspectFlags::DEPTH)
         .write(&mut refl0_tex)
@@ -4,6 +4,7 @@ use kajiya_backend::Image;
 use kajiya_rg::{self as rg, GetOrCreateTemporal};
 
 pub mod deferred;
+pub mod deform;
 pub mod dof;
 pub mod half_res;
 pub mod ibl;
@@ -31,6 +32,8 @@ pub mod dlss;
 pub struct GbufferDepth {
     pub geometric_normal: rg::Handle<Image>,
     pub gbuffer: rg::Handle<Image>,
+    /// Sheen and anisotropy; see `GbufferExtDataPacked` in the shaders.
+    pub gbuffer_ext: rg::Handle<Image>,
     pub depth: rg::Handle<Image>,
     half_view_normal: RefCell<Option<rg::Handle<Image>>>,
     half_depth: RefCell<Option<rg::Handle<Image>>>,
@@ -40,11 +43,13 @@ impl GbufferDepth {
     pub fn new(
         geometric_normal: rg::Handle<Image>,
         gbuffer: rg::Handle<Image>,
+        gbuffer_ext: rg::Handle<Image>,
         depth: rg::Handle<Image>,
     ) -> Self {
         Self {
             geometric_normal,
             gbuffer,
+            gbuffer_ext,
             depth,
             half_view_normal: Default::default(),
             half_depth: Default::default(),
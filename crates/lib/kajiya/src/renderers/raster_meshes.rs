@@ -7,15 +7,36 @@ use kajiya_backend::{
 };
 use kajiya_rg::{self as rg};
 use rg::{IntoRenderPassPipelineBinding, RenderGraph, RenderPassBinding};
+use rust_shaders_shared::camera::CameraMatrices;
 
-use crate::world_renderer::MeshInstance;
+use crate::{
+    math::{Affine3A, Vec3},
+    world_renderer::MeshInstance,
+};
 
 use super::GbufferDepth;
 
 #[derive(Clone)]
-pub struct UploadedTriMesh {
+pub struct UploadedMeshLod {
     pub index_buffer_offset: u64,
     pub index_count: u32,
+
+    /// Indices of triangles with single-sided materials come first in the index buffer,
+    /// followed by the double-sided ones.
+    pub single_sided_index_count: u32,
+
+    /// See `MeshLod::error`
+    pub error: f32,
+}
+
+#[derive(Clone)]
+pub struct UploadedTriMesh {
+    /// Level 0 is the full-resolution mesh; the others are increasingly simplified.
+    pub lods: Vec<UploadedMeshLod>,
+
+    /// Bounding sphere in mesh space, in the rest pose of deformable meshes.
+    pub bounds_center: Vec3,
+    pub bounds_radius: f32,
 }
 
 pub struct RasterMeshesData<'a> {
@@ -23,6 +44,42 @@ pub struct RasterMeshesData<'a> {
     pub instances: &'a [MeshInstance],
     pub vertex_buffer: Arc<Buffer>,
     pub bindless_descriptor_set: vk::DescriptorSet,
+    pub camera_matrices: CameraMatrices,
+
+    /// See `WorldRenderer::mesh_lod_pixel_error`
+    pub lod_pixel_error: f32,
+}
+
+/// Picks the coarsest level of detail of `mesh` whose error is at most `max_pixel_error`
+/// pixels when seen from `eye_position`.
+fn select_mesh_lod(
+    mesh: &UploadedTriMesh,
+    transform: &Affine3A,
+    eye_position: Vec3,
+    pixels_per_radian: f32,
+    max_pixel_error: f32,
+) -> usize {
+    let scale = transform
+        .matrix3
+        .x_axis
+        .length()
+        .max(transform.matrix3.y_axis.length())
+        .max(transform.matrix3.z_axis.length());
+
+    let center = transform.transform_point3(mesh.bounds_center);
+    let distance = center.distance(eye_position) - mesh.bounds_radius * scale;
+
+    if distance <= 0.0 {
+        return 0;
+    }
+
+    let pixels_per_mesh_unit = pixels_per_radian * scale / distance;
+
+    // Errors only grow with each level.
+    mesh.lods
+        .iter()
+        .rposition(|lod| lod.error * pixels_per_mesh_unit <= max_pixel_error)
+        .unwrap_or(0)
 }
 
 pub fn raster_meshes(
@@ -34,19 +91,40 @@ pub fn raster_meshes(
 ) {
     let mut pass = rg.add_pass("raster simple");
 
-    let pipeline = pass.register_raster_pipeline(
-        &[
-            PipelineShaderDesc::builder(ShaderPipelineStage::Vertex)
-                // .rust_source("raster_simple::raster_simple_vs")
-                .hlsl_source("/shaders/raster_simple_vs.hlsl")
-                .build()
-                .unwrap(),
-            PipelineShaderDesc::builder(ShaderPipelineStage::Pixel)
-                // .rust_source("raster_simple::raster_simple_fs")
-                .hlsl_source("/shaders/raster_simple_ps.hlsl")
-                .build()
-                .unwrap(),
-        ],
+    let shaders = [
+        PipelineShaderDesc::builder(ShaderPipelineStage::Vertex)
+            // .rust_source("raster_simple::raster_simple_vs")
+            .hlsl_source("/shaders/raster_simple_vs.hlsl")
+            .build()
+            .unwrap(),
+        PipelineShaderDesc::builder(ShaderPipelineStage::Pixel)
+            // .rust_source("raster_simple::raster_simple_fs")
+            .hlsl_source("/shaders/raster_simple_ps.hlsl")
+            .build()
+            .unwrap(),
+    ];
+
+    let single_sided_pipeline = pass.register_raster_pipeline(
+        &shaders,
+        RasterPipelineDesc::builder()
+            .render_pass(render_pass.clone())
+            .face_cull(true)
+            .push_constants_bytes(2 * std::mem::size_of::<u32>()),
+    );
+
+    // Mirroring flips the winding order, so mirrored instances are culled with the opposite
+    // front face. This matches glTF, where a negative determinant makes triangles clockwise.
+    let mirrored_single_sided_pipeline = pass.register_raster_pipeline(
+        &shaders,
+        RasterPipelineDesc::builder()
+            .render_pass(render_pass.clone())
+            .face_cull(true)
+            .front_face(vk::FrontFace::CLOCKWISE)
+            .push_constants_bytes(2 * std::mem::size_of::<u32>()),
+    );
+
+    let double_sided_pipeline = pass.register_raster_pipeline(
+        &shaders,
         RasterPipelineDesc::builder()
             .render_pass(render_pass.clone())
             .face_cull(false)
@@ -56,6 +134,28 @@ pub fn raster_meshes(
     let meshes: Vec<UploadedTriMesh> = mesh_data.meshes.to_vec();
     let instances: Vec<MeshInstance> = mesh_data.instances.to_vec();
 
+    let instance_lods: Vec<usize> = {
+        let eye_position = mesh_data.camera_matrices.eye_position();
+
+        // The focal length in pixels; about as many pixels per radian near the screen center.
+        let render_height = gbuffer_depth.gbuffer.desc().extent[1] as f32;
+        let pixels_per_radian =
+            mesh_data.camera_matrices.view_to_clip.y_axis.y * render_height * 0.5;
+
+        instances
+            .iter()
+            .map(|instance| {
+                select_mesh_lod(
+                    &meshes[instance.mesh.0],
+                    &instance.transform,
+                    eye_position,
+                    pixels_per_radian,
+                    mesh_data.lod_pixel_error,
+                )
+            })
+            .collect()
+    };
+
     let depth_ref = pass.raster(
         &mut gbuffer_depth.depth,
         AccessType::DepthAttachmentWriteStencilReadOnly,
@@ -67,6 +167,10 @@ pub fn raster_meshes(
     );
     let gbuffer_ref = pass.raster(&mut gbuffer_depth.gbuffer, AccessType::ColorAttachmentWrite);
     let velocity_ref = pass.raster(velocity_img, AccessType::ColorAttachmentWrite);
+    let gbuffer_ext_ref = pass.raster(
+        &mut gbuffer_depth.gbuffer_ext,
+        AccessType::ColorAttachmentWrite,
+    );
 
     let vertex_buffer = mesh_data.vertex_buffer.clone();
     let bindless_descriptor_set = mesh_data.bindless_descriptor_set;
@@ -117,6 +221,7 @@ pub fn raster_meshes(
                 (geometric_normal_ref, &ImageViewDesc::default()),
                 (gbuffer_ref, &ImageViewDesc::default()),
                 (velocity_ref, &ImageViewDesc::default()),
+                (gbuffer_ext_ref, &ImageViewDesc::default()),
             ],
             Some((
                 depth_ref,
@@ -129,45 +234,68 @@ pub fn raster_meshes(
 
         api.set_default_view_and_scissor([width, height]);
 
-        let pipeline = api.bind_raster_pipeline(
-            pipeline
-                .into_binding()
-                .descriptor_set(
-                    0,
-                    &[RenderPassBinding::DynamicConstantsStorageBuffer(
-                        instance_transforms_offset,
-                    )],
-                )
-                .raw_descriptor_set(1, bindless_descriptor_set),
-        )?;
+        for (pipeline, double_sided, mirrored_pipeline) in [
+            (single_sided_pipeline, false, false),
+            (mirrored_single_sided_pipeline, false, true),
+            (double_sided_pipeline, true, false),
+        ] {
+            let pipeline = api.bind_raster_pipeline(
+                pipeline
+                    .into_binding()
+                    .descriptor_set(
+                        0,
+                        &[RenderPassBinding::DynamicConstantsStorageBuffer(
+                            instance_transforms_offset,
+                        )],
+                    )
+                    .raw_descriptor_set(1, bindless_descriptor_set),
+            )?;
+
+            unsafe {
+                let raw_device = &api.device().raw;
+                let cb = api.cb;
+
+                for (draw_idx, instance) in instances.iter().enumerate() {
+                    let mesh = &meshes[instance.mesh.0].lods[instance_lods[draw_idx]];
+
+                    let mirrored = instance.transform.matrix3.determinant() < 0.0;
+
+                    let (first_index, index_count) = if double_sided {
+                        (
+                            mesh.single_sided_index_count,
+                            mesh.index_count - mesh.single_sided_index_count,
+                        )
+                    } else if mirrored == mirrored_pipeline {
+                        (0, mesh.single_sided_index_count)
+                    } else {
+                        continue;
+                    };
 
-        unsafe {
-            let raw_device = &api.device().raw;
-            let cb = api.cb;
-
-            for (draw_idx, instance) in instances.into_iter().enumerate() {
-                let mesh = &meshes[instance.mesh.0];
-
-                raw_device.cmd_bind_index_buffer(
-                    cb.raw,
-                    vertex_buffer.raw,
-                    mesh.index_buffer_offset,
-                    vk::IndexType::UINT32,
-                );
-
-                let push_constants = (draw_idx as u32, instance.mesh.0 as u32);
-
-                pipeline.push_constants(
-                    cb.raw,
-                    vk::ShaderStageFlags::ALL_GRAPHICS,
-                    0,
-                    std::slice::from_raw_parts(
-                        &push_constants as *const _ as *const u8,
-                        std::mem::size_of_val(&push_constants),
-                    ),
-                );
-
-                raw_device.cmd_draw_indexed(cb.raw, mesh.index_count, 1, 0, 0, 0);
+                    if index_count == 0 {
+                        continue;
+                    }
+
+                    raw_device.cmd_bind_index_buffer(
+                        cb.raw,
+                        vertex_buffer.raw,
+                        mesh.index_buffer_offset,
+                        vk::IndexType::UINT32,
+                    );
+
+                    let push_constants = (draw_idx as u32, instance.mesh.0 as u32);
+
+                    pipeline.push_constants(
+                        cb.raw,
+                        vk::ShaderStageFlags::ALL_GRAPHICS,
+                        0,
+                        std::slice::from_raw_parts(
+                            &push_constants as *const _ as *const u8,
+                            std::mem::size_of_val(&push_constants),
+                        ),
+                    );
+
+                    raw_device.cmd_draw_indexed(cb.raw, index_count, 1, first_index, 0, 0);
+                }
             }
         }
 
@@ -176,3 +304,51 @@ pub fn raster_meshes(
         Ok(())
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A unit sphere, with levels of detail straying up to 0, 0.01, 0.1, and 1 units.
+    fn test_mesh() -> UploadedTriMesh {
+        UploadedTriMesh {
+            lods: [0.0, 0.01, 0.1, 1.0]
+                .into_iter()
+                .map(|error| UploadedMeshLod {
+                    index_buffer_offset: 0,
+                    index_count: 0,
+                    single_sided_index_count: 0,
+                    error,
+                })
+                .collect(),
+            bounds_center: Vec3::ZERO,
+            bounds_radius: 1.0,
+        }
+    }
+
+    #[test]
+    fn select_mesh_lod_follows_the_projected_error() {
+        let mesh = test_mesh();
+        let select = |transform: Affine3A, eye_position: Vec3| {
+            select_mesh_lod(&mesh, &transform, eye_position, 1000.0, 1.0)
+        };
+
+        // Inside the bounds
+        assert_eq!(select(Affine3A::IDENTITY, Vec3::new(0.0, 0.0, 0.5)), 0);
+
+        // A thousandth of the distance to the bounds is allowed.
+        assert_eq!(select(Affine3A::IDENTITY, Vec3::new(0.0, 0.0, 5.0)), 0);
+        assert_eq!(select(Affine3A::IDENTITY, Vec3::new(0.0, 0.0, 11.0)), 1);
+        assert_eq!(select(Affine3A::IDENTITY, Vec3::new(0.0, 0.0, 1001.0)), 3);
+
+        // Instances are where their transforms put them.
+        let moved = Affine3A::from_translation(Vec3::new(100.0, 0.0, 0.0));
+        assert_eq!(select(moved, Vec3::new(-1000.0, 0.0, 0.0)), 3);
+        assert_eq!(select(moved, Vec3::new(100.0, 0.0, 11.0)), 1);
+
+        // Scaling up scales the errors and the bounds.
+        let scaled = Affine3A::from_scale(Vec3::new(1.0, 10.0, 1.0));
+        assert_eq!(select(scaled, Vec3::new(0.0, 0.0, 1001.0)), 1);
+        assert_eq!(select(scaled, Vec3::new(0.0, 0.0, 5.0)), 0);
+    }
+}
@@ -18,7 +18,11 @@ pub fn reference_path_trace(
             ShaderSource::hlsl("/shaders/rt/gbuffer.rmiss.hlsl"),
             ShaderSource::hlsl("/shaders/rt/shadow.rmiss.hlsl"),
         ],
-        [ShaderSource::hlsl("/shaders/rt/gbuffer.rchit.hlsl")],
+        [
+            rg::RtHitGroup::new(ShaderSource::hlsl("/shaders/rt/gbuffer.rchit.hlsl"))
+                .any_hit(ShaderSource::hlsl("/shaders/rt/gbuffer.rahit.hlsl")),
+            rg::RtHitGroup::any_hit_only(ShaderSource::hlsl("/shaders/rt/shadow.rahit.hlsl")),
+        ],
     )
     .write(output_img)
     .raw_descriptor_set(1, bindless_descriptor_set)
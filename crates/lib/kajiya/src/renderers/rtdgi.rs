@@ -297,7 +297,13 @@ impl RtdgiRenderer {
                     ShaderSource::hlsl("/shaders/rt/gbuffer.rmiss.hlsl"),
                     ShaderSource::hlsl("/shaders/rt/shadow.rmiss.hlsl"),
                 ],
-                [ShaderSource::hlsl("/shaders/rt/gbuffer.rchit.hlsl")],
+                [
+                    rg::RtHitGroup::new(ShaderSource::hlsl("/shaders/rt/gbuffer.rchit.hlsl"))
+                        .any_hit(ShaderSource::hlsl("/shaders/rt/gbuffer.rahit.hlsl")),
+                    rg::RtHitGroup::any_hit_only(ShaderSource::hlsl(
+                        "/shaders/rt/shadow.rahit.hlsl",
+                    )),
+                ],
             )
             .read(&*half_view_normal_tex)
             .read_aspect(&gbuffer_depth.depth, vk::ImageAspectFlags::DEPTH)
@@ -325,7 +331,13 @@ impl RtdgiRenderer {
                     ShaderSource::hlsl("/shaders/rt/gbuffer.rmiss.hlsl"),
                     ShaderSource::hlsl("/shaders/rt/shadow.rmiss.hlsl"),
                 ],
-                [ShaderSource::hlsl("/shaders/rt/gbuffer.rchit.hlsl")],
+                [
+                    rg::RtHitGroup::new(ShaderSource::hlsl("/shaders/rt/gbuffer.rchit.hlsl"))
+                        .any_hit(ShaderSource::hlsl("/shaders/rt/gbuffer.rahit.hlsl")),
+                    rg::RtHitGroup::any_hit_only(ShaderSource::hlsl(
+                        "/shaders/rt/shadow.rahit.hlsl",
+                    )),
+                ],
             )
             .read(&*half_view_normal_tex)
             .read_aspect(&gbuffer_depth.depth, vk::ImageAspectFlags::DEPTH)
@@ -483,7 +495,13 @@ impl RtdgiRenderer {
                         ShaderSource::hlsl("/shaders/rt/gbuffer.rmiss.hlsl"),
                         ShaderSource::hlsl("/shaders/rt/shadow.rmiss.hlsl"),
                     ],
-                    [ShaderSource::hlsl("/shaders/rt/gbuffer.rchit.hlsl")],
+                    [
+                        rg::RtHitGroup::new(ShaderSource::hlsl("/shaders/rt/gbuffer.rchit.hlsl"))
+                            .any_hit(ShaderSource::hlsl("/shaders/rt/gbuffer.rahit.hlsl")),
+                        rg::RtHitGroup::any_hit_only(ShaderSource::hlsl(
+                            "/shaders/rt/shadow.rahit.hlsl",
+                        )),
+                    ],
                 )
                 .read(&*half_depth_tex)
                 .read(&temporal_reservoir_packed_tex)
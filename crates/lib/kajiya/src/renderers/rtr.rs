@@ -136,7 +136,11 @@ impl RtrRenderer {
                 ShaderSource::hlsl("/shaders/rt/gbuffer.rmiss.hlsl"),
                 ShaderSource::hlsl("/shaders/rt/shadow.rmiss.hlsl"),
             ],
-            [ShaderSource::hlsl("/shaders/rt/gbuffer.rchit.hlsl")],
+            [
+                rg::RtHitGroup::new(ShaderSource::hlsl("/shaders/rt/gbuffer.rchit.hlsl"))
+                    .any_hit(ShaderSource::hlsl("/shaders/rt/gbuffer.rahit.hlsl")),
+                rg::RtHitGroup::any_hit_only(ShaderSource::hlsl("/shaders/rt/shadow.rahit.hlsl")),
+            ],
         )
         .read(&gbuffer_depth.gbuffer)
         .read_aspect(&gbuffer_depth.depth, vk::ImageAspectFlags::DEPTH)
@@ -212,7 +216,13 @@ impl RtrRenderer {
                     ShaderSource::hlsl("/shaders/rt/gbuffer.rmiss.hlsl"),
                     ShaderSource::hlsl("/shaders/rt/shadow.rmiss.hlsl"),
                 ],
-                [ShaderSource::hlsl("/shaders/rt/gbuffer.rchit.hlsl")],
+                [
+                    rg::RtHitGroup::new(ShaderSource::hlsl("/shaders/rt/gbuffer.rchit.hlsl"))
+                        .any_hit(ShaderSource::hlsl("/shaders/rt/gbuffer.rahit.hlsl")),
+                    rg::RtHitGroup::any_hit_only(ShaderSource::hlsl(
+                        "/shaders/rt/shadow.rahit.hlsl",
+                    )),
+                ],
             )
             .read(&gbuffer_depth.gbuffer)
             .read_aspect(&gbuffer_depth.depth, vk::ImageAspectFlags::DEPTH)
@@ -23,7 +23,11 @@ pub fn trace_sun_shadow_mask(
             ShaderSource::hlsl("/shaders/rt/shadow.rmiss.hlsl"),
             ShaderSource::hlsl("/shaders/rt/shadow.rmiss.hlsl"),
         ],
-        std::iter::empty(),
+        [
+            // Duplicated because `rt.hlsl` hardcodes the shadow hit group index to 1
+            rg::RtHitGroup::any_hit_only(ShaderSource::hlsl("/shaders/rt/shadow.rahit.hlsl")),
+            rg::RtHitGroup::any_hit_only(ShaderSource::hlsl("/shaders/rt/shadow.rahit.hlsl")),
+        ],
     )
     .read_aspect(&gbuffer_depth.depth, vk::ImageAspectFlags::DEPTH)
     .read(&gbuffer_depth.geometric_normal)
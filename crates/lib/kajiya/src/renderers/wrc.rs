@@ -50,7 +50,11 @@ pub fn wrc_trace(
             ShaderSource::hlsl("/shaders/rt/gbuffer.rmiss.hlsl"),
             ShaderSource::hlsl("/shaders/rt/shadow.rmiss.hlsl"),
         ],
-        [ShaderSource::hlsl("/shaders/rt/gbuffer.rchit.hlsl")],
+        [
+            rg::RtHitGroup::new(ShaderSource::hlsl("/shaders/rt/gbuffer.rchit.hlsl"))
+                .any_hit(ShaderSource::hlsl("/shaders/rt/gbuffer.rahit.hlsl")),
+            rg::RtHitGroup::any_hit_only(ShaderSource::hlsl("/shaders/rt/shadow.rahit.hlsl")),
+        ],
     )
     .read(sky_cube)
     .bind_mut(ircache)
@@ -84,7 +88,11 @@ impl WrcRenderState {
                 ShaderSource::hlsl("/shaders/rt/gbuffer.rmiss.hlsl"),
                 ShaderSource::hlsl("/shaders/rt/shadow.rmiss.hlsl"),
             ],
-            [ShaderSource::hlsl("/shaders/rt/gbuffer.rchit.hlsl")],
+            [
+                rg::RtHitGroup::new(ShaderSource::hlsl("/shaders/rt/gbuffer.rchit.hlsl"))
+                    .any_hit(ShaderSource::hlsl("/shaders/rt/gbuffer.rahit.hlsl")),
+                rg::RtHitGroup::any_hit_only(ShaderSource::hlsl("/shaders/rt/shadow.rahit.hlsl")),
+            ],
         )
         .bind(self)
         .read(sky_cube)
@@ -51,13 +51,18 @@ impl WorldRenderer {
                     frame_desc.render_extent,
                 ));
 
+                let gbuffer_ext = rg.create(ImageDesc::new_2d(
+                    vk::Format::R32G32_UINT,
+                    frame_desc.render_extent,
+                ));
+
                 let mut depth_img = rg.create(ImageDesc::new_2d(
                     vk::Format::D32_SFLOAT,
                     frame_desc.render_extent,
                 ));
                 rg::imageops::clear_depth(rg, &mut depth_img);
 
-                GbufferDepth::new(normal, gbuffer, depth_img)
+                GbufferDepth::new(normal, gbuffer, gbuffer_ext, depth_img)
             };
 
             let mut velocity_img = rg.create(ImageDesc::new_2d(
@@ -75,6 +80,8 @@ impl WorldRenderer {
                     instances: self.instances.as_slice(),
                     vertex_buffer: self.vertex_buffer.lock().clone(),
                     bindless_descriptor_set: self.bindless_descriptor_set,
+                    camera_matrices: frame_desc.camera_matrices,
+                    lod_pixel_error: self.mesh_lod_pixel_error,
                 },
             );
 
@@ -163,10 +170,11 @@ impl WorldRenderer {
         }
 
         // TODO: don't iter over all the things
-        let any_triangle_lights = self
-            .instances
-            .iter()
-            .any(|inst| !self.mesh_lights[inst.mesh.0].lights.is_empty());
+        let any_triangle_lights = !self.punctual_lights.is_empty()
+            || self
+                .instances
+                .iter()
+                .any(|inst| !self.mesh_lights[inst.mesh.0].lights.is_empty());
 
         let mut rtr = if let Some(((tlas, rtdgi_irradiance), rtdgi_candidates)) = tlas
             .as_ref()
@@ -209,19 +217,13 @@ impl WorldRenderer {
             gbuffer_depth.gbuffer.desc().extent_2d(),
         ));
 
-        let rtdgi = match rtdgi_irradiance {
-            Some(rtdgi) => rtdgi,
-            None => rg
-                .create(ImageDesc::new_2d(vk::Format::R8G8B8A8_UNORM, [1, 1]))
-                .into(),
-        };
-
         light_gbuffer(
             rg,
             &gbuffer_depth,
             &denoised_shadow_mask,
             &rtr,
-            &rtdgi,
+            rtdgi_irradiance.as_deref(),
+            &ssgi_tex,
             &mut ircache_state,
             &wrc,
             &mut accum_img,
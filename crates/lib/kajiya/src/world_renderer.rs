@@ -7,7 +7,7 @@ use crate::{
     frame_desc::WorldFrameDesc,
     image_lut::{ComputeImageLut, ImageLut},
     renderers::{
-        ibl::IblRenderer, ircache::IrcacheRenderer, lighting::LightingRenderer,
+        deform::*, ibl::IblRenderer, ircache::IrcacheRenderer, lighting::LightingRenderer,
         post::PostProcessRenderer, raster_meshes::*, rtdgi::RtdgiRenderer, rtr::*,
         shadow_denoise::ShadowDenoiseRenderer, ssgi::*, taa::TaaRenderer,
     },
@@ -51,6 +51,26 @@ struct GpuMesh {
 
     mat_data_offset: u32,
     index_offset: u32,
+    vertex_uv1_offset: u32,
+
+    /// Positions in the previous frame, for deformed meshes; zero if same as the current.
+    vertex_prev_pos_offset: u32,
+
+    /// The triangles at `index_offset` go into two BLASes: first the single-sided ones,
+    /// then the double-sided ones. Each has two geometries: first the non-opaque triangles,
+    /// which need the any-hit shader, then the opaque ones.
+    single_sided_triangle_count: u32,
+    single_sided_non_opaque_triangle_count: u32,
+    double_sided_non_opaque_triangle_count: u32,
+}
+
+/// The triangles of one of the BLASes of a mesh; see `GpuMesh::single_sided_triangle_count`.
+#[derive(Clone, Copy)]
+struct MeshBlasPart {
+    /// Relative to `GpuMesh::index_offset`
+    first_index: usize,
+    index_count: usize,
+    non_opaque_index_count: usize,
 }
 
 #[derive(Clone, Copy, Hash, PartialEq, Eq, Debug)]
@@ -59,6 +79,9 @@ pub struct MeshHandle(pub usize);
 #[derive(Clone, Copy, Hash, PartialEq, Eq, Debug)]
 pub struct InstanceHandle(pub usize);
 
+#[derive(Clone, Copy, Hash, PartialEq, Eq, Debug)]
+pub struct PunctualLightHandle(pub usize);
+
 impl InstanceHandle {
     pub const INVALID: InstanceHandle = InstanceHandle(!0);
 
@@ -109,6 +132,9 @@ pub enum RenderDebugMode {
 pub struct TriangleLight {
     pub verts: [[f32; 3]; 3],
     pub radiance: [f32; 3],
+    /// Cosines of the outer and inner angles of the cone around the triangle normal,
+    /// to which spot lights confine their emission. Equal for lights without a cone.
+    pub spot_cone: [f32; 2],
 }
 
 impl TriangleLight {
@@ -124,6 +150,7 @@ impl TriangleLight {
                 (rotation * Vec3::from(self.verts[2]) + translation).into(),
             ],
             radiance: self.radiance,
+            spot_cone: self.spot_cone,
         }
     }
 
@@ -131,6 +158,7 @@ impl TriangleLight {
         Self {
             verts: self.verts,
             radiance: (Vec3::from(self.radiance) * scale).into(),
+            spot_cone: self.spot_cone,
         }
     }
 }
@@ -139,6 +167,150 @@ pub struct MeshLightSet {
     pub lights: Vec<TriangleLight>,
 }
 
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PunctualLightKind {
+    Point,
+    /// Emits along `PunctualLight::direction`, fading out between the inner and outer
+    /// cone angles (in radians) as per `KHR_lights_punctual`.
+    Spot {
+        inner_cone_angle: f32,
+        outer_cone_angle: f32,
+    },
+}
+
+/// A light without geometry. Rendered as a tiny emitter in the triangle light list,
+/// so that everything which samples triangle lights picks it up.
+///
+/// Triangle lights are only sampled with ray tracing, so these have no effect without it,
+/// nor in the raster-only lighting path.
+#[derive(Clone, Copy, Debug)]
+pub struct PunctualLight {
+    pub kind: PunctualLightKind,
+    pub position: Vec3,
+    /// Only used by spot lights.
+    pub direction: Vec3,
+    /// Radiant intensity along the brightest direction.
+    pub intensity: Vec3,
+}
+
+/// Size of the emitters standing in for punctual lights.
+const PUNCTUAL_LIGHT_PROXY_RADIUS: f32 = 0.02;
+
+impl PunctualLight {
+    fn proxy_triangle_lights(&self) -> Vec<TriangleLight> {
+        let r = PUNCTUAL_LIGHT_PROXY_RADIUS;
+        let triangle = |a: Vec3, b: Vec3, c: Vec3, radiance: Vec3| TriangleLight {
+            verts: [
+                (self.position + a).into(),
+                (self.position + b).into(),
+                (self.position + c).into(),
+            ],
+            radiance: radiance.into(),
+            spot_cone: [0.0; 2],
+        };
+
+        match self.kind {
+            // An octahedron, facing outwards. Its mean projected area is `sqrt(3) * r^2`.
+            PunctualLightKind::Point => {
+                let radiance = self.intensity / (3.0f32.sqrt() * r * r);
+                let mut res = Vec::with_capacity(8);
+
+                for sx in [-1.0f32, 1.0] {
+                    for sy in [-1.0f32, 1.0] {
+                        for sz in [-1.0f32, 1.0] {
+                            let a = Vec3::X * sx * r;
+                            let b = Vec3::Y * sy * r;
+                            let c = Vec3::Z * sz * r;
+
+                            res.push(if sx * sy * sz > 0.0 {
+                                triangle(a, b, c, radiance)
+                            } else {
+                                triangle(a, c, b, radiance)
+                            });
+                        }
+                    }
+                }
+
+                res
+            }
+            // A square facing along the direction.
+            PunctualLightKind::Spot {
+                inner_cone_angle,
+                outer_cone_angle,
+            } => {
+                let radiance = self.intensity / (4.0 * r * r);
+                let direction = self.direction.normalize();
+                let u = direction.any_orthonormal_vector() * r;
+                let v = direction.cross(u);
+
+                // Keep the inner cone strictly narrower, so that the shader sees a cone at all.
+                let cos_outer = outer_cone_angle.min(std::f32::consts::FRAC_PI_2).cos();
+                let cos_inner = inner_cone_angle.cos().max(cos_outer + 1e-4);
+                let spot_cone = [cos_outer, cos_inner];
+
+                vec![
+                    TriangleLight {
+                        spot_cone,
+                        ..triangle(-u - v, u - v, u + v, radiance)
+                    },
+                    TriangleLight {
+                        spot_cone,
+                        ..triangle(-u - v, u + v, v - u, radiance)
+                    },
+                ]
+            }
+        }
+    }
+}
+
+/// A mesh with a skeleton or morph targets. Instances don't render it directly, but through
+/// copies of their own, into which the deformation pass writes the vertices.
+struct DeformableMesh {
+    source: &'static PackedTriMesh::Flat,
+    gpu_mesh: GpuMesh,
+    /// Zero if not skinned
+    joints_offset: u32,
+    weights_offset: u32,
+    morph_position_deltas_offset: u32,
+    morph_normal_deltas_offset: u32,
+    max_vertex: u32,
+
+    /// Single-sided and double-sided, of the level of detail at `gpu_mesh.index_offset`.
+    blas_parts: [MeshBlasPart; 2],
+
+    /// Copies no longer used by any instance.
+    free_copies: Vec<MeshHandle>,
+}
+
+struct DeformedMeshCopy {
+    source: MeshHandle,
+    vertex_core_offset: u32,
+    vertex_prev_pos_offset: u32,
+    vertex_tangent_offset: u32,
+    /// Single-sided and double-sided, if ray tracing and the mesh has such triangles.
+    blas_descs: [Option<RayTracingBottomAccelerationDesc>; 2],
+}
+
+#[derive(Clone, Default, PartialEq)]
+struct DeformationPose {
+    clip: Option<u32>,
+    time: f32,
+    morph_weights: Vec<f32>,
+}
+
+#[derive(Default)]
+struct InstanceDeformation {
+    pose: DeformationPose,
+
+    /// What the deformed copy was last updated to, if anything.
+    deformed_pose: Option<DeformationPose>,
+
+    /// Whether the copy was updated to `deformed_pose` twice in a row, so that its
+    /// previous positions match the current ones, and it needs no more updates until
+    /// the pose changes.
+    settled: bool,
+}
+
 pub struct WorldRenderer {
     device: Arc<device::Device>,
 
@@ -157,12 +329,18 @@ pub struct WorldRenderer {
     // The `usize` indexes into `instances` and `instance_handles`
     pub(super) instance_handle_to_index: HashMap<InstanceHandle, usize>,
 
+    deformable_meshes: HashMap<MeshHandle, DeformableMesh>,
+    // Keyed by the handles of the copies, which instances refer to
+    deformed_mesh_copies: HashMap<MeshHandle, DeformedMeshCopy>,
+    instance_deformations: HashMap<InstanceHandle, InstanceDeformation>,
+
     pub(super) vertex_buffer: Mutex<Arc<Buffer>>,
     vertex_buffer_written: u64,
 
     mesh_buffer: Mutex<Arc<Buffer>>,
 
-    mesh_blas: Vec<Arc<RayTracingAcceleration>>,
+    // Single-sided and double-sided
+    mesh_blas: Vec<[Option<Arc<RayTracingAcceleration>>; 2]>,
     tlas: Option<Arc<RayTracingAcceleration>>,
     accel_scratch: RayTracingAccelerationScratchBuffer,
 
@@ -171,6 +349,9 @@ pub struct WorldRenderer {
     next_instance_handle: usize,
     bindless_texture_sizes: Buffer,
 
+    pub(super) punctual_lights: Vec<(PunctualLightHandle, PunctualLight)>,
+    next_punctual_light_handle: usize,
+
     image_luts: Vec<ImageLut>,
     frame_idx: u32,
     prev_camera_matrices: Option<CameraMatrices>,
@@ -204,6 +385,10 @@ pub struct WorldRenderer {
     pub dynamic_exposure: DynamicExposureState,
     pub contrast: f32,
 
+    /// Rasterized meshes use the coarsest level of detail whose simplification error
+    /// is at most this many pixels on screen.
+    pub mesh_lod_pixel_error: f32,
+
     pub sun_size_multiplier: f32,
     pub sun_color_multiplier: Vec3,
     pub sky_ambient: Vec3,
@@ -294,15 +479,20 @@ pub enum RenderMode {
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub struct BindlessImageHandle(pub u32);
 
-fn load_gpu_image_asset(
-    device: Arc<kajiya_backend::Device>,
+pub(crate) fn mmapped_gpu_image_asset(
     asset: AssetRef<GpuImage::Flat>,
-) -> Arc<Image> {
-    let asset = crate::mmap::mmapped_asset::<GpuImage::Flat, _>(&format!(
+) -> anyhow::Result<&'static GpuImage::Flat> {
+    crate::mmap::mmapped_asset::<GpuImage::Flat, _>(&format!(
         "/cache/{:8.8x}.image",
         asset.identity()
     ))
-    .unwrap();
+}
+
+fn load_gpu_image_asset(
+    device: Arc<kajiya_backend::Device>,
+    asset: AssetRef<GpuImage::Flat>,
+) -> Arc<Image> {
+    let asset = mmapped_gpu_image_asset(asset).unwrap();
 
     let desc = ImageDesc::new_2d(asset.format, [asset.extent[0], asset.extent[1]])
         .usage(vk::ImageUsageFlags::SAMPLED)
@@ -325,6 +515,10 @@ fn load_gpu_image_asset(
 #[derive(Default)]
 pub struct AddMeshOptions {
     pub use_lights: bool,
+
+    /// Level of detail which ray tracing sees the mesh at; clamped to the coarsest one.
+    /// Zero is full resolution.
+    pub blas_lod: usize,
 }
 
 impl AddMeshOptions {
@@ -336,6 +530,11 @@ impl AddMeshOptions {
         self.use_lights = v;
         self
     }
+
+    pub fn blas_lod(mut self, v: usize) -> Self {
+        self.blas_lod = v;
+        self
+    }
 }
 
 impl WorldRenderer {
@@ -356,6 +555,8 @@ impl WorldRenderer {
                     RenderPassAttachmentDesc::new(vk::Format::R32G32B32A32_SFLOAT).garbage_input(),
                     // velocity
                     RenderPassAttachmentDesc::new(vk::Format::R16G16B16A16_SFLOAT).garbage_input(),
+                    // gbuffer extension: sheen and anisotropy
+                    RenderPassAttachmentDesc::new(vk::Format::R32G32_UINT).garbage_input(),
                 ],
                 depth_attachment: Some(RenderPassAttachmentDesc::new(vk::Format::D32_SFLOAT)),
             },
@@ -370,15 +571,19 @@ impl WorldRenderer {
             None,
         )?;
 
+        let mut vertex_buffer_usage = vk::BufferUsageFlags::STORAGE_BUFFER
+            | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS
+            | vk::BufferUsageFlags::INDEX_BUFFER
+            | vk::BufferUsageFlags::TRANSFER_DST;
+
+        // Only valid with the acceleration structure extension, which e.g. lavapipe lacks.
+        if backend.device.ray_tracing_enabled() {
+            vertex_buffer_usage |=
+                vk::BufferUsageFlags::ACCELERATION_STRUCTURE_BUILD_INPUT_READ_ONLY_KHR;
+        }
+
         let vertex_buffer = backend.device.create_buffer(
-            BufferDesc::new_gpu_only(
-                VERTEX_BUFFER_CAPACITY,
-                vk::BufferUsageFlags::STORAGE_BUFFER
-                    | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS
-                    | vk::BufferUsageFlags::INDEX_BUFFER
-                    | vk::BufferUsageFlags::TRANSFER_DST
-                    | vk::BufferUsageFlags::ACCELERATION_STRUCTURE_BUILD_INPUT_READ_ONLY_KHR,
-            ),
+            BufferDesc::new_gpu_only(VERTEX_BUFFER_CAPACITY, vertex_buffer_usage),
             "vertex buffer",
             None,
         )?;
@@ -452,6 +657,10 @@ impl WorldRenderer {
             instance_handles: Default::default(),
             instance_handle_to_index: Default::default(),
 
+            deformable_meshes: Default::default(),
+            deformed_mesh_copies: Default::default(),
+            instance_deformations: Default::default(),
+
             mesh_lights: Default::default(),
 
             mesh_blas: Default::default(),
@@ -467,6 +676,8 @@ impl WorldRenderer {
 
             next_bindless_image_id: 0,
             next_instance_handle: 0,
+            punctual_lights: Default::default(),
+            next_punctual_light_handle: 0,
             bindless_texture_sizes,
 
             rg_debug_hook: None,
@@ -505,6 +716,8 @@ impl WorldRenderer {
             dynamic_exposure: Default::default(),
             contrast: 1.0,
 
+            mesh_lod_pixel_error: 1.0,
+
             sun_size_multiplier: 1.0, // Sun as seen from Earth
             sun_color_multiplier: Vec3::ONE,
             sky_ambient: Vec3::ZERO,
@@ -597,6 +810,7 @@ impl WorldRenderer {
         handle
     }
 
+    /// The mesh must have triangles; see `add_baked_mesh`.
     pub fn add_mesh(
         &mut self,
         mesh: &'static PackedTriMesh::Flat,
@@ -650,15 +864,88 @@ impl WorldRenderer {
             }
         }
 
+        let double_sided_materials: Vec<bool> = mesh
+            .materials
+            .iter()
+            .map(|mat| mat.flags & MeshMaterialFlags::MESH_MATERIAL_FLAG_DOUBLE_SIDED != 0)
+            .collect();
+
         let vertex_data_offset = self.vertex_buffer_written as u32;
 
         let mut buffer_builder = BufferBuilder::new();
+        let lods: Vec<UploadedMeshLod> = (0..mesh.lod_count())
+            .map(|lod| {
+                let (indices, single_sided_index_count) = partition_by_sidedness(
+                    mesh.lod_indices(lod),
+                    mesh.material_ids.as_slice(),
+                    &double_sided_materials,
+                );
+                let index_count = indices.len();
+
+                UploadedMeshLod {
+                    index_buffer_offset: buffer_builder.append(indices) + vertex_data_offset as u64,
+                    index_count: index_count as _,
+                    single_sided_index_count: single_sided_index_count as _,
+                    error: mesh.lod_error(lod),
+                }
+            })
+            .collect();
+
+        let blas_lod_idx = opts.blas_lod.min(lods.len() - 1);
+        let blas_lod = &lods[blas_lod_idx];
+        let (blas_lod_indices, blas_single_sided_index_count) = partition_by_sidedness(
+            mesh.lod_indices(blas_lod_idx),
+            mesh.material_ids.as_slice(),
+            &double_sided_materials,
+        );
+
+        // Alpha-masked and transmissive materials are resolved in the any-hit shader,
+        // so their triangles go into separate non-opaque BLAS geometries.
+        let is_non_opaque_triangle = |tri: &[u32]| {
+            let mat = &mesh.materials[mesh.material_ids[tri[0] as usize] as usize];
+            mat.flags & MeshMaterialFlags::MESH_MATERIAL_FLAG_ALPHA_MASK != 0
+                || mat.transmission_factor > 0.0
+        };
+
+        // Double-sided triangles go into a BLAS of their own, so that only its instances
+        // need to disable back-face culling.
+        let (single_sided, double_sided) = blas_lod_indices.split_at(blas_single_sided_index_count);
+        let (single_sided, single_sided_non_opaque_index_count) =
+            partition_by_opacity(single_sided, is_non_opaque_triangle);
+        let (double_sided, double_sided_non_opaque_index_count) =
+            partition_by_opacity(double_sided, is_non_opaque_triangle);
+
+        let blas_parts = [
+            MeshBlasPart {
+                first_index: 0,
+                index_count: single_sided.len(),
+                non_opaque_index_count: single_sided_non_opaque_index_count,
+            },
+            MeshBlasPart {
+                first_index: single_sided.len(),
+                index_count: double_sided.len(),
+                non_opaque_index_count: double_sided_non_opaque_index_count,
+            },
+        ];
+
+        // Usually all triangles are opaque, and the raster index buffer of the LOD can be reused.
         let vertex_index_offset =
-            buffer_builder.append(mesh.indices.as_slice()) as u32 + vertex_data_offset;
+            if single_sided_non_opaque_index_count + double_sided_non_opaque_index_count == 0 {
+                blas_lod.index_buffer_offset as u32
+            } else {
+                let indices: Vec<u32> = single_sided.into_iter().chain(double_sided).collect();
+                buffer_builder.append(indices) as u32 + vertex_data_offset
+            };
         let vertex_core_offset =
             buffer_builder.append(mesh.verts.as_slice()) as u32 + vertex_data_offset;
         let vertex_uv_offset =
             buffer_builder.append(mesh.uvs.as_slice()) as u32 + vertex_data_offset;
+        // Without a second uv set, maps which refer to it read the first one.
+        let vertex_uv1_offset = if mesh.uvs1.is_empty() {
+            vertex_uv_offset
+        } else {
+            buffer_builder.append(mesh.uvs1.as_slice()) as u32 + vertex_data_offset
+        };
         let vertex_mat_offset =
             buffer_builder.append(mesh.material_ids.as_slice()) as u32 + vertex_data_offset;
         let vertex_aux_offset =
@@ -667,59 +954,49 @@ impl WorldRenderer {
             buffer_builder.append(mesh.tangents.as_slice()) as u32 + vertex_data_offset;
         let mat_data_offset = buffer_builder.append(materials) as u32 + vertex_data_offset;
 
-        let total_buffer_size = buffer_builder.current_offset();
-        let mut vertex_buffer = self.vertex_buffer.lock();
-        buffer_builder
-            .upload(
-                self.device.as_ref(),
-                Arc::get_mut(&mut *vertex_buffer).expect("refs may not be retained"),
-                self.vertex_buffer_written,
+        let is_skinned = !mesh.skin_joints.is_empty();
+        let (joints_offset, weights_offset) = if is_skinned {
+            (
+                buffer_builder.append(mesh.joints.as_slice()) as u32 + vertex_data_offset,
+                buffer_builder.append(mesh.weights.as_slice()) as u32 + vertex_data_offset,
             )
-            .map_err(|err| self.device.report_error(err))
-            .unwrap();
-        self.vertex_buffer_written += total_buffer_size;
+        } else {
+            (0, 0)
+        };
 
-        let mesh_buffer_dst = unsafe {
-            let mut mesh_buffer = self.mesh_buffer.lock();
-            let mesh_buffer = Arc::get_mut(&mut *mesh_buffer).expect("refs may not be retained");
-            let mesh_buffer_dst =
-                mesh_buffer.allocation.mapped_ptr().unwrap().as_ptr() as *mut GpuMesh;
-            std::slice::from_raw_parts_mut(mesh_buffer_dst, MAX_GPU_MESHES)
+        let is_morphed = !mesh.morph_default_weights.is_empty();
+        let (morph_position_deltas_offset, morph_normal_deltas_offset) = if is_morphed {
+            (
+                buffer_builder.append(mesh.morph_position_deltas.as_slice()) as u32
+                    + vertex_data_offset,
+                buffer_builder.append(mesh.morph_normal_deltas.as_slice()) as u32
+                    + vertex_data_offset,
+            )
+        } else {
+            (0, 0)
         };
 
-        if self.device.ray_tracing_enabled() {
-            let base_da = vertex_buffer.device_address(&self.device);
-            let vertex_buffer_da = base_da + vertex_core_offset as u64;
-            let index_buffer_da = base_da + vertex_index_offset as u64;
+        self.upload_to_vertex_buffer(buffer_builder);
 
-            let blas = self
-                .device
-                .create_ray_tracing_bottom_acceleration(&RayTracingBottomAccelerationDesc {
-                    geometries: vec![RayTracingGeometryDesc {
-                        geometry_type: RayTracingGeometryType::Triangle,
-                        vertex_buffer: vertex_buffer_da,
-                        index_buffer: index_buffer_da,
-                        vertex_format: vk::Format::R32G32B32_SFLOAT,
-                        vertex_stride: size_of::<PackedVertex>(),
-                        parts: vec![RayTracingGeometryPart {
-                            index_count: mesh.indices.len(),
-                            index_offset: 0,
-                            max_vertex: mesh
-                                .indices
-                                .as_slice()
-                                .iter()
-                                .copied()
-                                .max()
-                                .expect("mesh must not be empty"),
-                        }],
-                    }],
-                })
-                .expect("blas");
+        let max_vertex = mesh
+            .indices
+            .as_slice()
+            .iter()
+            .copied()
+            .max()
+            .expect("mesh must not be empty");
 
-            self.mesh_blas.push(Arc::new(blas));
+        if self.device.ray_tracing_enabled() {
+            self.create_mesh_blases(
+                vertex_core_offset,
+                vertex_index_offset,
+                blas_parts,
+                max_vertex,
+                false,
+            );
         }
 
-        mesh_buffer_dst[mesh_idx] = GpuMesh {
+        let gpu_mesh = GpuMesh {
             vertex_core_offset,
             vertex_uv_offset,
             vertex_mat_offset,
@@ -727,11 +1004,50 @@ impl WorldRenderer {
             vertex_tangent_offset,
             mat_data_offset,
             index_offset: vertex_index_offset,
+            vertex_uv1_offset,
+            vertex_prev_pos_offset: 0,
+            single_sided_triangle_count: blas_parts[0].index_count as u32 / 3,
+            single_sided_non_opaque_triangle_count: blas_parts[0].non_opaque_index_count as u32 / 3,
+            double_sided_non_opaque_triangle_count: blas_parts[1].non_opaque_index_count as u32 / 3,
+        };
+        self.write_gpu_mesh(mesh_idx, gpu_mesh);
+
+        if is_skinned || is_morphed {
+            self.deformable_meshes.insert(
+                MeshHandle(mesh_idx),
+                DeformableMesh {
+                    source: mesh,
+                    gpu_mesh,
+                    joints_offset,
+                    weights_offset,
+                    morph_position_deltas_offset,
+                    morph_normal_deltas_offset,
+                    max_vertex,
+                    blas_parts,
+                    free_copies: Vec::new(),
+                },
+            );
+        }
+
+        let (bounds_center, bounds_radius) = {
+            let (min, max) = mesh.verts.iter().fold(
+                (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+                |(min, max), v| (min.min(Vec3::from(v.pos)), max.max(Vec3::from(v.pos))),
+            );
+            let center = (min + max) * 0.5;
+            let radius = mesh
+                .verts
+                .iter()
+                .map(|v| Vec3::from(v.pos).distance(center))
+                .fold(0.0, f32::max);
+
+            (center, radius)
         };
 
         self.meshes.push(UploadedTriMesh {
-            index_buffer_offset: vertex_index_offset as u64,
-            index_count: mesh.indices.len() as _,
+            lods,
+            bounds_center,
+            bounds_radius,
         });
 
         let mesh_lights = if opts.use_lights {
@@ -756,6 +1072,7 @@ impl WorldRenderer {
                 mesh_lights.push(TriangleLight {
                     verts: [v0, v1, v2],
                     radiance,
+                    spot_cone: [0.0; 2],
                 });
             }
 
@@ -771,11 +1088,204 @@ impl WorldRenderer {
         MeshHandle(mesh_idx)
     }
 
+    /// Appends the contents of `buffer_builder` to the vertex buffer.
+    fn upload_to_vertex_buffer(&mut self, buffer_builder: BufferBuilder) {
+        let total_buffer_size = buffer_builder.current_offset();
+        let mut vertex_buffer = self.vertex_buffer.lock();
+        buffer_builder
+            .upload(
+                self.device.as_ref(),
+                Arc::get_mut(&mut *vertex_buffer).expect("refs may not be retained"),
+                self.vertex_buffer_written,
+            )
+            .map_err(|err| self.device.report_error(err))
+            .unwrap();
+        self.vertex_buffer_written += total_buffer_size;
+    }
+
+    fn write_gpu_mesh(&self, mesh_idx: usize, gpu_mesh: GpuMesh) {
+        let mesh_buffer_dst = unsafe {
+            let mut mesh_buffer = self.mesh_buffer.lock();
+            let mesh_buffer = Arc::get_mut(&mut *mesh_buffer).expect("refs may not be retained");
+            let mesh_buffer_dst =
+                mesh_buffer.allocation.mapped_ptr().unwrap().as_ptr() as *mut GpuMesh;
+            std::slice::from_raw_parts_mut(mesh_buffer_dst, MAX_GPU_MESHES)
+        };
+
+        mesh_buffer_dst[mesh_idx] = gpu_mesh;
+    }
+
+    /// Creates the BLASes of a mesh, pushing them to `mesh_blas`, and returns their descriptions.
+    fn create_mesh_blases(
+        &mut self,
+        vertex_core_offset: u32,
+        index_offset: u32,
+        parts: [MeshBlasPart; 2],
+        max_vertex: u32,
+        allow_update: bool,
+    ) -> [Option<RayTracingBottomAccelerationDesc>; 2] {
+        let descs = parts.map(|part| {
+            (part.index_count > 0).then(|| {
+                self.mesh_blas_desc(
+                    vertex_core_offset,
+                    index_offset + (part.first_index * size_of::<u32>()) as u32,
+                    part.index_count,
+                    part.non_opaque_index_count,
+                    max_vertex,
+                    allow_update,
+                )
+            })
+        });
+
+        let blases = descs.each_ref().map(|desc| {
+            desc.as_ref().map(|desc| {
+                Arc::new(
+                    self.device
+                        .create_ray_tracing_bottom_acceleration(desc)
+                        .expect("blas"),
+                )
+            })
+        });
+        self.mesh_blas.push(blases);
+
+        descs
+    }
+
+    /// The first `non_opaque_index_count` indices go into a non-opaque geometry, and the rest
+    /// into an opaque one. Empty geometries are left out, so hit shaders can tell them apart
+    /// via `GeometryIndex()` and the non-opaque triangle counts in `GpuMesh`.
+    fn mesh_blas_desc(
+        &self,
+        vertex_core_offset: u32,
+        index_offset: u32,
+        index_count: usize,
+        non_opaque_index_count: usize,
+        max_vertex: u32,
+        allow_update: bool,
+    ) -> RayTracingBottomAccelerationDesc {
+        let base_da = self.vertex_buffer.lock().device_address(&self.device);
+
+        let ranges = [
+            (0, non_opaque_index_count, false),
+            (non_opaque_index_count, index_count, true),
+        ];
+
+        RayTracingBottomAccelerationDesc {
+            geometries: ranges
+                .into_iter()
+                .filter(|(start, end, _)| end > start)
+                .map(|(start, end, opaque)| RayTracingGeometryDesc {
+                    geometry_type: RayTracingGeometryType::Triangle,
+                    vertex_buffer: base_da + vertex_core_offset as u64,
+                    index_buffer: base_da
+                        + (index_offset as usize + start * size_of::<u32>()) as u64,
+                    vertex_format: vk::Format::R32G32B32_SFLOAT,
+                    vertex_stride: size_of::<PackedVertex>(),
+                    parts: vec![RayTracingGeometryPart {
+                        index_count: end - start,
+                        index_offset: 0,
+                        max_vertex,
+                    }],
+                    opaque,
+                })
+                .collect(),
+            allow_update,
+        }
+    }
+
+    /// Returns a copy of the deformable mesh `source` for one instance to deform.
+    fn acquire_deformed_mesh_copy(&mut self, source: MeshHandle) -> MeshHandle {
+        let deformable = self.deformable_meshes.get_mut(&source).unwrap();
+        if let Some(copy) = deformable.free_copies.pop() {
+            return copy;
+        }
+
+        let mesh = deformable.source;
+        let gpu_mesh = deformable.gpu_mesh;
+        let (max_vertex, blas_parts) = (deformable.max_vertex, deformable.blas_parts);
+
+        let mesh_idx = self.meshes.len();
+        let vertex_data_offset = self.vertex_buffer_written as u32;
+
+        // Start out in the rest pose, until the deformation pass runs.
+        let prev_positions: Vec<[f32; 4]> = mesh
+            .verts
+            .as_slice()
+            .iter()
+            .map(|v| [v.pos[0], v.pos[1], v.pos[2], 1.0])
+            .collect();
+
+        let mut buffer_builder = BufferBuilder::new();
+        let vertex_core_offset =
+            buffer_builder.append(mesh.verts.as_slice()) as u32 + vertex_data_offset;
+        let vertex_prev_pos_offset =
+            buffer_builder.append(prev_positions) as u32 + vertex_data_offset;
+        let vertex_tangent_offset =
+            buffer_builder.append(mesh.tangents.as_slice()) as u32 + vertex_data_offset;
+        self.upload_to_vertex_buffer(buffer_builder);
+
+        let blas_descs = if self.device.ray_tracing_enabled() {
+            self.create_mesh_blases(
+                vertex_core_offset,
+                gpu_mesh.index_offset,
+                blas_parts,
+                max_vertex,
+                true,
+            )
+        } else {
+            [None, None]
+        };
+
+        self.write_gpu_mesh(
+            mesh_idx,
+            GpuMesh {
+                vertex_core_offset,
+                vertex_tangent_offset,
+                vertex_prev_pos_offset,
+                ..gpu_mesh
+            },
+        );
+
+        self.meshes.push(self.meshes[source.0].clone());
+
+        // Emissive triangles of deformed meshes move, so they can't be static lights.
+        self.mesh_lights.push(MeshLightSet { lights: Vec::new() });
+
+        self.deformed_mesh_copies.insert(
+            MeshHandle(mesh_idx),
+            DeformedMeshCopy {
+                source,
+                vertex_core_offset,
+                vertex_prev_pos_offset,
+                vertex_tangent_offset,
+                blas_descs,
+            },
+        );
+
+        MeshHandle(mesh_idx)
+    }
+
     pub fn add_instance(&mut self, mesh: MeshHandle, transform: Affine3A) -> InstanceHandle {
         let handle = self.next_instance_handle;
         self.next_instance_handle += 1;
         let handle = InstanceHandle(handle);
 
+        let mesh = if let Some(deformable) = self.deformable_meshes.get(&mesh) {
+            self.instance_deformations.insert(
+                handle,
+                InstanceDeformation {
+                    pose: DeformationPose {
+                        morph_weights: deformable.source.morph_default_weights.to_vec(),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+            );
+            self.acquire_deformed_mesh_copy(mesh)
+        } else {
+            mesh
+        };
+
         let index = self.instances.len();
 
         self.instances.push(MeshInstance {
@@ -798,9 +1308,19 @@ impl WorldRenderer {
             .instance_handle_to_index
             .remove(&inst)
             .expect("no such instance");
-        self.instances.swap_remove(index);
+        let instance = self.instances.swap_remove(index);
         self.instance_handles.swap_remove(index);
 
+        // Let another instance reuse the deformed copy of the mesh.
+        if self.instance_deformations.remove(&inst).is_some() {
+            let source = self.deformed_mesh_copies[&instance.mesh].source;
+            self.deformable_meshes
+                .get_mut(&source)
+                .unwrap()
+                .free_copies
+                .push(instance.mesh);
+        }
+
         // A new instance could have been moved into this slot in the vec.
         // Make sure `instance_handle_to_index` reflects this.
         if let Some(new_handle) = self.instance_handles.get(index).copied() {
@@ -808,6 +1328,25 @@ impl WorldRenderer {
         }
     }
 
+    pub fn add_punctual_light(&mut self, light: PunctualLight) -> PunctualLightHandle {
+        let handle = PunctualLightHandle(self.next_punctual_light_handle);
+        self.next_punctual_light_handle += 1;
+
+        self.punctual_lights.push((handle, light));
+        handle
+    }
+
+    pub fn remove_punctual_light(&mut self, light: PunctualLightHandle) {
+        self.punctual_lights.retain(|(handle, _)| *handle != light);
+    }
+
+    pub fn get_punctual_light_mut(&mut self, light: PunctualLightHandle) -> &mut PunctualLight {
+        self.punctual_lights
+            .iter_mut()
+            .find_map(|(handle, res)| (*handle == light).then_some(res))
+            .expect("no such light")
+    }
+
     pub fn set_instance_transform(&mut self, inst: InstanceHandle, transform: Affine3A) {
         let index = self.instance_handle_to_index[&inst];
         self.instances[index].transform = transform;
@@ -829,21 +1368,156 @@ impl WorldRenderer {
         &mut self.instances[index].dynamic_parameters
     }
 
+    /// Weights of the morph targets of an instance; empty if its mesh has none.
+    pub fn get_instance_morph_weights(&self, inst: InstanceHandle) -> &[f32] {
+        self.instance_deformations
+            .get(&inst)
+            .map_or(&[][..], |deformation| {
+                deformation.pose.morph_weights.as_slice()
+            })
+    }
+
+    pub fn get_instance_morph_weights_mut(&mut self, inst: InstanceHandle) -> &mut [f32] {
+        self.instance_deformations
+            .get_mut(&inst)
+            .map_or(&mut [][..], |deformation| {
+                deformation.pose.morph_weights.as_mut_slice()
+            })
+    }
+
+    /// Clips which can be played on instances of `mesh` with `set_instance_animation`.
+    /// Empty unless the mesh is skinned.
+    pub fn mesh_animation_clips(&self, mesh: MeshHandle) -> Vec<AnimationClipInfo> {
+        self.deformable_meshes
+            .get(&mesh)
+            .map_or_else(Vec::new, |deformable| {
+                mesh_animation_clips(deformable.source)
+            })
+    }
+
+    /// Like `mesh_animation_clips`, for the mesh of an instance.
+    pub fn instance_animation_clips(&self, inst: InstanceHandle) -> Vec<AnimationClipInfo> {
+        let mesh = self.instances[self.instance_handle_to_index[&inst]].mesh;
+        self.deformed_mesh_copies
+            .get(&mesh)
+            .map_or_else(Vec::new, |copy| self.mesh_animation_clips(copy.source))
+    }
+
+    /// Poses a skinned instance at `time` seconds into `clip`, an index into
+    /// `mesh_animation_clips`; or in the rest pose if `None`. Clips loop.
+    /// Ignored for instances of meshes without a skeleton.
+    pub fn set_instance_animation(&mut self, inst: InstanceHandle, clip: Option<usize>, time: f32) {
+        let Some(deformation) = self.instance_deformations.get_mut(&inst) else {
+            return;
+        };
+
+        let mesh = self.instances[self.instance_handle_to_index[&inst]].mesh;
+        let source = self.deformed_mesh_copies[&mesh].source;
+        if self.deformable_meshes[&source].joints_offset == 0 {
+            return;
+        }
+
+        // Time has no effect on the rest pose; don't let it count as a change.
+        deformation.pose.clip = clip.map(|clip| clip as u32);
+        deformation.pose.time = if clip.is_some() { time } else { 0.0 };
+    }
+
+    /// Adds the passes deforming instances of skinned and morphed meshes to their current pose.
+    /// Instances which have settled in an unchanged pose are skipped.
+    fn prepare_mesh_deformation(&mut self, rg: &mut rg::RenderGraph) {
+        let instances: Vec<DeformedInstance> = self
+            .instances
+            .iter()
+            .zip(&self.instance_handles)
+            .filter_map(|(inst, handle)| {
+                let copy = self.deformed_mesh_copies.get(&inst.mesh)?;
+                let deformable = &self.deformable_meshes[&copy.source];
+                let deformation = self.instance_deformations.get_mut(handle).unwrap();
+
+                if deformation.deformed_pose.as_ref() == Some(&deformation.pose) {
+                    if deformation.settled {
+                        return None;
+                    }
+                    deformation.settled = true;
+                } else {
+                    deformation.deformed_pose = Some(deformation.pose.clone());
+                    deformation.settled = false;
+                }
+
+                let pose = &deformation.pose;
+                let skin_matrices = if deformable.joints_offset != 0 {
+                    evaluate_skin_pose(deformable.source, pose.clip, pose.time)
+                } else {
+                    Vec::new()
+                };
+
+                Some(DeformedInstance {
+                    constants: DeformConstants {
+                        vertex_count: deformable.source.verts.len() as u32,
+                        source_core_offset: deformable.gpu_mesh.vertex_core_offset,
+                        source_tangent_offset: deformable.gpu_mesh.vertex_tangent_offset,
+                        joints_offset: deformable.joints_offset,
+                        weights_offset: deformable.weights_offset,
+                        morph_target_count: pose.morph_weights.len() as u32,
+                        morph_position_deltas_offset: deformable.morph_position_deltas_offset,
+                        morph_normal_deltas_offset: deformable.morph_normal_deltas_offset,
+                        output_core_offset: copy.vertex_core_offset,
+                        output_prev_pos_offset: copy.vertex_prev_pos_offset,
+                        output_tangent_offset: copy.vertex_tangent_offset,
+                    },
+                    skin_matrices,
+                    morph_weights: pose.morph_weights.clone(),
+                    blases: copy
+                        .blas_descs
+                        .iter()
+                        .zip(&self.mesh_blas[inst.mesh.0])
+                        .filter_map(|(desc, blas)| Some((blas.clone()?, desc.clone()?)))
+                        .collect(),
+                })
+            })
+            .collect();
+
+        deform_meshes(
+            rg,
+            self.vertex_buffer.lock().clone(),
+            self.bindless_descriptor_set,
+            instances,
+        );
+    }
+
+    /// Two per instance: its single-sided triangles at `2 * i`, and the double-sided ones,
+    /// which can be hit from behind, at `2 * i + 1`; see `rt_hit_instance_index` in `rt.hlsl`.
+    /// If the mesh has no triangles of a kind, the other BLAS is masked out in its place.
+    fn ray_tracing_instances(&self) -> Vec<RayTracingInstanceDesc> {
+        self.instances
+            .iter()
+            .flat_map(|inst| {
+                let blases = &self.mesh_blas[inst.mesh.0];
+                // Meshes without triangles are rejected when added.
+                let any_blas = blases
+                    .iter()
+                    .flatten()
+                    .next()
+                    .expect("mesh must have triangles");
+
+                (0..2).map(move |part| RayTracingInstanceDesc {
+                    blas: blases[part].as_ref().unwrap_or(any_blas).clone(),
+                    transformation: inst.transform,
+                    mesh_index: inst.mesh.0 as u32,
+                    mask: if blases[part].is_some() { 0xff } else { 0 },
+                    cull_disable: part == 1,
+                })
+            })
+            .collect()
+    }
+
     pub(crate) fn build_ray_tracing_top_level_acceleration(&mut self) {
         let tlas = self
             .device
             .create_ray_tracing_top_acceleration(
                 &RayTracingTopAccelerationDesc {
                     //instances: self.mesh_blas.iter().collect::<Vec<_>>(),
-                    instances: self
-                        .instances
-                        .iter()
-                        .map(|inst| RayTracingInstanceDesc {
-                            blas: self.mesh_blas[inst.mesh.0].clone(),
-                            transformation: inst.transform,
-                            mesh_index: inst.mesh.0 as u32,
-                        })
-                        .collect::<Vec<_>>(),
+                    instances: self.ray_tracing_instances(),
                     preallocate_bytes: TLAS_PREALLOCATE_BYTES,
                 },
                 &self.accel_scratch,
@@ -867,15 +1541,7 @@ impl WorldRenderer {
             vk_sync::AccessType::AnyShaderReadOther,
         );
 
-        let instances = self
-            .instances
-            .iter()
-            .map(|inst| RayTracingInstanceDesc {
-                blas: self.mesh_blas[inst.mesh.0].clone(),
-                transformation: inst.transform,
-                mesh_index: inst.mesh.0 as u32,
-            })
-            .collect::<Vec<_>>();
+        let instances = self.ray_tracing_instances();
 
         let mut pass = rg.add_pass("rebuild tlas");
         let tlas_ref = pass.write(&mut tlas, AccessType::TransferWrite);
@@ -965,6 +1631,8 @@ impl WorldRenderer {
             image_lut.compute_if_needed(rg);
         }
 
+        self.prepare_mesh_deformation(rg);
+
         match self.render_mode {
             RenderMode::Standard => {
                 if USE_TAA_JITTER {
@@ -1049,6 +1717,11 @@ impl WorldRenderer {
                             .scale_radiance(emissive_multiplier)
                     })
             })
+            .chain(
+                self.punctual_lights
+                    .iter()
+                    .flat_map(|(_, light)| light.proxy_triangle_lights()),
+            )
             .collect();
 
         // Initialize constants for the maximum allowed cascade count, even if we're not using them,
@@ -1109,6 +1782,50 @@ impl WorldRenderer {
     }
 }
 
+/// Puts the single-sided triangles first, so that they can be drawn with back-face culling,
+/// and the double-sided ones without, using two ranges of the same index buffer.
+/// Returns the reordered indices, and how many of them belong to single-sided triangles.
+fn partition_by_sidedness(
+    indices: &[u32],
+    material_ids: &[u32],
+    double_sided_materials: &[bool],
+) -> (Vec<u32>, usize) {
+    let (single_sided, double_sided): (Vec<&[u32]>, Vec<&[u32]>) = indices
+        .chunks_exact(3)
+        .partition(|tri| !double_sided_materials[material_ids[tri[0] as usize] as usize]);
+
+    let single_sided_index_count = single_sided.len() * 3;
+    let indices: Vec<u32> = single_sided
+        .into_iter()
+        .chain(double_sided)
+        .flatten()
+        .copied()
+        .collect();
+
+    (indices, single_sided_index_count)
+}
+
+/// Puts the non-opaque triangles first, so that they can go into a separate BLAS geometry.
+/// Returns the reordered indices, and how many of them belong to non-opaque triangles.
+fn partition_by_opacity(
+    indices: &[u32],
+    is_non_opaque_triangle: impl Fn(&[u32]) -> bool,
+) -> (Vec<u32>, usize) {
+    let (non_opaque, opaque): (Vec<&[u32]>, Vec<&[u32]>) = indices
+        .chunks_exact(3)
+        .partition(|tri| is_non_opaque_triangle(tri));
+
+    let non_opaque_index_count = non_opaque.len() * 3;
+    let indices: Vec<u32> = non_opaque
+        .into_iter()
+        .chain(opaque)
+        .flatten()
+        .copied()
+        .collect();
+
+    (indices, non_opaque_index_count)
+}
+
 fn radical_inverse(mut n: u32, base: u32) -> f32 {
     let mut val = 0.0f32;
     let inv_base = 1.0f32 / base as f32;
@@ -1123,3 +1840,47 @@ fn radical_inverse(mut n: u32, base: u32) -> f32 {
 
     val
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partition_by_sidedness_puts_single_sided_triangles_first() {
+        // Vertices 0..3 use the double-sided material 1, and 3..6 the single-sided material 0.
+        let material_ids = [1, 1, 1, 0, 0, 0];
+        let double_sided_materials = [false, true];
+
+        // As in a level of detail, triangles refer to a subset of the vertices.
+        let indices = [0, 1, 2, 3, 4, 5, 2, 1, 0, 5, 3, 4];
+        let (partitioned, single_sided_index_count) =
+            partition_by_sidedness(&indices, &material_ids, &double_sided_materials);
+
+        assert_eq!(single_sided_index_count, 6);
+        assert_eq!(partitioned, [3, 4, 5, 5, 3, 4, 0, 1, 2, 2, 1, 0]);
+
+        let (single_sided, double_sided) = partitioned.split_at(single_sided_index_count);
+        assert!(single_sided.iter().all(|&v| material_ids[v as usize] == 0));
+        assert!(double_sided.iter().all(|&v| material_ids[v as usize] == 1));
+
+        let (partitioned, single_sided_index_count) =
+            partition_by_sidedness(&indices[6..9], &material_ids, &double_sided_materials);
+        assert_eq!(single_sided_index_count, 0);
+        assert_eq!(partitioned, [2, 1, 0]);
+    }
+
+    #[test]
+    fn partition_by_opacity_puts_non_opaque_triangles_first() {
+        // Vertex 3 is only used by non-opaque triangles.
+        let indices = [0, 1, 2, 3, 1, 2, 2, 1, 0, 2, 3, 0];
+        let (partitioned, non_opaque_index_count) =
+            partition_by_opacity(&indices, |tri| tri.contains(&3));
+
+        assert_eq!(non_opaque_index_count, 6);
+        assert_eq!(partitioned, [3, 1, 2, 2, 3, 0, 0, 1, 2, 2, 1, 0]);
+
+        let (partitioned, non_opaque_index_count) = partition_by_opacity(&indices, |_| false);
+        assert_eq!(non_opaque_index_count, 0);
+        assert_eq!(partitioned, indices);
+    }
+}
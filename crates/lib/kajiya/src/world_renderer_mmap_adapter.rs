@@ -1,6 +1,6 @@
 use kajiya_asset::mesh::PackedTriMesh;
 
-use crate::world_renderer::{AddMeshOptions, MeshHandle, WorldRenderer};
+use crate::world_renderer::{AddMeshOptions, MeshHandle, WorldRenderer, mmapped_gpu_image_asset};
 
 impl WorldRenderer {
     pub fn add_baked_mesh(
@@ -8,9 +8,21 @@ impl WorldRenderer {
         path: impl Into<std::path::PathBuf>,
         opts: AddMeshOptions,
     ) -> anyhow::Result<MeshHandle> {
-        Ok(self.add_mesh(
-            crate::mmap::mmapped_asset::<PackedTriMesh::Flat, _>(path)?,
-            opts,
-        ))
+        let path = path.into();
+        let mesh = crate::mmap::mmapped_asset::<PackedTriMesh::Flat, _>(&path)?;
+
+        // Ray tracing needs a BLAS for every instance, and one can't be built without triangles.
+        anyhow::ensure!(
+            !mesh.indices.is_empty(),
+            "The mesh {:?} has no triangles",
+            path
+        );
+
+        // Check the images up front, so that a bad one fails the load instead of `add_mesh`.
+        for map in mesh.maps.iter() {
+            mmapped_gpu_image_asset(*map)?;
+        }
+
+        Ok(self.add_mesh(mesh, opts))
     }
 }
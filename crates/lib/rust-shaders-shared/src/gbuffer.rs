@@ -1,8 +1,6 @@
 use crate::util::*;
 use glam::*;
 
-#[cfg(target_arch = "spirv")]
-use spirv_std::float::{f16x2_to_vec2, vec2_to_f16x2};
 #[cfg(target_arch = "spirv")]
 use spirv_std::num_traits::Float;
 
@@ -19,6 +17,8 @@ pub struct GbufferData {
     pub normal: Vec3,
     pub roughness: f32,
     pub metalness: f32,
+    pub clearcoat: f32,
+    pub clearcoat_roughness: f32,
 }
 
 pub fn roughness_to_perceptual_roughness(r: f32) -> f32 {
@@ -39,10 +39,15 @@ impl GbufferData {
             v: UVec4::new(
                 pack_color_888(self.albedo),
                 pack_normal_11_10_11(self.normal).to_bits(),
-                vec2_to_f16x2(vec2(
-                    roughness_to_perceptual_roughness(self.roughness),
-                    self.metalness,
-                )),
+                // Perceptual roughness: 12 bits, metalness: 6, clearcoat: 6,
+                // clearcoat perceptual roughness: 8
+                pack_unorm(roughness_to_perceptual_roughness(self.roughness), 12)
+                    | (pack_unorm(self.metalness, 6) << 12)
+                    | (pack_unorm(self.clearcoat, 6) << 18)
+                    | (pack_unorm(
+                        roughness_to_perceptual_roughness(self.clearcoat_roughness),
+                        8,
+                    ) << 24),
                 float3_to_rgb9e5(self.emissive),
             ),
         }
@@ -51,17 +56,14 @@ impl GbufferData {
 
 impl GbufferDataPacked {
     pub fn unpack(&self) -> GbufferData {
-        #[cfg(not(target_arch = "spirv"))]
-        let roughness_metalness: Vec2 = Vec2::new(0.0, 0.0);
-        #[cfg(target_arch = "spirv")]
-        let roughness_metalness: Vec2 = f16x2_to_vec2(self.v.z);
-
         GbufferData {
             albedo: self.unpack_albedo(),
             emissive: rgb9e5_to_float3(self.v.w),
             normal: self.unpack_normal(),
-            roughness: perceptual_roughness_to_roughness(roughness_metalness.x),
-            metalness: roughness_metalness.y,
+            roughness: perceptual_roughness_to_roughness(unpack_unorm(self.v.z, 12)),
+            metalness: unpack_unorm(self.v.z >> 12, 6),
+            clearcoat: unpack_unorm(self.v.z >> 18, 6),
+            clearcoat_roughness: perceptual_roughness_to_roughness(unpack_unorm(self.v.z >> 24, 8)),
         }
     }
 
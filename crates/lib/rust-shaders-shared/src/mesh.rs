@@ -11,6 +11,11 @@ pub struct MeshDescriptor {
     pub vertex_tangent_offset: u32,
     pub mat_data_offset: u32,
     pub index_offset: u32,
+    pub vertex_uv1_offset: u32,
+    pub vertex_prev_pos_offset: u32,
+    pub single_sided_triangle_count: u32,
+    pub single_sided_non_opaque_triangle_count: u32,
+    pub double_sided_non_opaque_triangle_count: u32,
 }
 
 #[repr(C, align(16))]
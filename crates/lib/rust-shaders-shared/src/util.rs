@@ -145,12 +145,12 @@ pub fn cs_to_uv(cs: Vec2) -> Vec2 {
     cs * Vec2::new(0.5, -0.5) + Vec2::new(0.5, 0.5)
 }
 
-fn pack_unorm(val: f32, bit_count: u32) -> u32 {
+pub fn pack_unorm(val: f32, bit_count: u32) -> u32 {
     let max_val = (1u32 << bit_count) - 1;
     (val.clamp(0.0, 1.0) * max_val as f32) as u32
 }
 
-fn unpack_unorm(pckd: u32, bit_count: u32) -> f32 {
+pub fn unpack_unorm(pckd: u32, bit_count: u32) -> f32 {
     let max_val = (1u32 << bit_count) - 1;
     (pckd & max_val) as f32 / max_val as f32
 }
@@ -1,4 +1,4 @@
-use glam::{UVec4, Vec2, Vec3};
+use glam::{UVec4, Vec3};
 use rust_shaders_shared::util;
 
 #[repr(C)]
@@ -9,6 +9,8 @@ pub struct GBufferData {
     pub normal: Vec3,
     pub roughness: f32,
     pub metalness: f32,
+    pub clearcoat: f32,
+    pub clearcoat_roughness: f32,
 }
 
 impl GBufferData {
@@ -18,12 +20,14 @@ impl GBufferData {
         res.x = util::pack_color_888(self.albedo);
         res.y = util::pack_normal_11_10_11(self.normal) as u32;
 
-        let roughness_metalness = Vec2::new(
-            util::roughness_to_perceptual_roughness(self.roughness),
-            self.metalness,
-        );
-
-        res.z = spirv_std::float::vec2_to_f16x2(roughness_metalness);
+        // Perceptual roughness: 12 bits, metalness: 6, clearcoat: 6, clearcoat perceptual roughness: 8
+        res.z = util::pack_unorm(util::roughness_to_perceptual_roughness(self.roughness), 12)
+            | (util::pack_unorm(self.metalness, 6) << 12)
+            | (util::pack_unorm(self.clearcoat, 6) << 18)
+            | (util::pack_unorm(
+                util::roughness_to_perceptual_roughness(self.clearcoat_roughness),
+                8,
+            ) << 24);
         res.w = util::float3_to_rgb9e5(self.emissive);
 
         res